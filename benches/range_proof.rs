@@ -0,0 +1,69 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use jagguar::protocols::opa::{InputRangeProof, NormBound, NormProof};
+
+const SESSION_ID: u64 = 1;
+const ROUND_ID: u64 = 0;
+
+// Packed input words as clients submit them; every value fits the narrowest width.
+fn input(len: usize) -> Vec<u32> {
+    (0..len as u32).map(|i| i.wrapping_mul(2_654_435_761) >> 24).collect()
+}
+
+// The masked words a proof is bound to; their values do not change the cost.
+fn masked(len: usize) -> Vec<u128> {
+    (0..len as u128).map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835)).collect()
+}
+
+fn benchmark_range_proof(c: &mut Criterion) {
+    let mut group = c.benchmark_group("range_proof");
+    group.sample_size(10);
+
+    for bits in [8, 32] {
+        for len in [64, 1024] {
+            let (values, masked) = (input(len), masked(len));
+            let (proof, _) = InputRangeProof::prove(&values, bits, SESSION_ID, ROUND_ID, &masked).unwrap();
+            // what each client adds to its submission, next to the 16 bytes per masked word
+            println!("range_proof/{}_bits/{}: {} bytes", bits, len, proof.to_bytes().len());
+
+            let label = format!("{}_bits", bits);
+            group.throughput(Throughput::Elements(len as u64));
+            group.bench_with_input(BenchmarkId::new(format!("prove/{}", label), len), &values, |b, values| {
+                b.iter(|| {
+                    black_box(InputRangeProof::prove(values, bits, SESSION_ID, ROUND_ID, &masked).unwrap())
+                });
+            });
+            group.bench_with_input(BenchmarkId::new(format!("verify/{}", label), len), &proof, |b, proof| {
+                b.iter(|| black_box(proof.verify(bits, len, SESSION_ID, ROUND_ID, &masked).unwrap()));
+            });
+        }
+    }
+    group.finish();
+}
+
+fn benchmark_norm_proof(c: &mut Criterion) {
+    let mut group = c.benchmark_group("norm_proof");
+    group.sample_size(10);
+
+    for (label, bound) in [("l2", NormBound::L2(u32::MAX)), ("linf", NormBound::LInf(255))] {
+        for len in [64, 1024] {
+            let (values, masked) = (input(len), masked(len));
+            let (range, blindings) = InputRangeProof::prove(&values, 32, SESSION_ID, ROUND_ID, &masked).unwrap();
+            let committed = range.verify(32, len, SESSION_ID, ROUND_ID, &masked).unwrap();
+            let proof = NormProof::prove(&values, &blindings, bound, SESSION_ID, ROUND_ID).unwrap();
+            // added to the submission on top of the range proof
+            println!("norm_proof/{}/{}: {} bytes", label, len, proof.to_bytes().len());
+
+            group.throughput(Throughput::Elements(len as u64));
+            group.bench_with_input(BenchmarkId::new(format!("prove/{}", label), len), &values, |b, values| {
+                b.iter(|| black_box(NormProof::prove(values, &blindings, bound, SESSION_ID, ROUND_ID).unwrap()));
+            });
+            group.bench_with_input(BenchmarkId::new(format!("verify/{}", label), len), &proof, |b, proof| {
+                b.iter(|| proof.verify(&committed, bound, SESSION_ID, ROUND_ID).unwrap());
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_range_proof, benchmark_norm_proof);
+criterion_main!(benches);
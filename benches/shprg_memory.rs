@@ -10,7 +10,7 @@ const ONE_MIB: u64 = 1024 * 1024;
 fn elements_for_bytes(target_bytes: u64, bits_per_element: u32) -> usize {
     let target_bits = target_bytes.saturating_mul(8);
     let bits_per_element = u64::from(bits_per_element);
-    ((target_bits + bits_per_element - 1) / bits_per_element) as usize
+    target_bits.div_ceil(bits_per_element) as usize
 }
 
 fn main() {
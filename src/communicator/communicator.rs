@@ -1,32 +1,380 @@
-use std::net::{TcpListener, TcpStream, SocketAddr};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::io::{self, Cursor, Read, Write};
 use std::thread;
+use std::time::{Duration, Instant};
 use socket2::{Socket, Domain, Type};
+use ed25519_dalek::SigningKey;
+
+use crate::communicator::signing::{AddressBook, ENVELOPE_LEN, Envelope};
+
+/// Identifies one of the independent sessions multiplexed over a listener.
+pub type SessionId = u64;
+/// Session used by communicators that never pick one.
+pub const DEFAULT_SESSION: SessionId = 0;
+
+type SignalCallback = Arc<dyn Fn(TcpStream, u16) + Send + Sync>;
+type CommitteeCompleteCallback = Arc<dyn Fn(Vec<Vec<u8>>) + Send + Sync>;
+/// Decides whether a committee message may be queued at all, e.g. based on the round phase.
+type CommitteeMessageFilter = Arc<dyn Fn(&[u8]) -> io::Result<()> + Send + Sync>;
+/// Consumes a regular (non-signal, non-committee) message directly from the socket.
+/// Returns the bytes, if any, that should still be buffered in `received_messages`.
+/// The outcome is reported back to the sender, see `Communicator::send_to_server_with_reply`.
+type MessageStreamCallback =
+    Arc<dyn Fn(&mut dyn Read, SocketAddr) -> io::Result<Option<Vec<u8>>> + Send + Sync>;
+/// Answers a request message with the bytes to send back, see `Communicator::request_from_server`.
+type RequestCallback = Arc<dyn Fn(&[u8], SocketAddr) -> io::Result<Vec<u8>> + Send + Sync>;
+/// Decides whether a signed message's round id is current.
+type RoundCheck = Arc<dyn Fn(u64) -> io::Result<()> + Send + Sync>;
+
+/// Bounds applied to every inbound connection so a single peer cannot exhaust server memory.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageLimits {
+    /// Largest message, in bytes, accepted on a single connection.
+    pub max_message_size: usize,
+    /// Upper bound on the total bytes held in the received and committee buffers.
+    pub max_buffered_bytes: usize,
+    /// Wall-clock budget for a peer to deliver its entire message.
+    pub read_deadline: Duration,
+    /// Longest a single read may stall before the peer is dropped.
+    pub idle_timeout: Duration,
+    /// Most connections handled at once; further ones wait in the listen backlog until
+    /// a handler is free.
+    pub max_connections: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_message_size: 16 * 1024 * 1024,
+            max_buffered_bytes: 1024 * 1024 * 1024,
+            read_deadline: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(5),
+            max_connections: 256,
+        }
+    }
+}
+
+/// When queued committee outputs are handed to the completion callback: as soon as
+/// `expected` outputs are in, or `grace_period` after the first `threshold` arrived.
+#[derive(Debug, Clone, Copy)]
+pub struct CommitteeCompletion {
+    /// Outputs required before the round may complete.
+    pub threshold: usize,
+    /// Full committee size; the round completes immediately once all of them report.
+    pub expected: usize,
+    /// How long to keep waiting for stragglers after the threshold is reached.
+    pub grace_period: Duration,
+}
+
+/// Wraps a reader so that at most `limit` bytes are returned and the whole read
+/// finishes before `deadline`. Exceeding either bound surfaces as an `io::Error`.
+pub struct BoundedReader<R> {
+    inner: R,
+    remaining: usize,
+    deadline: Instant,
+}
+
+impl<R: Read> BoundedReader<R> {
+    pub fn new(inner: R, limit: usize, deadline: Instant) -> Self {
+        Self { inner, remaining: limit, deadline }
+    }
+}
+
+impl<R: Read> Read for BoundedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if Instant::now() > self.deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "message read deadline exceeded"));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            // probe for a trailing byte so oversize messages are rejected rather than truncated
+            let mut probe = [0u8; 1];
+            return match self.inner.read(&mut probe)? {
+                0 => Ok(0),
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "message exceeds size limit")),
+            };
+        }
+        let max = buf.len().min(self.remaining);
+        let n = self.inner.read(&mut buf[..max])?;
+        self.remaining -= n;
+        Ok(n)
+    }
+}
+
+/// Shared accounting of buffered bytes against `MessageLimits::max_buffered_bytes`.
+/// Cloned handles refer to the same budget, so data retained outside the communicator
+/// (e.g. by a stream callback) can be charged against the same limit.
+#[derive(Debug, Clone)]
+pub struct BufferBudget {
+    used: Arc<AtomicUsize>,
+    max: Arc<AtomicUsize>,
+}
+
+impl BufferBudget {
+    fn new(max: usize) -> Self {
+        Self {
+            used: Arc::new(AtomicUsize::new(0)),
+            max: Arc::new(AtomicUsize::new(max)),
+        }
+    }
+
+    /// Reserve room for `len` more buffered bytes, failing if the budget would be exceeded.
+    pub fn reserve(&self, len: usize) -> io::Result<()> {
+        let max = self.max.load(Ordering::SeqCst);
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(len).filter(|&total| total <= max)
+            })
+            .map(|_| ())
+            .map_err(|_| io::Error::new(io::ErrorKind::OutOfMemory, "message buffer limit reached"))
+    }
+
+    /// Reserve room for `len` more buffered bytes until the returned guard is dropped.
+    fn reserve_scoped(&self, len: usize) -> io::Result<Reservation> {
+        self.reserve(len)?;
+        Ok(Reservation { budget: self.clone(), len })
+    }
+
+    pub fn release(&self, len: usize) {
+        self.used.fetch_sub(len, Ordering::SeqCst);
+    }
+
+    /// Number of bytes currently charged against the budget.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+}
+
+/// Bytes reserved from a `BufferBudget` for as long as the reservation lives, e.g. the
+/// declared length of a message while its connection is handled.
+struct Reservation {
+    budget: BufferBudget,
+    len: usize,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.release(self.len);
+    }
+}
+
+/// Connections being handled, counted against `MessageLimits::max_connections`.
+#[derive(Debug, Clone, Default)]
+struct ConnectionSlots {
+    active: Arc<AtomicUsize>,
+}
+
+impl ConnectionSlots {
+    /// Take one of `max` slots, if any is free; it is freed again when dropped.
+    fn try_acquire(&self, max: usize) -> Option<ConnectionSlot> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| (active < max).then_some(active + 1))
+            .ok()
+            .map(|_| ConnectionSlot { active: Arc::clone(&self.active) })
+    }
+}
+
+struct ConnectionSlot {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Handlers and buffers of one session. Every connection opens with the id of the session
+/// it belongs to and is only ever dispatched to that session's handlers, so sessions sharing
+/// a listener never see each other's messages; only the `BufferBudget` is shared.
+#[derive(Clone, Default)]
+pub struct Session {
+    messages: Arc<Mutex<Vec<Vec<u8>>>>,
+    committee_messages: Arc<Mutex<Vec<Vec<u8>>>>,
+    signal_callback: Option<SignalCallback>,
+    stream_callback: Option<MessageStreamCallback>,
+    request_callback: Option<RequestCallback>,
+    committee_completion: Option<CommitteeCompletion>,
+    /// Number of committee batches handed to the completion callback so far.
+    committee_batch: Arc<AtomicU64>,
+    committee_complete_callback: Option<CommitteeCompleteCallback>,
+    committee_filter: Option<CommitteeMessageFilter>,
+    /// Keys every message must be signed with, by the port naming the sender.
+    address_book: Option<Arc<AddressBook>>,
+    round_check: Option<RoundCheck>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_signal_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(TcpStream, u16) + Send + Sync + 'static,
+    {
+        self.signal_callback = Some(Arc::new(callback));
+    }
+
+    /// Decode regular messages straight off the socket instead of buffering them whole.
+    /// The reader handed to the callback enforces the configured `MessageLimits`.
+    pub fn set_stream_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&mut dyn Read, SocketAddr) -> io::Result<Option<Vec<u8>>> + Send + Sync + 'static,
+    {
+        self.stream_callback = Some(Arc::new(callback));
+    }
+
+    /// Answer request messages, whose sender waits for the bytes the callback returns.
+    pub fn set_request_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&[u8], SocketAddr) -> io::Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.request_callback = Some(Arc::new(callback));
+    }
+
+    /// Only take messages signed by the party their envelope names in `address_book`; the
+    /// signature is verified before any callback sees the message, and callbacks see the
+    /// signer's port in place of the port the connection came from. Stream callbacks then
+    /// get the message once it is whole, rather than straight off the socket.
+    pub fn set_address_book(&mut self, address_book: AddressBook) {
+        self.address_book = Some(Arc::new(address_book));
+    }
+
+    /// Reject signed messages for a round other than the ones `check` accepts.
+    pub fn set_round_check<F>(&mut self, check: F)
+    where
+        F: Fn(u64) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.round_check = Some(Arc::new(check));
+    }
+
+    /// Complete only once exactly `size` committee outputs have been received.
+    pub fn set_committee_expected_size(&mut self, size: usize) {
+        self.set_committee_completion(CommitteeCompletion {
+            threshold: size,
+            expected: size,
+            grace_period: Duration::ZERO,
+        });
+    }
+
+    pub fn set_committee_completion(&mut self, completion: CommitteeCompletion) {
+        assert!(completion.threshold <= completion.expected, "threshold exceeds committee size");
+        self.committee_completion = Some(completion);
+    }
+
+    pub fn set_committee_complete_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(Vec<Vec<u8>>) + Send + Sync + 'static,
+    {
+        self.committee_complete_callback = Some(Arc::new(callback));
+    }
+
+    /// Reject committee messages before they are queued; rejected messages never count
+    /// towards completion.
+    pub fn set_committee_message_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(&[u8]) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.committee_filter = Some(Arc::new(filter));
+    }
+
+    /// Queue committee messages received before a restart, charging them to `budget`.
+    /// They count towards completion once the session is served again.
+    pub fn restore_committee_messages(&self, messages: Vec<Vec<u8>>, budget: &BufferBudget) -> io::Result<()> {
+        budget.reserve(messages.iter().map(Vec::len).sum())?;
+        self.committee_messages.lock().unwrap().extend(messages);
+        Ok(())
+    }
+
+    pub fn get_received_messages(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
+        Arc::clone(&self.messages)
+    }
+
+    pub fn get_committee_messages(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
+        Arc::clone(&self.committee_messages)
+    }
+}
+
+/// Sessions served by a listener, keyed by id. Cloned handles refer to the same table,
+/// so sessions can be opened and closed while the listener is running.
+#[derive(Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<RwLock<HashMap<SessionId, Arc<Session>>>>,
+}
+
+impl SessionRegistry {
+    /// Start dispatching connections for `id` to `session`.
+    pub fn register(&self, id: SessionId, session: Session) -> io::Result<()> {
+        let mut sessions = self.sessions.write().unwrap();
+        if sessions.contains_key(&id) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("session {} already exists", id)));
+        }
+        sessions.insert(id, Arc::new(session));
+        Ok(())
+    }
+
+    /// Stop serving `id`; later connections for it are refused. Returns whether it existed.
+    pub fn remove(&self, id: SessionId) -> bool {
+        self.sessions.write().unwrap().remove(&id).is_some()
+    }
+
+    pub fn contains(&self, id: SessionId) -> bool {
+        self.sessions.read().unwrap().contains_key(&id)
+    }
+
+    fn get(&self, id: SessionId) -> Option<Arc<Session>> {
+        self.sessions.read().unwrap().get(&id).cloned()
+    }
+}
+
+/// Everything a connection handler thread needs, cloned once per accepted connection.
+#[derive(Clone)]
+struct ConnectionContext {
+    limits: MessageLimits,
+    budget: BufferBudget,
+    sessions: SessionRegistry,
+}
 
 pub struct Communicator {
     port: u16,
+    /// Host the server runs on; messages go to `server_ip` at the given server port.
+    server_ip: IpAddr,
     listener: Option<TcpListener>,
     shutdown: Option<Arc<AtomicBool>>,
-    received_messages: Arc<Mutex<Vec<Vec<u8>>>>,
-    committee_messages: Arc<Mutex<Vec<Vec<u8>>>>,
-    signal_callback: Option<Arc<dyn Fn(TcpStream, u16) + Send + Sync>>,
-	committee_expected_size: Option<usize>,
-	committee_complete_callback: Option<Arc<dyn Fn(Vec<Vec<u8>>) + Send + Sync>>,
+    limits: MessageLimits,
+    budget: BufferBudget,
+    connections: ConnectionSlots,
+    /// Session this communicator sends in and, when listening, serves with `session`.
+    session_id: SessionId,
+    session: Session,
+    sessions: SessionRegistry,
+    /// Key outgoing messages are signed with, if the server checks signatures.
+    signing_key: Option<SigningKey>,
+    /// Round id outgoing messages are signed for.
+    round_id: u64,
 }
 
 impl Communicator {
     pub fn new(port: u16) -> Self {
         Self {
             port,
+            server_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
             listener: None,
             shutdown: None,
-            received_messages: Arc::new(Mutex::new(Vec::new())),
-            committee_messages: Arc::new(Mutex::new(Vec::new())),
-            signal_callback: None,
-			committee_expected_size: None,
-			committee_complete_callback: None,
+            limits: MessageLimits::default(),
+            budget: BufferBudget::new(MessageLimits::default().max_buffered_bytes),
+            connections: ConnectionSlots::default(),
+            session_id: DEFAULT_SESSION,
+            session: Session::new(),
+            sessions: SessionRegistry::default(),
+            signing_key: None,
+            round_id: 0,
         }
     }
 
@@ -34,22 +382,102 @@ impl Communicator {
         self.port
     }
 
+    /// Reach the server on another host instead of this one.
+    pub fn set_server_ip(&mut self, ip: IpAddr) {
+        self.server_ip = ip;
+    }
+
+    pub fn set_limits(&mut self, limits: MessageLimits) {
+        self.limits = limits;
+        self.budget.max.store(limits.max_buffered_bytes, Ordering::SeqCst);
+    }
+
+    pub fn limits(&self) -> MessageLimits {
+        self.limits
+    }
+
+    /// Send every outgoing message in session `id`, and serve that session when listening.
+    pub fn set_session_id(&mut self, id: SessionId) {
+        self.session_id = id;
+    }
+
+    /// Sign every outgoing message with `key`, for a server whose session has this
+    /// communicator's port in its address book.
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.signing_key = Some(key);
+    }
+
+    /// Round outgoing messages are signed for.
+    pub fn set_round(&mut self, round_id: u64) {
+        self.round_id = round_id;
+    }
+
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    /// This communicator's own session, served under `session_id` once listening starts
+    /// unless a session with that id was registered explicitly.
+    pub fn session_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+
+    /// Handle for opening further sessions on this communicator's listener.
+    pub fn sessions(&self) -> SessionRegistry {
+        self.sessions.clone()
+    }
+
     pub fn set_signal_callback<F>(&mut self, callback: F)
     where
         F: Fn(TcpStream, u16) + Send + Sync + 'static,
     {
-        self.signal_callback = Some(Arc::new(callback));
+        self.session.set_signal_callback(callback);
+    }
+
+    /// Decode regular messages straight off the socket instead of buffering them whole.
+    /// The reader handed to the callback enforces the configured `MessageLimits`.
+    pub fn set_stream_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&mut dyn Read, SocketAddr) -> io::Result<Option<Vec<u8>>> + Send + Sync + 'static,
+    {
+        self.session.set_stream_callback(callback);
     }
 
+    /// Answer request messages, whose sender waits for the bytes the callback returns.
+    pub fn set_request_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&[u8], SocketAddr) -> io::Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.session.set_request_callback(callback);
+    }
+
+    pub fn set_address_book(&mut self, address_book: AddressBook) {
+        self.session.set_address_book(address_book);
+    }
+
+	/// Complete only once exactly `size` committee outputs have been received.
 	pub fn set_committee_expected_size(&mut self, size: usize) {
-		self.committee_expected_size = Some(size);
+		self.session.set_committee_expected_size(size);
+	}
+
+	pub fn set_committee_completion(&mut self, completion: CommitteeCompletion) {
+		self.session.set_committee_completion(completion);
 	}
 
 	pub fn set_committee_complete_callback<F>(&mut self, callback: F)
 	where
 		F: Fn(Vec<Vec<u8>>) + Send + Sync + 'static,
 	{
-		self.committee_complete_callback = Some(Arc::new(callback));
+		self.session.set_committee_complete_callback(callback);
+	}
+
+	/// Reject committee messages before they are queued; rejected messages never count
+	/// towards completion.
+	pub fn set_committee_message_filter<F>(&mut self, filter: F)
+	where
+		F: Fn(&[u8]) -> io::Result<()> + Send + Sync + 'static,
+	{
+		self.session.set_committee_message_filter(filter);
 	}
 
     pub fn set_shutdown_flag(&mut self, shutdown: Arc<AtomicBool>) {
@@ -57,37 +485,47 @@ impl Communicator {
     }
 
     pub fn start_server(&mut self) -> std::io::Result<()> {
-        let listener = TcpListener::bind(format!("0.0.0.0:{}", self.port))?;
+        self.start_server_at(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+
+    /// Listen on `ip` only, rather than on every interface.
+    pub fn start_server_at(&mut self, ip: IpAddr) -> std::io::Result<()> {
+        let listener = TcpListener::bind(SocketAddr::new(ip, self.port))?;
         self.listener = Some(listener);
         Ok(())
     }
 
-    pub fn listen_loop(&self) -> std::io::Result<()> {
+    fn connection_context(&self) -> ConnectionContext {
+        ConnectionContext {
+            limits: self.limits,
+            budget: self.budget.clone(),
+            sessions: self.sessions.clone(),
+        }
+    }
+
+    pub fn listen_loop(&mut self) -> std::io::Result<()> {
+        if !self.sessions.contains(self.session_id) {
+            self.sessions.register(self.session_id, self.session.clone())?;
+        }
         let _ = self.listener.as_ref().unwrap().set_nonblocking(true);
         loop {
-            if let Some(ref shutdown) = self.shutdown {
-                if shutdown.load(Ordering::Relaxed) {
-                    println!("Server shutting down gracefully");
-                    break;
-                }
+            if let Some(ref shutdown) = self.shutdown
+                && shutdown.load(Ordering::Relaxed)
+            {
+                println!("Server shutting down gracefully");
+                break;
             }
+            // leave further connections in the backlog while every handler is busy
+            let Some(slot) = self.connections.try_acquire(self.limits.max_connections) else {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                continue;
+            };
             match self.listener.as_ref().unwrap().accept() {
                 Ok((stream, addr)) => {
-                    let messages = Arc::clone(&self.received_messages);
-                    let committee_messages = Arc::clone(&self.committee_messages);
-                    let callback = self.signal_callback.clone();
-					let committee_expected_size = self.committee_expected_size;
-					let committee_complete_callback = self.committee_complete_callback.clone();
+                    let context = self.connection_context();
                     thread::spawn(move || {
-						Self::handle_connection(
-							stream,
-							addr,
-							messages,
-							committee_messages,
-							callback,
-							committee_expected_size,
-							committee_complete_callback,
-						);
+						Self::handle_connection(stream, addr, context);
+						drop(slot);
                     });
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -101,98 +539,341 @@ impl Communicator {
         Ok(())
     }
 
-    fn handle_connection(
+    fn handle_connection(stream: TcpStream, addr: SocketAddr, context: ConnectionContext) {
+        if let Err(e) = Self::dispatch_connection(stream, addr, &context) {
+            eprintln!("Rejected connection from {:?}: {}", addr, e);
+        }
+    }
+
+    fn dispatch_connection(
         mut stream: TcpStream,
         addr: SocketAddr,
-        messages: Arc<Mutex<Vec<Vec<u8>>>>,
-        committee_messages: Arc<Mutex<Vec<Vec<u8>>>>,
-        callback: Option<Arc<dyn Fn(TcpStream, u16) + Send + Sync>>,
-		committee_expected_size: Option<usize>,
-		committee_complete_callback: Option<Arc<dyn Fn(Vec<Vec<u8>>) + Send + Sync>>,
-    ) {
+        context: &ConnectionContext,
+    ) -> io::Result<()> {
+        // accepted sockets must block so the idle timeout applies to every read
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(context.limits.idle_timeout))?;
+        let deadline = Instant::now() + context.limits.read_deadline;
+
+        // every connection opens with the id of its session and the length of its message
+        let mut header = [0u8; 12];
+        BoundedReader::new(&mut stream, header.len(), deadline).read_exact(&mut header)?;
+        let session_id = SessionId::from_le_bytes(header[..8].try_into().unwrap());
+        let message_len = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+        let session = context.sessions.get(session_id);
+        let unknown_session =
+            || io::Error::new(io::ErrorKind::NotFound, format!("unknown session {}", session_id));
+        if message_len > context.limits.max_message_size {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "message exceeds size limit"));
+        }
+        // the declared length counts against the budget until the connection is done, so
+        // slow senders cannot hold more than the budget between them; whatever a handler
+        // retains is charged again by the handler
+        let _reservation = context.budget.reserve_scoped(message_len)?;
+
+        // everything is bounded by the declared length, including the prefix; a session
+        // checking signatures reads the message whole and verifies it before looking at it
+        let bounded = BoundedReader::new(stream.try_clone()?, message_len, deadline);
+        let (mut reader, addr): (Box<dyn Read>, SocketAddr) = match session {
+            Some(ref session) if session.address_book.is_some() => {
+                let (body, sender) = Self::read_signed(bounded, &mut stream, addr, session_id, session)?;
+                // the signer is the party, whichever port its connection came from
+                (Box::new(Cursor::new(body)), SocketAddr::new(addr.ip(), sender))
+            }
+            _ => (Box::new(bounded), addr),
+        };
+
         // read exactly 6 bytes to check if it's a signal
-        let mut signal_buffer = vec![0u8; 6];
-        match stream.read_exact(&mut signal_buffer) {
-            Ok(_) => {
-                if signal_buffer.starts_with(b"signal") {
-                    // it's a signal - call the callback with the stream
-                    println!("Signal received from {:?}", addr);
-                    if let Some(ref cb) = callback {
-                        cb(stream, addr.port());
-                    }
-                    return;
-                }
-                // not a signal - read the rest and combine with what we already read
-                let mut rest = Vec::new();
-                let _ = stream.read_to_end(&mut rest);
-                signal_buffer.extend_from_slice(&rest);
-                
-                // check if it's a committee message
-                if signal_buffer.starts_with(b"committee") {
-					let mut committee_queue = committee_messages.lock().unwrap();
-					committee_queue.push(signal_buffer);
-					let current_len = committee_queue.len();
-					println!("Committee message queued (total: {} committee messages)", current_len);
-
-					// If we have the expected number of committee messages, trigger the callback
-					if let Some(expected) = committee_expected_size {
-						if current_len == expected {
-							// Take a snapshot and clear the queue before invoking the callback
-							let batch = committee_queue.clone();
-							committee_queue.clear();
-							drop(committee_queue);
-							if let Some(ref cb) = committee_complete_callback {
-								cb(batch);
-							}
-						}
-					}
-                } else {
-                    // regular client message
-                    let mut messages = messages.lock().unwrap();
-                    messages.push(signal_buffer);
-                    println!("Received message from {:?} (total: {} messages)", addr, messages.len());
-                }
+        let mut prefix = vec![0u8; SIGNAL_PREFIX.len()];
+        reader.read_exact(&mut prefix)?;
+        if prefix == SIGNAL_PREFIX {
+            // it's a signal - call the callback with the stream
+            println!("Signal received from {:?}", addr);
+            let session = session.ok_or_else(unknown_session)?;
+            stream.set_read_timeout(None)?;
+            if let Some(ref cb) = session.signal_callback {
+                cb(stream, addr.port());
             }
-            Err(e) => {
-                eprintln!("Failed to read from {:?}: {}", addr, e);
+            return Ok(());
+        }
+
+        // extend the prefix far enough to recognize committee messages
+        let mut tag = [0u8; 3];
+        reader.read_exact(&mut tag)?;
+        prefix.extend_from_slice(&tag);
+
+        // check if it's a committee message
+        if prefix.starts_with(b"committee") {
+            let session = session.ok_or_else(unknown_session)?;
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest)?;
+            prefix.extend_from_slice(&rest);
+            return Self::queue_committee_message(prefix, &session, &context.budget);
+        }
+
+        // regular client messages are answered, so tell the sender its session does not exist
+        let Some(session) = session else {
+            // drain the message first, so closing the socket does not reset the connection
+            io::copy(&mut reader, &mut io::sink())?;
+            let outcome = Err(unknown_session());
+            if let Err(e) = Self::write_reply(&mut stream, outcome.as_ref().map(|()| &[][..])) {
+                eprintln!("Failed to reply to {:?}: {}", addr, e);
+            }
+            return outcome;
+        };
+
+        // requests are answered with the callback's bytes instead of a bare verdict
+        if prefix.starts_with(REQUEST_PREFIX) {
+            let mut request = prefix.split_off(REQUEST_PREFIX.len());
+            reader.read_to_end(&mut request)?;
+            let outcome = match session.request_callback {
+                Some(ref cb) => cb(&request, addr),
+                None => Err(io::Error::new(io::ErrorKind::Unsupported, "session does not answer requests")),
+            };
+            if let Err(e) = Self::write_reply(&mut stream, outcome.as_deref()) {
+                eprintln!("Failed to reply to {:?}: {}", addr, e);
+            }
+            return outcome.map(|_| ());
+        }
+
+        // regular client message, streamed to the owner if it asked for it
+        if let Some(ref cb) = session.stream_callback {
+            let mut chained = Cursor::new(prefix).chain(reader);
+            let outcome = cb(&mut chained, addr)
+                .and_then(|retained| match retained {
+                    Some(retained) => Self::queue_message(retained, addr, &session, &context.budget),
+                    None => Ok(()),
+                });
+            // a message rejected part way is drained, so the sender gets the verdict, not a reset
+            if outcome.is_err() {
+                let _ = io::copy(&mut chained, &mut io::sink());
             }
+            // tell the sender whether its message was accepted; it may already have hung up
+            if let Err(e) = Self::write_reply(&mut stream, outcome.as_ref().map(|()| &[][..])) {
+                eprintln!("Failed to reply to {:?}: {}", addr, e);
+            }
+            return outcome;
+        }
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        prefix.extend_from_slice(&rest);
+        Self::queue_message(prefix, addr, &session, &context.budget)
+    }
+
+    /// Read a signed message whole, `[envelope][body]`, and check that the party its
+    /// envelope names signed it for a current round, telling the sender if not. Returns the
+    /// body and the port naming the signer; `addr` is only where the connection came from.
+    /// A signal is only the prefix, since its sender waits for the answer rather than
+    /// closing its end.
+    fn read_signed(
+        mut reader: impl Read,
+        stream: &mut TcpStream,
+        addr: SocketAddr,
+        session_id: SessionId,
+        session: &Session,
+    ) -> io::Result<(Vec<u8>, u16)> {
+        let mut envelope = [0u8; ENVELOPE_LEN];
+        reader.read_exact(&mut envelope)?;
+        let envelope = Envelope::from_bytes(&envelope);
+        let mut body = vec![0u8; SIGNAL_PREFIX.len()];
+        reader.read_exact(&mut body)?;
+        if body != SIGNAL_PREFIX {
+            reader.read_to_end(&mut body)?;
+        }
+        let address_book = session.address_book.as_ref().expect("session checks signatures");
+        let verified = envelope.verify(address_book, session_id, &body).and_then(|()| {
+            session.round_check.as_ref().map_or(Ok(()), |check| check(envelope.round_id))
+        });
+        if let Err(e) = verified {
+            // a signal's sender reads the answer as data, so it only sees the connection close
+            if body != SIGNAL_PREFIX && let Err(reply_error) = Self::write_reply(stream, Err(&e)) {
+                eprintln!("Failed to reply to {:?}: {}", addr, reply_error);
+            }
+            return Err(e);
+        }
+        Ok((body, envelope.sender))
+    }
+
+    fn queue_message(
+        message: Vec<u8>,
+        addr: SocketAddr,
+        session: &Session,
+        budget: &BufferBudget,
+    ) -> io::Result<()> {
+        budget.reserve(message.len())?;
+        let mut messages = session.messages.lock().unwrap();
+        messages.push(message);
+        println!("Received message from {:?} (total: {} messages)", addr, messages.len());
+        Ok(())
+    }
+
+    fn queue_committee_message(
+        message: Vec<u8>,
+        session: &Arc<Session>,
+        budget: &BufferBudget,
+    ) -> io::Result<()> {
+        if let Some(ref filter) = session.committee_filter {
+            filter(&message)?;
+        }
+        let mut committee_queue = session.committee_messages.lock().unwrap();
+        budget.reserve(message.len())?;
+        committee_queue.push(message);
+        let current_len = committee_queue.len();
+        println!("Committee message queued (total: {} committee messages)", current_len);
+
+        let Some(completion) = session.committee_completion else {
+            return Ok(());
+        };
+        if current_len >= completion.expected
+            || (current_len == completion.threshold && completion.grace_period.is_zero())
+        {
+            Self::complete_committee(committee_queue, session, budget);
+        } else if current_len == completion.threshold {
+            // enough outputs to finish; give stragglers a grace period before completing
+            println!(
+                "Committee threshold reached; waiting up to {:?} for remaining members",
+                completion.grace_period
+            );
+            let session = Arc::clone(session);
+            let budget = budget.clone();
+            let batch = session.committee_batch.load(Ordering::SeqCst);
+            thread::spawn(move || {
+                thread::sleep(completion.grace_period);
+                let committee_queue = session.committee_messages.lock().unwrap();
+                // unless the batch already completed with the full committee
+                if session.committee_batch.load(Ordering::SeqCst) == batch {
+                    Self::complete_committee(committee_queue, &session, &budget);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Hand the queued committee outputs to the completion callback as one batch. The next
+    /// committee message starts a new batch (e.g. for the next round); owners that must
+    /// refuse stragglers from a completed batch do so with a committee message filter.
+    fn complete_committee(
+        mut committee_queue: std::sync::MutexGuard<'_, Vec<Vec<u8>>>,
+        session: &Session,
+        budget: &BufferBudget,
+    ) {
+        session.committee_batch.fetch_add(1, Ordering::SeqCst);
+        // Take the batch out of the queue before invoking the callback
+        let batch = std::mem::take(&mut *committee_queue);
+        drop(committee_queue);
+        budget.release(batch.iter().map(Vec::len).sum());
+        if let Some(ref cb) = session.committee_complete_callback {
+            cb(batch);
         }
     }
 
     pub fn get_received_messages(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
-        Arc::clone(&self.received_messages)
+        self.session.get_received_messages()
     }
 
     pub fn get_committee_messages(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
-        Arc::clone(&self.committee_messages)
+        self.session.get_committee_messages()
     }
 
-    fn connect_to_server(&self, server_port: u16) -> std::io::Result<TcpStream> {
-        // create a socket and bind to our local port
-        let socket = Socket::new(Domain::IPV4, Type::STREAM, None)?;
+    /// Handle to the buffered-bytes budget shared by every connection.
+    pub fn buffer_budget(&self) -> BufferBudget {
+        self.budget.clone()
+    }
+
+    fn connect_to_server(&self, server_port: u16, message_len: usize) -> std::io::Result<TcpStream> {
+        // create a socket and bind to our local port, on loopback when the server is local
+        let server_addr = SocketAddr::new(self.server_ip, server_port);
+        let socket = Socket::new(Domain::for_address(server_addr), Type::STREAM, None)?;
         socket.set_reuse_address(true)?;
-        let local_addr: SocketAddr = format!("127.0.0.1:{}", self.port).parse().unwrap();
-        socket.bind(&local_addr.into())?;
+        let local_ip = match self.server_ip {
+            ip if ip.is_loopback() => ip,
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
+        };
+        socket.bind(&SocketAddr::new(local_ip, self.port).into())?;
 
         // connect to the server's port
-        let server_addr: SocketAddr = format!("127.0.0.1:{}", server_port).parse().unwrap();
         socket.connect(&server_addr.into())?;
 
-        // convert to TcpStream
-        let stream = TcpStream::from(socket);
+        // convert to TcpStream and open with our session id and the message length
+        let message_len = u32::try_from(message_len)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too long"))?;
+        let mut stream = TcpStream::from(socket);
+        stream.write_all(&self.session_id.to_le_bytes())?;
+        stream.write_all(&message_len.to_le_bytes())?;
+        Ok(stream)
+    }
+
+    /// Connect and send `body`, signed if the communicator has a signing key.
+    fn send_message(&self, server_port: u16, body: &[u8]) -> std::io::Result<TcpStream> {
+        let envelope = self
+            .signing_key
+            .as_ref()
+            .map(|key| Envelope::sign(key, self.session_id, self.port, self.round_id, body).to_bytes());
+        let envelope = envelope.as_ref().map_or(&[][..], |envelope| &envelope[..]);
+        let mut stream = self.connect_to_server(server_port, envelope.len() + body.len())?;
+        stream.write_all(envelope)?;
+        stream.write_all(body)?;
         Ok(stream)
     }
 
     pub fn send_to_server(&self, server_port: u16, data: &[u8]) -> std::io::Result<()> {
-        let mut stream = self.connect_to_server(server_port)?;
-        stream.write_all(data)?;
+        self.send_message(server_port, data)?;
         Ok(())
     }
 
+    /// Send a message and wait for the server's verdict on it. A rejection is returned as an
+    /// `io::Error` carrying the same kind and reason the server rejected the message with.
+    pub fn send_to_server_with_reply(&self, server_port: u16, data: &[u8]) -> std::io::Result<()> {
+        let mut stream = self.send_message(server_port, data)?;
+        // the server reads until end of stream before replying
+        stream.shutdown(std::net::Shutdown::Write)?;
+        Self::read_reply(&mut stream).map(|_| ())
+    }
+
+    /// Send a request and wait for the server's answer, e.g. a token it issues. A refusal
+    /// is returned as an `io::Error` like a rejected message.
+    pub fn request_from_server(&self, server_port: u16, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut stream = self.send_message(server_port, &[REQUEST_PREFIX, data].concat())?;
+        stream.shutdown(std::net::Shutdown::Write)?;
+        Self::read_reply(&mut stream)
+    }
+
+    /// Reply format: `[0][answer...]` if the message was accepted, with an empty answer
+    /// unless it was a request, otherwise `[1][error kind code u8][reason utf8...]`.
+    fn write_reply(stream: &mut TcpStream, outcome: Result<&[u8], &io::Error>) -> io::Result<()> {
+        let reply = match outcome {
+            Ok(answer) => [&[0u8][..], answer].concat(),
+            Err(e) => {
+                let mut reply = vec![1u8, error_kind_code(e.kind())];
+                reply.extend_from_slice(e.to_string().as_bytes());
+                reply
+            }
+        };
+        stream.write_all(&reply)?;
+        stream.flush()
+    }
+
+    fn read_reply(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+        let mut reply = Vec::new();
+        stream.take(MAX_REPLY_SIZE).read_to_end(&mut reply)?;
+        match reply.as_slice() {
+            [0, answer @ ..] => Ok(answer.to_vec()),
+            [1, code, reason @ ..] => Err(io::Error::new(
+                error_kind_from_code(*code),
+                String::from_utf8_lossy(reason).into_owned(),
+            )),
+            [] => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "server closed the connection without replying",
+            )),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "malformed reply from server")),
+        }
+    }
+
     pub fn signal_server(&self, server_port: u16) -> std::io::Result<TcpStream> {
-        let mut stream = self.connect_to_server(server_port)?;
-        stream.write_all(b"signal")?;
-        Ok(stream)
+        self.send_message(server_port, SIGNAL_PREFIX)
     }
 
     pub fn receive_from_server(&self, server_port: u16) -> std::io::Result<Vec<u8>> {
@@ -212,7 +893,7 @@ impl Communicator {
             data.extend_from_slice(&len.to_le_bytes());
             data.extend_from_slice(input);
         }
-        
+
         // Send the data through the TCP stream
         stream.write_all(&data)?;
         stream.flush()?;
@@ -222,3 +903,253 @@ impl Communicator {
         Ok(())
     }
 }
+
+/// Longest reply accepted from the server.
+const MAX_REPLY_SIZE: u64 = 4096;
+/// Leading bytes of a signal, which the server answers on the same connection.
+const SIGNAL_PREFIX: &[u8] = b"signal";
+/// Leading bytes of a request message.
+const REQUEST_PREFIX: &[u8] = b"request";
+
+/// Error kinds a rejection reply can carry; anything else is reported as `Other`.
+const REPLY_ERROR_KINDS: [io::ErrorKind; 8] = [
+    io::ErrorKind::InvalidData,
+    io::ErrorKind::PermissionDenied,
+    io::ErrorKind::AlreadyExists,
+    io::ErrorKind::TimedOut,
+    io::ErrorKind::OutOfMemory,
+    io::ErrorKind::UnexpectedEof,
+    io::ErrorKind::NotFound,
+    io::ErrorKind::Unsupported,
+];
+
+fn error_kind_code(kind: io::ErrorKind) -> u8 {
+    REPLY_ERROR_KINDS
+        .iter()
+        .position(|&k| k == kind)
+        .map_or(0, |i| i as u8 + 1)
+}
+
+fn error_kind_from_code(code: u8) -> io::ErrorKind {
+    code.checked_sub(1)
+        .and_then(|i| REPLY_ERROR_KINDS.get(i as usize).copied())
+        .unwrap_or(io::ErrorKind::Other)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // test that the bounded reader passes messages within the limit and rejects larger ones
+    fn test_bounded_reader() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let data = vec![7u8; 64];
+
+        let mut within = Vec::new();
+        BoundedReader::new(&data[..], 64, deadline).read_to_end(&mut within).unwrap();
+        assert_eq!(within, data);
+
+        let mut oversize = Vec::new();
+        let err = BoundedReader::new(&data[..], 63, deadline).read_to_end(&mut oversize).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let expired = Instant::now() - Duration::from_millis(1);
+        let err = BoundedReader::new(&data[..], 64, expired).read(&mut [0u8; 8]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    // test that declared lengths are reserved before any body is read and excess connections wait their turn
+    fn test_connection_limits() {
+        let mut server = Communicator::new(18641);
+        server.set_limits(MessageLimits {
+            max_buffered_bytes: 1024,
+            idle_timeout: Duration::from_millis(500),
+            max_connections: 1,
+            ..MessageLimits::default()
+        });
+        server.set_stream_callback(|reader, _addr| {
+            io::copy(reader, &mut io::sink())?;
+            Ok(None)
+        });
+        let budget = server.buffer_budget();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        server.set_shutdown_flag(Arc::clone(&shutdown));
+        server.start_server().unwrap();
+        let listener = thread::spawn(move || server.listen_loop());
+
+        // a sender that declares a message and stalls holds its length and the only handler
+        let mut stalled = TcpStream::connect(("127.0.0.1", 18641)).unwrap();
+        stalled.write_all(&DEFAULT_SESSION.to_le_bytes()).unwrap();
+        stalled.write_all(&1000u32.to_le_bytes()).unwrap();
+        let waiting = Instant::now();
+        while budget.used() != 1000 {
+            assert!(waiting.elapsed() < Duration::from_secs(5), "the declared length was never reserved");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let started = Instant::now();
+        Communicator::new(18642).send_to_server_with_reply(18641, b"after the stalled sender").unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert_eq!(budget.used(), 0);
+
+        // a message declaring more than the budget holds is refused before it is read
+        assert!(Communicator::new(18643).send_to_server_with_reply(18641, &[0u8; 1025]).is_err());
+        assert_eq!(budget.used(), 0);
+        drop(stalled);
+
+        shutdown.store(true, Ordering::Relaxed);
+        listener.join().unwrap().unwrap();
+    }
+
+    #[test]
+    // test that streamed messages are answered with an explicit accept or reject reply, and requests with data
+    fn test_submission_reply() {
+        let mut server = Communicator::new(18601);
+        server.set_stream_callback(|reader, _addr| {
+            let mut message = Vec::new();
+            reader.read_to_end(&mut message)?;
+            if message == b"submitted late" {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "input collection is closed"));
+            }
+            Ok(None)
+        });
+        server.set_request_callback(|request, addr| match request {
+            b"token" => Ok(addr.port().to_le_bytes().to_vec()),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown request")),
+        });
+        let shutdown = Arc::new(AtomicBool::new(false));
+        server.set_shutdown_flag(Arc::clone(&shutdown));
+        server.start_server().unwrap();
+        let listener = thread::spawn(move || server.listen_loop());
+
+        Communicator::new(18602).send_to_server_with_reply(18601, b"submitted on time").unwrap();
+        let err = Communicator::new(18603).send_to_server_with_reply(18601, b"submitted late").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(err.to_string(), "input collection is closed");
+        let reply = Communicator::new(18602).request_from_server(18601, b"token").unwrap();
+        assert_eq!(reply, 18602u16.to_le_bytes());
+        let err = Communicator::new(18603).request_from_server(18601, b"coffee").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        shutdown.store(true, Ordering::Relaxed);
+        listener.join().unwrap().unwrap();
+    }
+
+    #[test]
+    // test that a session with an address book only takes messages its parties signed for a current round
+    fn test_signed_messages() {
+        let keys: Vec<SigningKey> =
+            (0..2).map(|_| SigningKey::generate(&mut crate::crypto::prg::default_prg())).collect();
+        let mut address_book = AddressBook::new();
+        address_book.insert(18632, keys[0].verifying_key().to_bytes()).unwrap();
+        address_book.insert(18633, keys[1].verifying_key().to_bytes()).unwrap();
+
+        let mut server = Communicator::new(18631);
+        server.set_address_book(address_book);
+        server.session_mut().set_round_check(|round_id| match round_id {
+            2 => Ok(()),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "wrong round")),
+        });
+        server.set_stream_callback(|reader, _addr| {
+            io::copy(reader, &mut io::sink())?;
+            Ok(None)
+        });
+        server.set_signal_callback(|mut stream, port| {
+            let _ = stream.write_all(&port.to_le_bytes());
+        });
+        let shutdown = Arc::new(AtomicBool::new(false));
+        server.set_shutdown_flag(Arc::clone(&shutdown));
+        server.start_server().unwrap();
+        let listener = thread::spawn(move || server.listen_loop());
+
+        let party = |port: u16, key: &SigningKey, round_id: u64| {
+            let mut communicator = Communicator::new(port);
+            communicator.set_signing_key(key.clone());
+            communicator.set_round(round_id);
+            communicator
+        };
+        party(18632, &keys[0], 2).send_to_server_with_reply(18631, b"masked input").unwrap();
+        assert_eq!(party(18633, &keys[1], 2).receive_from_server(18631).unwrap(), 18633u16.to_le_bytes());
+
+        // unsigned, signed with another party's key, or signed for another round
+        let unsigned = Communicator::new(18633).send_to_server_with_reply(18631, &[0u8; 100]).unwrap_err();
+        assert_eq!(unsigned.kind(), io::ErrorKind::PermissionDenied);
+        let impersonated = party(18633, &keys[0], 2)
+            .send_to_server_with_reply(18631, b"masked input")
+            .unwrap_err();
+        assert_eq!(impersonated.kind(), io::ErrorKind::PermissionDenied);
+        assert!(party(18633, &keys[0], 2).receive_from_server(18631).unwrap().is_empty());
+        let stale = party(18632, &keys[0], 1).send_to_server_with_reply(18631, b"masked input").unwrap_err();
+        assert_eq!(stale.kind(), io::ErrorKind::InvalidData);
+
+        // the party is the one the envelope names, not the port the connection comes from
+        let signal = |key: &SigningKey| {
+            let mut stream = TcpStream::connect(("127.0.0.1", 18631)).unwrap();
+            let envelope = Envelope::sign(key, DEFAULT_SESSION, 18632, 2, SIGNAL_PREFIX);
+            stream.write_all(&DEFAULT_SESSION.to_le_bytes()).unwrap();
+            stream.write_all(&((ENVELOPE_LEN + SIGNAL_PREFIX.len()) as u32).to_le_bytes()).unwrap();
+            stream.write_all(&envelope.to_bytes()).unwrap();
+            stream.write_all(SIGNAL_PREFIX).unwrap();
+            let mut reply = Vec::new();
+            stream.read_to_end(&mut reply).unwrap();
+            reply
+        };
+        assert_eq!(signal(&keys[0]), 18632u16.to_le_bytes());
+        assert!(signal(&keys[1]).is_empty());
+
+        shutdown.store(true, Ordering::Relaxed);
+        listener.join().unwrap().unwrap();
+    }
+
+    #[test]
+    // test that messages reach only the session they were sent in and unknown sessions are refused
+    fn test_sessions() {
+        let mut server = Communicator::new(18604);
+        let received = Arc::new(Mutex::new(Vec::<(SessionId, Vec<u8>)>::new()));
+        for id in [1, 2] {
+            let mut session = Session::new();
+            let received = Arc::clone(&received);
+            session.set_stream_callback(move |reader, _addr| {
+                let mut message = Vec::new();
+                reader.read_to_end(&mut message)?;
+                received.lock().unwrap().push((id, message));
+                Ok(None)
+            });
+            server.sessions().register(id, session).unwrap();
+        }
+        let error = server.sessions().register(2, Session::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        server.set_shutdown_flag(Arc::clone(&shutdown));
+        server.start_server().unwrap();
+        let sessions = server.sessions();
+        let listener = thread::spawn(move || server.listen_loop());
+
+        let send = |port: u16, session_id: SessionId, message: &[u8]| {
+            let mut client = Communicator::new(port);
+            client.set_session_id(session_id);
+            client.send_to_server_with_reply(18604, message)
+        };
+        send(18605, 2, b"for the second session").unwrap();
+        send(18606, 1, b"for the first session").unwrap();
+        let err = send(18607, 3, b"for a missing session").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let mut client = Communicator::new(18605);
+        client.set_session_id(2);
+        assert_eq!(client.request_from_server(18604, b"token").unwrap_err().kind(), io::ErrorKind::Unsupported);
+        assert!(sessions.remove(1));
+        assert_eq!(send(18608, 1, b"for a closed session").unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(
+            received,
+            vec![(1, b"for the first session".to_vec()), (2, b"for the second session".to_vec())]
+        );
+
+        shutdown.store(true, Ordering::Relaxed);
+        listener.join().unwrap().unwrap();
+    }
+}
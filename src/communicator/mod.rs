@@ -1,2 +1,7 @@
 pub mod communicator;
-pub use communicator::Communicator;
+pub mod signing;
+pub use communicator::{
+    BoundedReader, BufferBudget, CommitteeCompletion, Communicator, DEFAULT_SESSION, MessageLimits, Session,
+    SessionId, SessionRegistry,
+};
+pub use signing::{AddressBook, Envelope};
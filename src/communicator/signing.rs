@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::io;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use crate::communicator::SessionId;
+
+const MESSAGE_DOMAIN: &[u8] = b"jagguar/communicator/message/v1";
+
+/// Length of the envelope a signed message carries after its session id:
+/// `[sender port u16][round id u64][signature]`.
+pub const ENVELOPE_LEN: usize = 2 + 8 + 64;
+
+/// The public keys parties sign their messages with, by the port that names them in the
+/// protocol. A session with an address book only takes messages signed by the party its
+/// envelope names, so no one can speak for a committee member or client without its key,
+/// whatever address it connects from.
+#[derive(Debug, Clone, Default)]
+pub struct AddressBook {
+    keys: HashMap<u16, VerifyingKey>,
+}
+
+impl AddressBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the public key of the party named by `port`.
+    pub fn insert(&mut self, port: u16, key: [u8; 32]) -> io::Result<()> {
+        let key = VerifyingKey::from_bytes(&key)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("key of port {}: {}", port, e)))?;
+        self.keys.insert(port, key);
+        Ok(())
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        self.keys.contains_key(&port)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Who sent a message, for which round, and the sender's signature over it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope {
+    pub sender: u16,
+    pub round_id: u64,
+    pub signature: [u8; 64],
+}
+
+impl Envelope {
+    /// Sign `body`, the message as sent after the envelope, as `sender` in `round_id`.
+    pub fn sign(key: &SigningKey, session_id: SessionId, sender: u16, round_id: u64, body: &[u8]) -> Self {
+        let signature = key.sign(&signed_message(session_id, sender, round_id, body)).to_bytes();
+        Self { sender, round_id, signature }
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENVELOPE_LEN] {
+        let mut bytes = [0u8; ENVELOPE_LEN];
+        bytes[..2].copy_from_slice(&self.sender.to_le_bytes());
+        bytes[2..10].copy_from_slice(&self.round_id.to_le_bytes());
+        bytes[10..].copy_from_slice(&self.signature);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; ENVELOPE_LEN]) -> Self {
+        Self {
+            sender: u16::from_le_bytes(bytes[..2].try_into().unwrap()),
+            round_id: u64::from_le_bytes(bytes[2..10].try_into().unwrap()),
+            signature: bytes[10..].try_into().unwrap(),
+        }
+    }
+
+    /// Check that the sender this envelope names signed `body` under its key in
+    /// `address_book`. Only the signed sender identifies the party, not the address the
+    /// message arrived from.
+    pub fn verify(&self, address_book: &AddressBook, session_id: SessionId, body: &[u8]) -> io::Result<()> {
+        let denied = |reason: String| io::Error::new(io::ErrorKind::PermissionDenied, reason);
+        let key = address_book
+            .keys
+            .get(&self.sender)
+            .ok_or_else(|| denied(format!("port {} is not in the address book", self.sender)))?;
+        let message = signed_message(session_id, self.sender, self.round_id, body);
+        key.verify_strict(&message, &Signature::from_bytes(&self.signature))
+            .map_err(|_| denied(format!("invalid signature on message from port {}", self.sender)))
+    }
+}
+
+fn signed_message(session_id: SessionId, sender: u16, round_id: u64, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(MESSAGE_DOMAIN.len() + 18 + body.len());
+    message.extend_from_slice(MESSAGE_DOMAIN);
+    message.extend_from_slice(&session_id.to_le_bytes());
+    message.extend_from_slice(&sender.to_le_bytes());
+    message.extend_from_slice(&round_id.to_le_bytes());
+    message.extend_from_slice(body);
+    message
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::prg::default_prg;
+
+    #[test]
+    // test that an envelope only verifies for its signer's port, session, round and body
+    fn test_envelope() {
+        let (key, other) = (SigningKey::generate(&mut default_prg()), SigningKey::generate(&mut default_prg()));
+        let mut address_book = AddressBook::new();
+        address_book.insert(9001, key.verifying_key().to_bytes()).unwrap();
+        address_book.insert(9002, other.verifying_key().to_bytes()).unwrap();
+
+        let envelope = Envelope::sign(&key, 3, 9001, 7, b"committee output");
+        assert_eq!(Envelope::from_bytes(&envelope.to_bytes()), envelope);
+        envelope.verify(&address_book, 3, b"committee output").unwrap();
+
+        // passed off as another sender's, in another session, or over another body
+        let renamed = Envelope { sender: 9002, ..envelope };
+        assert!(renamed.verify(&address_book, 3, b"committee output").is_err());
+        assert!(envelope.verify(&address_book, 4, b"committee output").is_err());
+        assert!(envelope.verify(&address_book, 3, b"committee outpux").is_err());
+        // the round is signed too
+        let moved = Envelope { round_id: 8, ..envelope };
+        assert!(moved.verify(&address_book, 3, b"committee output").is_err());
+        // a party cannot sign for another port, nor can a party missing from the book
+        let forged = Envelope::sign(&other, 3, 9001, 7, b"committee output");
+        let error = forged.verify(&address_book, 3, b"committee output").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        let stranger = Envelope::sign(&key, 3, 9003, 7, b"x");
+        assert!(stranger.verify(&address_book, 3, b"x").is_err());
+    }
+}
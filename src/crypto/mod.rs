@@ -1,13 +1,17 @@
 use ark_ff::{Fp, MontBackend, MontConfig};
 
+pub mod lwr_estimator;
 pub mod prg;
+pub mod sealed_box;
 pub mod seed_homomorphic_prg;
 pub mod shamir;
 pub mod util;
 
-pub use seed_homomorphic_prg::{SeedHomomorphicPRG, OUTER_MODULUS_BITS};
+pub use lwr_estimator::{CostModel, LwrParameters, SecurityEstimate};
+pub use seed_homomorphic_prg::{KeyHomomorphicPRF, SeedHomomorphicPRG, ShprgError, OUTER_MODULUS_BITS};
+pub use sealed_box::{DecryptionKey, EncryptionKey, SEALED_OVERHEAD};
 pub use shamir::Shamir;
-pub use util::{field_from_bytes, field_low_u128, field_to_bytes};
+pub use util::{field_carry_u32, field_from_bytes, field_low_u128, field_to_bytes};
 
 /// Byte length for serializing field elements on the wire.
 pub const FIELD_ELEMENT_BYTES: usize = 32;
@@ -0,0 +1,120 @@
+use std::io;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+
+use crate::crypto::prg::default_prg;
+
+const KEY_DOMAIN: &[u8] = b"jagguar/sealed-box/key/v1";
+
+/// Public X25519 key that boxes are sealed to.
+pub type EncryptionKey = [u8; 32];
+
+/// Bytes a sealed box adds to its plaintext: the ephemeral public key and the AEAD tag.
+pub const SEALED_OVERHEAD: usize = 32 + 16;
+
+/// Seal `plaintext` so only the holder of the secret behind `recipient` can open it:
+/// `[ephemeral public key][ciphertext || tag]`. Each box has its own ephemeral key, so the
+/// AEAD key is never reused and a zero nonce is safe. `context` is authenticated but not
+/// sent; opening fails unless the recipient supplies the same context.
+pub fn seal(recipient: &EncryptionKey, context: &[u8], plaintext: &[u8]) -> io::Result<Vec<u8>> {
+    let ephemeral = StaticSecret::random_from_rng(default_prg());
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let recipient = PublicKey::from(*recipient);
+    let cipher = box_cipher(ephemeral.diffie_hellman(&recipient), &ephemeral_public, &recipient)?;
+    let ciphertext = cipher
+        .encrypt(&Nonce::default(), Payload { msg: plaintext, aad: context })
+        .map_err(|_| io::Error::other("sealing failed"))?;
+    Ok([ephemeral_public.as_bytes().as_slice(), &ciphertext].concat())
+}
+
+/// The X25519 secret a party opens boxes sealed to it with.
+#[derive(Clone)]
+pub struct DecryptionKey(StaticSecret);
+
+// the secret stays out of logs
+impl std::fmt::Debug for DecryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DecryptionKey").field(&self.public_key()).finish()
+    }
+}
+
+impl DecryptionKey {
+    pub fn generate() -> Self {
+        Self(StaticSecret::random_from_rng(default_prg()))
+    }
+
+    pub fn from_bytes(secret: [u8; 32]) -> Self {
+        Self(StaticSecret::from(secret))
+    }
+
+    /// The key to publish for others to seal boxes to.
+    pub fn public_key(&self) -> EncryptionKey {
+        PublicKey::from(&self.0).to_bytes()
+    }
+
+    /// Open a box sealed to this key under `context`; fails with `InvalidData` if it was
+    /// sealed to another key or context, or altered.
+    pub fn open(&self, context: &[u8], sealed: &[u8]) -> io::Result<Vec<u8>> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "sealed box does not open");
+        if sealed.len() < SEALED_OVERHEAD {
+            return Err(invalid());
+        }
+        let (ephemeral_public, ciphertext) = sealed.split_at(32);
+        let ephemeral_public = PublicKey::from(<[u8; 32]>::try_from(ephemeral_public).unwrap());
+        let shared = self.0.diffie_hellman(&ephemeral_public);
+        let cipher = box_cipher(shared, &ephemeral_public, &PublicKey::from(&self.0)).map_err(|_| invalid())?;
+        cipher
+            .decrypt(&Nonce::default(), Payload { msg: ciphertext, aad: context })
+            .map_err(|_| invalid())
+    }
+}
+
+/// AEAD keyed by the hash of the shared secret and both public keys, which either side
+/// computes from its own secret and the other's public key.
+fn box_cipher(
+    shared: SharedSecret,
+    ephemeral: &PublicKey,
+    recipient: &PublicKey,
+) -> io::Result<ChaCha20Poly1305> {
+    // a low-order public key would make the shared secret public
+    if !shared.was_contributory() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "encryption key is of low order"));
+    }
+    let key: [u8; 32] = Sha256::new()
+        .chain_update(KEY_DOMAIN)
+        .chain_update(shared.as_bytes())
+        .chain_update(ephemeral.as_bytes())
+        .chain_update(recipient.as_bytes())
+        .finalize()
+        .into();
+    Ok(ChaCha20Poly1305::new(&key.into()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // test that a box opens only with its recipient's key and context, and only unaltered
+    fn test_sealed_box() {
+        let (recipient, other) = (DecryptionKey::generate(), DecryptionKey::generate());
+        let sealed = seal(&recipient.public_key(), b"member 1", b"seed share").unwrap();
+        assert_eq!(sealed.len(), b"seed share".len() + SEALED_OVERHEAD);
+        assert_eq!(recipient.open(b"member 1", &sealed).unwrap(), b"seed share");
+
+        // sealing again gives another box
+        assert_ne!(seal(&recipient.public_key(), b"member 1", b"seed share").unwrap(), sealed);
+        assert!(other.open(b"member 1", &sealed).is_err());
+        assert!(recipient.open(b"member 2", &sealed).is_err());
+        let mut altered = sealed.clone();
+        altered[40] ^= 1;
+        assert_eq!(recipient.open(b"member 1", &altered).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(recipient.open(b"member 1", &sealed[..SEALED_OVERHEAD - 1]).is_err());
+        // a key of low order is refused rather than sealed to
+        assert!(seal(&[0u8; 32], b"member 1", b"seed share").is_err());
+    }
+}
@@ -1,18 +1,53 @@
+use std::fmt;
+use std::sync::OnceLock;
+
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
 
+use crate::crypto::lwr_estimator::{CostModel, LwrParameters, SecurityEstimate};
 use crate::crypto::prg::{populate_random, populate_random_bytes, seeded_rng_at_word};
 use crate::crypto::util::dot_product;
 
 // Default parameters obtained via the lattice estimator.
-// See /scripts/parameters/shprg_parameters.json for details.
+// See /scripts/parameters/shprg_parameters.json for details; `lwr_estimator` reproduces them.
 const LAMBDA: usize = 3072;
 /// SHPRG outer modulus: arithmetic in Z_{2^128}.
 pub const OUTER_MODULUS_BITS: u32 = 128;
-const DEFAULT_INNER_MODULUS_BITS: u32 = 92;
+pub const DEFAULT_INNER_MODULUS_BITS: u32 = 92;
+/// Bit security below which no SHPRG is built on an inner modulus.
+pub const MIN_SECURITY_BITS: f64 = 128.0;
 // Each u128 occupies 4 × 32-bit ChaCha words.
 const WORDS_PER_U128: u128 = 4;
 
+/// Why an inner modulus cannot be used for the SHPRG.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShprgError {
+    /// The inner modulus must be smaller than the outer one for rounding to drop bits.
+    InnerModulusTooLarge { inner_modulus_bits: u32 },
+    /// The LWR instance is estimated below `MIN_SECURITY_BITS`.
+    InsufficientSecurity { inner_modulus_bits: u32, estimated_bits: f64 },
+}
+
+impl fmt::Display for ShprgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShprgError::InnerModulusTooLarge { inner_modulus_bits } => write!(
+                f,
+                "inner modulus 2^{} is not smaller than the outer modulus 2^{}",
+                inner_modulus_bits, OUTER_MODULUS_BITS
+            ),
+            ShprgError::InsufficientSecurity { inner_modulus_bits, estimated_bits } => write!(
+                f,
+                "inner modulus 2^{} leaves an estimated {:.1} bits of security",
+                inner_modulus_bits, estimated_bits
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShprgError {}
+
 #[derive(Debug)]
 pub struct SeedHomomorphicPRG {
     public_parameter_seed: [u8; 32],
@@ -20,47 +55,58 @@ pub struct SeedHomomorphicPRG {
     inner_modulus_bits: u32,
 }
 
+impl Default for SeedHomomorphicPRG {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SeedHomomorphicPRG {
     pub fn new() -> Self {
-        let mut rng = ChaCha20Rng::from_entropy();
-        let mut public_parameter_seed = [0u8; 32];
-        populate_random_bytes(&mut public_parameter_seed, &mut rng);
-        Self {
-            public_parameter_seed,
-            seed: Self::sample_seed(LAMBDA),
-            inner_modulus_bits: DEFAULT_INNER_MODULUS_BITS,
-        }
+        Self::new_from_public_seed(Self::sample_public_seed())
     }
 
     pub fn new_from_public_seed(public_parameter_seed: [u8; 32]) -> Self {
-        Self {
-            public_parameter_seed,
-            seed: Self::sample_seed(LAMBDA),
-            inner_modulus_bits: DEFAULT_INNER_MODULUS_BITS,
-        }
+        Self::new_from_both_seeds(public_parameter_seed, Self::sample_seed(LAMBDA))
     }
 
     pub fn new_from_both_seeds(public_parameter_seed: [u8; 32], seed: Vec<u128>) -> Self {
-        Self {
-            public_parameter_seed,
-            seed,
-            inner_modulus_bits: DEFAULT_INNER_MODULUS_BITS,
-        }
+        Self::with_parameters(public_parameter_seed, seed, DEFAULT_INNER_MODULUS_BITS)
+            .expect("the default inner modulus meets MIN_SECURITY_BITS")
     }
 
-    pub fn new_with_params(inner_modulus_bits: u32) -> Self {
-        assert!(
-            inner_modulus_bits < OUTER_MODULUS_BITS,
-            "inner modulus must be smaller than outer"
-        );
-        let mut rng = ChaCha20Rng::from_entropy();
-        let mut public_parameter_seed = [0u8; 32];
-        populate_random_bytes(&mut public_parameter_seed, &mut rng);
-        Self {
-            public_parameter_seed,
-            seed: Self::sample_seed(LAMBDA),
-            inner_modulus_bits,
+    pub fn new_with_params(inner_modulus_bits: u32) -> Result<Self, ShprgError> {
+        Self::with_parameters(Self::sample_public_seed(), Self::sample_seed(LAMBDA), inner_modulus_bits)
+    }
+
+    /// Every constructor ends up here, so no SHPRG rests on an LWR instance estimated
+    /// below `MIN_SECURITY_BITS`.
+    fn with_parameters(
+        public_parameter_seed: [u8; 32],
+        seed: Vec<u128>,
+        inner_modulus_bits: u32,
+    ) -> Result<Self, ShprgError> {
+        Self::check_parameters(inner_modulus_bits)?;
+        Ok(Self { public_parameter_seed, seed, inner_modulus_bits })
+    }
+
+    /// Check that an SHPRG with this inner modulus is estimated at `MIN_SECURITY_BITS` or
+    /// more. The default modulus is estimated once per process.
+    pub fn check_parameters(inner_modulus_bits: u32) -> Result<(), ShprgError> {
+        if inner_modulus_bits >= OUTER_MODULUS_BITS {
+            return Err(ShprgError::InnerModulusTooLarge { inner_modulus_bits });
         }
+        let estimate = |bits| Self::lwr_parameters(bits).estimate(CostModel::default()).bits();
+        let estimated_bits = if inner_modulus_bits == DEFAULT_INNER_MODULUS_BITS {
+            static DEFAULT_ESTIMATE: OnceLock<f64> = OnceLock::new();
+            *DEFAULT_ESTIMATE.get_or_init(|| estimate(DEFAULT_INNER_MODULUS_BITS))
+        } else {
+            estimate(inner_modulus_bits)
+        };
+        if estimated_bits < MIN_SECURITY_BITS {
+            return Err(ShprgError::InsufficientSecurity { inner_modulus_bits, estimated_bits });
+        }
+        Ok(())
     }
 
     /// Expand to an arbitrary number of output elements.
@@ -78,6 +124,13 @@ impl SeedHomomorphicPRG {
         output
     }
 
+    fn sample_public_seed() -> [u8; 32] {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let mut public_parameter_seed = [0u8; 32];
+        populate_random_bytes(&mut public_parameter_seed, &mut rng);
+        public_parameter_seed
+    }
+
     fn sample_seed(size: usize) -> Vec<u128> {
         let mut rng = ChaCha20Rng::from_entropy();
         let mut seed = vec![0u128; size];
@@ -100,6 +153,59 @@ impl SeedHomomorphicPRG {
     pub fn inner_modulus_bits(&self) -> u32 {
         self.inner_modulus_bits
     }
+
+    /// The LWR instance an SHPRG with this inner modulus rests on.
+    pub fn lwr_parameters(inner_modulus_bits: u32) -> LwrParameters {
+        LwrParameters::new(LAMBDA, OUTER_MODULUS_BITS, inner_modulus_bits)
+    }
+
+    pub fn security_estimate(&self) -> SecurityEstimate {
+        Self::lwr_parameters(self.inner_modulus_bits).estimate(CostModel::default())
+    }
+}
+
+/// Key-homomorphic PRF F_k(x) = round(A_x · k) from the LWR construction above: the
+/// public matrix A_x is expanded from a hash of a succinct seed and the input x, so one
+/// long-term key k yields a fresh mask for every x, and F_{k1}(x) + F_{k2}(x) equals
+/// F_{k1 + k2}(x) up to a rounding error of 1 per coordinate.
+#[derive(Debug, Clone)]
+pub struct KeyHomomorphicPRF {
+    key: Vec<u128>,
+}
+
+impl Default for KeyHomomorphicPRF {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyHomomorphicPRF {
+    /// Sample a fresh key.
+    pub fn new() -> Self {
+        Self { key: SeedHomomorphicPRG::sample_seed(LAMBDA) }
+    }
+
+    pub fn from_key(key: Vec<u128>) -> Self {
+        Self { key }
+    }
+
+    /// The seed of the public matrix A_x for input `x` under `succinct_seed`.
+    pub fn public_seed(succinct_seed: [u8; 32], x: u64) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"jagguar/opa/round-seed/v1");
+        hasher.update(succinct_seed);
+        hasher.update(x.to_le_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Evaluate the PRF at `x`, expanded to `n` output elements.
+    pub fn evaluate(&self, succinct_seed: [u8; 32], x: u64, n: usize) -> Vec<u128> {
+        SeedHomomorphicPRG::new_from_both_seeds(Self::public_seed(succinct_seed, x), self.key.clone()).expand(n)
+    }
+
+    pub fn get_key(&self) -> &Vec<u128> {
+        &self.key
+    }
 }
 
 #[cfg(test)]
@@ -133,9 +239,51 @@ mod tests {
             let o1 = output_1[i];
             let o_sum = output_sum[i];
             let sum_mod = o0.wrapping_add(o1) % m;
-            let delta = if sum_mod >= o_sum { sum_mod - o_sum } else { o_sum - sum_mod };
+            let delta = sum_mod.abs_diff(o_sum);
             let dist = delta.min(m - delta);
             assert!(dist <= 1);
         }
     }
+
+    #[test]
+    // test that the PRF is key-homomorphic at every input and differs between inputs
+    fn test_key_homomorphic_prf() {
+        let prf_0 = KeyHomomorphicPRF::new();
+        let prf_1 = KeyHomomorphicPRF::new();
+        let key_sum: Vec<u128> = prf_0
+            .get_key()
+            .iter()
+            .zip(prf_1.get_key())
+            .map(|(&a, &b)| a.wrapping_add(b))
+            .collect();
+        let prf_sum = KeyHomomorphicPRF::from_key(key_sum);
+
+        let m = 1u128 << DEFAULT_INNER_MODULUS_BITS;
+        for x in 0..2 {
+            let output_0 = prf_0.evaluate([7u8; 32], x, 64);
+            let output_1 = prf_1.evaluate([7u8; 32], x, 64);
+            let output_sum = prf_sum.evaluate([7u8; 32], x, 64);
+            for i in 0..output_sum.len() {
+                let sum_mod = output_0[i].wrapping_add(output_1[i]) % m;
+                let delta = sum_mod.abs_diff(output_sum[i]);
+                assert!(delta.min(m - delta) <= 1);
+            }
+        }
+        assert_ne!(prf_0.evaluate([7u8; 32], 0, 64), prf_0.evaluate([7u8; 32], 1, 64));
+    }
+
+    #[test]
+    // test that the default parameters meet the security bound and weaker ones are refused
+    fn test_parameter_security() {
+        assert!(SeedHomomorphicPRG::new().security_estimate().bits() >= MIN_SECURITY_BITS);
+        assert_eq!(SeedHomomorphicPRG::new_with_params(80).unwrap().inner_modulus_bits(), 80);
+        assert!(matches!(
+            SeedHomomorphicPRG::new_with_params(120),
+            Err(ShprgError::InsufficientSecurity { inner_modulus_bits: 120, .. })
+        ));
+        assert_eq!(
+            SeedHomomorphicPRG::check_parameters(128),
+            Err(ShprgError::InnerModulusTooLarge { inner_modulus_bits: 128 })
+        );
+    }
 }
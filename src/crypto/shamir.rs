@@ -60,31 +60,50 @@ impl<F: Field> Shamir<F> {
 
     // Reconstruct a secret from any t shares using Lagrange interpolation at x = 0
     pub fn reconstruct(&self, shares: &[(F, F)]) -> Result<F, ShamirError> {
+        self.interpolate_at(shares, F::ZERO)
+    }
+
+    // Evaluate the degree t-1 polynomial through the first t shares at an arbitrary x
+    pub fn interpolate_at(&self, shares: &[(F, F)], x: F) -> Result<F, ShamirError> {
         if shares.len() < self.threshold { return Err(ShamirError::InsufficientShares); }
 
         // Use exactly t shares (first t provided)
-        let k = self.threshold;
-        let used = &shares[..k];
-
-        // s = sum_i y_i * l_i(0), where
-        // l_i(0) = prod_{j!=i} (-x_j) / (x_i - x_j)
-        let mut secret = F::ZERO;
-        for i in 0..k {
-            let (xi, yi) = (used[i].0, used[i].1);
+        let used = &shares[..self.threshold];
+        let xs: Vec<F> = used.iter().map(|&(xi, _)| xi).collect();
+        let coefficients = self.lagrange_coefficients(&xs, x)?;
+        Ok(used.iter().zip(coefficients).map(|(&(_, yi), li)| yi * li).sum())
+    }
+
+    // Lagrange basis values l_i(x) = prod_{j!=i} (x - x_j) / (x_i - x_j) for the first t points.
+    // Callers interpolating many secrets shared at the same points can compute these once.
+    pub fn lagrange_coefficients(&self, xs: &[F], x: F) -> Result<Vec<F>, ShamirError> {
+        if xs.len() < self.threshold { return Err(ShamirError::InsufficientShares); }
+
+        let used = &xs[..self.threshold];
+        let mut coefficients = Vec::with_capacity(used.len());
+        for (i, &xi) in used.iter().enumerate() {
             let mut numerator = F::ONE;
             let mut denominator = F::ONE;
-            for j in 0..k {
+            for (j, &xj) in used.iter().enumerate() {
                 if i == j { continue; }
-                let xj = used[j].0;
-                numerator *= -xj;
+                numerator *= x - xj;
                 denominator *= xi - xj;
             }
             let denom_inv = denominator.inverse().ok_or(ShamirError::ReconstructionFailed)?;
-            let li_at_zero = numerator * denom_inv;
-            secret += yi * li_at_zero;
+            coefficients.push(numerator * denom_inv);
         }
+        Ok(coefficients)
+    }
 
-        Ok(secret)
+    // Check that every share beyond the first t lies on the polynomial those t define.
+    // With fewer than t + 1 shares there is nothing to cross-check and this returns true.
+    pub fn is_consistent(&self, shares: &[(F, F)]) -> Result<bool, ShamirError> {
+        for &(x, y) in shares.iter().skip(self.threshold) {
+            if self.interpolate_at(shares, x)? != y {
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 
     // getter functions
@@ -137,4 +156,21 @@ mod tests {
         let secret = shamir.reconstruct(&shares).unwrap();
         assert_eq!(secret, F256::from(17));
     }
+
+    #[test]
+    // test that extra shares are used to detect a corrupted share
+    fn test_consistency() {
+        let shamir = Shamir::<F256>::new(31, 16);
+        let mut rng = ChaCha20Rng::from_entropy();
+        let mut shares = shamir.share(F256::from(17), &mut rng).unwrap();
+
+        // any subset of t shares reconstructs the same secret
+        assert!(shamir.is_consistent(&shares).unwrap());
+        assert_eq!(shamir.reconstruct(&shares[15..]).unwrap(), F256::from(17));
+
+        // a single corrupted share outside the first t is caught
+        shares[20].1 += F256::from(1u64);
+        assert!(!shamir.is_consistent(&shares).unwrap());
+        assert!(shamir.is_consistent(&shares[..16]).unwrap());
+    }
 }
\ No newline at end of file
@@ -4,7 +4,7 @@ use crate::crypto::{FieldBytes, FIELD_ELEMENT_BYTES};
 
 // Multiply a matrix (rows of u128) by a vector over Z_{2^128}.
 // Each multiplication wraps modulo 2^128; the sum also wraps — i.e. arithmetic in Z_{2^128}.
-pub fn matrix_vector_multiplication(matrix: &Vec<Vec<u128>>, vector: &Vec<u128>) -> Vec<u128> {
+pub fn matrix_vector_multiplication(matrix: &[Vec<u128>], vector: &[u128]) -> Vec<u128> {
     let mut out = Vec::with_capacity(matrix.len());
     for row in matrix.iter() {
         assert_eq!(row.len(), vector.len(), "row length must match vector length");
@@ -38,11 +38,26 @@ where
 {
     let limbs = x.into_bigint();
     let limbs = limbs.as_ref();
-    let lo = limbs.get(0).copied().unwrap_or(0) as u128;
+    let lo = limbs.first().copied().unwrap_or(0) as u128;
     let hi = limbs.get(1).copied().unwrap_or(0) as u128;
     lo | (hi << 64)
 }
 
+// Bits 128 to 160 of a field element's integer representative, or None if it has more.
+pub fn field_carry_u32<F>(x: F) -> Option<u32>
+where
+    F: PrimeField,
+    <F as PrimeField>::BigInt: AsRef<[u64]>,
+{
+    let limbs = x.into_bigint();
+    let limbs = limbs.as_ref();
+    let carry = limbs.get(2).copied().unwrap_or(0);
+    if carry > u32::MAX as u64 || limbs.iter().skip(3).any(|&limb| limb != 0) {
+        return None;
+    }
+    Some(carry as u32)
+}
+
 // Serialize a field element to a fixed-width little-endian byte array.
 pub fn field_to_bytes<F>(x: F) -> FieldBytes
 where
@@ -0,0 +1,4 @@
+// jagguar-client <config.json>: Submit one client's input to a deployment.
+fn main() {
+    jagguar::deploy::run_from_args(jagguar::deploy::run_client);
+}
@@ -0,0 +1,4 @@
+// jagguar-committee <config.json>: Run one committee member of a deployment.
+fn main() {
+    jagguar::deploy::run_from_args(jagguar::deploy::run_committee);
+}
@@ -0,0 +1,4 @@
+// jagguar-server <config.json>: Run the aggregation server of a deployment.
+fn main() {
+    jagguar::deploy::run_from_args(jagguar::deploy::run_server);
+}
@@ -0,0 +1,329 @@
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::communicator::{AddressBook, SessionId};
+use crate::deploy::format::DataFormat;
+use crate::protocols::opa::params::hex_seed;
+use crate::protocols::opa::{
+    Beacon, ClientId, CommitteeKey, IdentityKey, NormBound, OPASetupParameters, SeedDerivation,
+};
+
+/// How long a party waits for the server by default: for the public parameters to be
+/// published, or for a round to finish.
+const DEFAULT_TIMEOUT_MS: u64 = 600_000;
+
+/// Configuration of one party of a deployment, read from a JSON file. Every party names
+/// the server and the public parameter file; the remaining fields are role specific.
+///
+/// ```json
+/// {
+///   "server": "10.0.0.1:9000",
+///   "public_params": "/shared/params.json",
+///   "protocol": { "committee_size": 3, "corruption_threshold": 1, "reconstruction_threshold": 2 },
+///   "output": "aggregate.txt"
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeploymentConfig {
+    /// Address book entry of the server, as the other parties reach it.
+    pub server: SocketAddr,
+    /// Interface the server listens on, at the server's port; defaults to every interface.
+    #[serde(default)]
+    pub listen: Option<IpAddr>,
+    #[serde(default)]
+    pub session_id: SessionId,
+    /// Where the server publishes its public parameters and the other parties load them.
+    pub public_params: PathBuf,
+    /// Protocol parameters; only the server reads them, the others get them published.
+    #[serde(default)]
+    pub protocol: Option<ProtocolConfig>,
+    /// Local port a client or committee member connects from. The server identifies
+    /// committee members by it, so it must be the server's port plus the member's offset.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Client input vector.
+    #[serde(default)]
+    pub input: Option<PathBuf>,
+    /// Format of `input`; implied by its extension if not given.
+    #[serde(default)]
+    pub input_format: Option<DataFormat>,
+    /// Secret key a client enrolls with under admission control, as 64 hex digits; a fresh
+    /// one is generated if not given, which an allowlist will refuse.
+    #[serde(default, with = "hex_seed::option")]
+    pub identity: Option<[u8; 32]>,
+    /// Largest value a client accepts in its input, below what the protocol can decode.
+    #[serde(default)]
+    pub max_input_value: Option<u32>,
+    /// File the server writes the decoded aggregate to, with its metadata alongside. With
+    /// several rounds, each round goes to its own file with the round id in the name.
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    /// Format of `output`; implied by its extension if not given.
+    #[serde(default)]
+    pub output_format: Option<DataFormat>,
+    /// Secret key this party signs its messages with, as 64 hex digits, if the server
+    /// checks signatures.
+    #[serde(default, with = "hex_seed::option")]
+    pub signing_key: Option<[u8; 32]>,
+    /// Public keys of the parties by their port; only the server reads it, and with it
+    /// refuses every message not signed by the party its envelope names.
+    #[serde(default)]
+    pub address_book: Vec<AddressBookEntry>,
+    /// X25519 secret a committee member opens the shares sealed to it with, as 64 hex
+    /// digits; its public key must be among the server's `protocol.committee_keys`.
+    #[serde(default, with = "hex_seed::option")]
+    pub decryption_key: Option<[u8; 32]>,
+    /// Directory the server or a committee member checkpoints to.
+    #[serde(default)]
+    pub checkpoint_dir: Option<PathBuf>,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// One party of the address book, e.g. `{ "port": 9001, "key": "<64 hex digits>" }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddressBookEntry {
+    pub port: u16,
+    #[serde(with = "hex_seed")]
+    pub key: [u8; 32],
+}
+
+/// The server's protocol parameters; see `OPASetupParameters`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtocolConfig {
+    #[serde(default = "default_security_parameter")]
+    pub security_parameter: u64,
+    pub corruption_threshold: u64,
+    pub reconstruction_threshold: u64,
+    pub committee_size: u64,
+    #[serde(default)]
+    pub min_participants: Option<u64>,
+    #[serde(default)]
+    pub input_deadline_ms: Option<u64>,
+    #[serde(default)]
+    pub committee_grace_period_ms: Option<u64>,
+    #[serde(default)]
+    pub committee_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub key_homomorphic_masks: bool,
+    /// Rounds the server runs before it exits.
+    #[serde(default = "default_rounds")]
+    pub rounds: u64,
+    /// Client ports to draw the committee from instead of consecutive ports after the
+    /// server's; the drawn clients then also run as committee members on their ports.
+    #[serde(default)]
+    pub committee_population: Option<Vec<ClientId>>,
+    /// Public randomness for the draw as 64 hex digits; defaults to a hash of the population.
+    #[serde(default, with = "hex_seed::option")]
+    pub beacon: Option<Beacon>,
+    /// Derive the public seed instead of sampling it from a descriptor agreed on in advance,
+    /// as `{ "descriptor": "..." }`.
+    #[serde(default)]
+    pub seed_derivation: Option<SeedDerivation>,
+    /// Only accept submissions signed by enrolled client identities, once per round.
+    #[serde(default)]
+    pub admission_control: bool,
+    /// Public keys of the clients allowed to enroll, as 64 hex digits each; anyone may
+    /// enroll if empty.
+    #[serde(default, with = "hex_seed::vec")]
+    pub allowed_clients: Vec<IdentityKey>,
+    /// Public keys of the committee members by port, e.g. `[{ "port": 9001, "key": ... }]`;
+    /// with them clients seal every share so the server cannot read it.
+    #[serde(default)]
+    pub committee_keys: Vec<CommitteeKey>,
+    /// Have clients commit to their masked inputs and write a proof of each aggregate into
+    /// its metadata, which anyone holding the aggregate can check.
+    #[serde(default)]
+    pub verifiable_aggregation: bool,
+    /// Have clients prove every input value below `2^range_proof_bits` (8, 16 or 32),
+    /// and check the aggregate against the proofs before it is published.
+    #[serde(default)]
+    pub range_proof_bits: Option<u32>,
+    /// Have clients also prove the norm of their input within a bound, e.g.
+    /// `{ "l2": 1000 }` or `{ "linf": 255 }`; needs `range_proof_bits`.
+    #[serde(default)]
+    pub norm_bound: Option<NormBound>,
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_TIMEOUT_MS
+}
+
+fn default_security_parameter() -> u64 {
+    40
+}
+
+fn default_rounds() -> u64 {
+    1
+}
+
+impl DeploymentConfig {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// The local port, which clients and committee members must configure.
+    pub fn require_port(&self) -> io::Result<u16> {
+        self.port.ok_or_else(|| missing("port"))
+    }
+
+    pub fn require_protocol(&self) -> io::Result<&ProtocolConfig> {
+        self.protocol.as_ref().ok_or_else(|| missing("protocol"))
+    }
+
+    pub fn require_input(&self) -> io::Result<&Path> {
+        self.input.as_deref().ok_or_else(|| missing("input"))
+    }
+
+    /// The address book to check signatures against, if one is configured.
+    pub fn address_book(&self) -> io::Result<Option<AddressBook>> {
+        if self.address_book.is_empty() {
+            return Ok(None);
+        }
+        let mut address_book = AddressBook::new();
+        for entry in &self.address_book {
+            address_book.insert(entry.port, entry.key)?;
+        }
+        Ok(Some(address_book))
+    }
+
+    /// Where the aggregate of `round_id` goes: `output` itself for a single-round server,
+    /// otherwise `output` with `-round<id>` added to the file stem.
+    pub fn output_path(&self, round_id: u64) -> Option<PathBuf> {
+        let output = self.output.as_ref()?;
+        let single_round = self.protocol.as_ref().is_none_or(|protocol| protocol.rounds <= 1);
+        if single_round {
+            return Some(output.clone());
+        }
+        let mut name = output.file_stem().unwrap_or_default().to_owned();
+        name.push(format!("-round{}", round_id));
+        if let Some(extension) = output.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        Some(output.with_file_name(name))
+    }
+}
+
+impl ProtocolConfig {
+    pub fn setup_parameters(&self, session_id: SessionId) -> OPASetupParameters {
+        let mut parameters = OPASetupParameters::new(
+            self.security_parameter,
+            self.corruption_threshold,
+            self.reconstruction_threshold,
+            self.committee_size,
+        )
+        .with_key_homomorphic_masks(self.key_homomorphic_masks)
+        .with_admission_control(self.admission_control)
+        .with_verifiable_aggregation(self.verifiable_aggregation)
+        .with_session_id(session_id);
+        if let Some(bits) = self.range_proof_bits {
+            parameters = parameters.with_range_proofs(bits);
+        }
+        if let Some(bound) = self.norm_bound {
+            parameters = parameters.with_norm_bound(bound);
+        }
+        if let Some(min_participants) = self.min_participants {
+            parameters = parameters.with_min_participants(min_participants);
+        }
+        if let Some(ms) = self.input_deadline_ms {
+            parameters = parameters.with_input_deadline(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.committee_grace_period_ms {
+            parameters = parameters.with_committee_grace_period(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.committee_timeout_ms {
+            parameters = parameters.with_committee_timeout(Duration::from_millis(ms));
+        }
+        parameters
+    }
+}
+
+fn missing(field: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("config is missing `{}`", field))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // test that a minimal config gets defaults and typos are refused rather than ignored
+    fn test_deployment_config() {
+        let config: DeploymentConfig = serde_json::from_str(
+            r#"{
+                "server": "127.0.0.1:9000",
+                "public_params": "params.json",
+                "protocol": { "committee_size": 3, "corruption_threshold": 1, "reconstruction_threshold": 2 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.session_id, 0);
+        assert_eq!(config.timeout(), Duration::from_millis(DEFAULT_TIMEOUT_MS));
+        let protocol = config.require_protocol().unwrap();
+        assert_eq!((protocol.security_parameter, protocol.rounds), (40, 1));
+        assert_eq!(protocol.setup_parameters(5).session_id(), 5);
+        assert_eq!(config.require_port().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(config.output_path(0), None);
+        assert!(config.address_book().unwrap().is_none());
+
+        // rounds after the first get their own output file
+        let mut config = config;
+        config.output = Some(PathBuf::from("out/aggregate.npy"));
+        assert_eq!(config.output_path(3), Some(PathBuf::from("out/aggregate.npy")));
+        config.protocol.as_mut().unwrap().rounds = 4;
+        assert_eq!(config.output_path(3), Some(PathBuf::from("out/aggregate-round3.npy")));
+
+        let drawn: ProtocolConfig = serde_json::from_str(&format!(
+            r#"{{ "committee_size": 3, "corruption_threshold": 1, "reconstruction_threshold": 2,
+                 "committee_population": [9001, 9002, 9003], "beacon": "{}",
+                 "seed_derivation": {{ "descriptor": "pilot 2026-10" }},
+                 "admission_control": true, "allowed_clients": ["{}"],
+                 "committee_keys": [{{ "port": 9002, "key": "{}" }}] }}"#,
+            "ab".repeat(32),
+            "cd".repeat(32),
+            "12".repeat(32),
+        ))
+        .unwrap();
+        assert_eq!(drawn.committee_population, Some(vec![9001, 9002, 9003]));
+        assert_eq!(drawn.beacon, Some([0xab; 32]));
+        assert_eq!(drawn.seed_derivation, Some(SeedDerivation::Descriptor("pilot 2026-10".to_string())));
+        assert_eq!(drawn.allowed_clients, vec![[0xcd; 32]]);
+        assert_eq!(drawn.committee_keys, vec![CommitteeKey { port: 9002, key: [0x12; 32] }]);
+        assert!(drawn.setup_parameters(0).admission_control());
+
+        // the server checks messages against the address book
+        let key = ed25519_dalek::SigningKey::from_bytes(&[1; 32]).verifying_key().to_bytes();
+        let key: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+        let signed: DeploymentConfig = serde_json::from_str(&format!(
+            r#"{{ "server": "127.0.0.1:9000", "public_params": "params.json", "signing_key": "{}",
+                 "address_book": [{{ "port": 9001, "key": "{}" }}] }}"#,
+            "ef".repeat(32),
+            key,
+        ))
+        .unwrap();
+        assert_eq!(signed.signing_key, Some([0xef; 32]));
+        assert!(signed.address_book().unwrap().unwrap().contains(9001));
+
+        let typo = serde_json::from_str::<DeploymentConfig>(
+            r#"{ "server": "127.0.0.1:9000", "public_params": "params.json", "prot": {} }"#,
+        );
+        assert!(typo.is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use ed25519_dalek::SigningKey;
+
+use crate::communicator::Communicator;
+use crate::crypto::DecryptionKey;
+use crate::deploy::config::DeploymentConfig;
+use crate::deploy::format::{DataFormat, read_vector, validate_input, write_output};
+use crate::protocols::client::Client;
+use crate::protocols::committee::Committee;
+use crate::protocols::server::Server;
+use crate::protocols::opa::checkpoint::write_atomically;
+use crate::protocols::opa::server::OPAState;
+use crate::protocols::opa::{OPAClient, OPACommittee, OPAPublicParams, OPAServer};
+
+/// How often clients and committee members look for the published parameters.
+const PARAMS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Entry point of the deployment binaries: run `role` with the config file named by the
+/// only argument, exiting with status 1 if it fails.
+pub fn run_from_args(role: fn(&DeploymentConfig) -> io::Result<()>) {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, path] = args.as_slice() else {
+        eprintln!("usage: {} <config.json>", args.first().map_or("jagguar", String::as_str));
+        std::process::exit(2);
+    };
+    if let Err(e) = DeploymentConfig::from_file(Path::new(path)).and_then(|config| role(&config)) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Serve `protocol.rounds` rounds, publishing the public parameters of each, and write
+/// every decoded aggregate with its metadata. Fails if a round aborts or does not finish
+/// in time.
+pub fn run_server(config: &DeploymentConfig) -> io::Result<()> {
+    let protocol = config.require_protocol()?;
+    let port = config.server.port();
+    let parameters = protocol.setup_parameters(config.session_id);
+    parameters.validate()?;
+    let mut server = OPAServer::new(parameters);
+    if let Some(ref population) = protocol.committee_population {
+        server.select_committee(population, protocol.beacon)?;
+    }
+    if let Some(ref derivation) = protocol.seed_derivation {
+        server.derive_public_seed(derivation.clone())?;
+    }
+    if !protocol.allowed_clients.is_empty() {
+        server.allow_clients(protocol.allowed_clients.iter().copied())?;
+    }
+    if let Some(address_book) = config.address_book()? {
+        server.set_address_book(address_book)?;
+    }
+    if !protocol.committee_keys.is_empty() {
+        let keys = protocol.committee_keys.iter().map(|committee_key| (committee_key.port, committee_key.key));
+        server.set_committee_keys(keys)?;
+    }
+    if let Some(ref dir) = config.checkpoint_dir {
+        server.set_checkpoint_dir(dir)?;
+    }
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let mut communicator = Communicator::new(port);
+    communicator.set_shutdown_flag(Arc::clone(&shutdown));
+    communicator.start_server_at(config.listen.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)))?;
+    server.set_communicator(communicator);
+    server.on_communicator_setup(port);
+    println!("Server listening on port {}", port);
+
+    let mut state = server.get_state().clone();
+    publish(&state, &config.public_params)?;
+    let listener = std::thread::spawn(move || server.get_communicator().listen_loop());
+
+    let mut result = Ok(());
+    for round_id in 0..protocol.rounds {
+        if round_id > 0 {
+            match OPAServer::start_round(&state, round_id) {
+                Ok(next) => state = next,
+                Err(e) => {
+                    result = Err(e.into());
+                    break;
+                }
+            }
+            publish(&state, &config.public_params)?;
+        }
+        match state.round.outcome(round_id, config.timeout()) {
+            Ok(aggregate) => {
+                println!("Round {} produced an aggregate of {} values", round_id, aggregate.len());
+                if let Some(path) = config.output_path(round_id) {
+                    let format = config.output_format.unwrap_or_else(|| DataFormat::from_path(&path));
+                    let contributors = state.round.contributors().unwrap_or_default();
+                    let proof = state.aggregate_proof();
+                    let params = state.public_params();
+                    write_output(&path, format, &aggregate, round_id, contributors, params, proof)?;
+                    println!("Wrote the aggregate of round {} to {}", round_id, path.display());
+                }
+            }
+            Err(e) => {
+                result = Err(e.into());
+                break;
+            }
+        }
+    }
+
+    shutdown.store(true, Ordering::Relaxed);
+    listener.join().expect("listener thread panicked")?;
+    result
+}
+
+/// Encrypt the configured input under the published parameters and submit it, after
+/// getting admitted if the server runs admission control, failing if the server rejects it.
+pub fn run_client(config: &DeploymentConfig) -> io::Result<()> {
+    let port = config.require_port()?;
+    let input = read_vector(config.require_input()?, config.input_format)?;
+    let state: OPAState = wait_for_params(config)?.into();
+    validate_input(&input, &state, config.max_input_value)?;
+
+    let mut client = OPAClient::<u32>::new();
+    client.set_server_state(state);
+    client.set_server_ip(config.server.ip());
+    if let Some(ref key) = config.identity {
+        client.set_identity(SigningKey::from_bytes(key));
+    }
+    if let Some(ref key) = config.signing_key {
+        client.set_signing_key(SigningKey::from_bytes(key));
+    }
+    client.set_input(input);
+    client.encrypt_input();
+    client.admit(port)?;
+    client.submit(port)?;
+    println!("Server accepted input from port {}", port);
+    Ok(())
+}
+
+/// Fetch this member's shares, sum them and report the result to the server, or report
+/// the share saved before a restart if the member checkpoints.
+pub fn run_committee(config: &DeploymentConfig) -> io::Result<()> {
+    let port = config.require_port()?;
+    let state: OPAState = wait_for_params(config)?.into();
+    if state.committee_index(port).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("port {} is not a committee port of the server on port {}", port, state.port),
+        ));
+    }
+
+    let mut member = OPACommittee::new(port);
+    if let Some(key) = config.decryption_key {
+        member.set_decryption_key(DecryptionKey::from_bytes(key));
+    }
+    // a member that cannot open its shares would fetch them for nothing
+    if state.seals_shares() && state.committee_keys.get(&port) != Some(&member.encryption_key()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("the server published another key for port {} than `decryption_key`", port),
+        ));
+    }
+    member.set_server_state(state);
+    member.set_server_ip(config.server.ip());
+    if let Some(ref key) = config.signing_key {
+        member.set_signing_key(SigningKey::from_bytes(key));
+    }
+    if let Some(ref dir) = config.checkpoint_dir {
+        member.set_checkpoint_dir(dir)?;
+    }
+    if !member.resume() {
+        member.retrieve_inputs();
+        member.aggregate();
+    }
+    member.send_output();
+    Ok(())
+}
+
+/// Write the public parameters where the other parties load them, e.g. a shared volume
+/// or a file copied to their hosts out of band.
+fn publish(state: &OPAState, path: &Path) -> io::Result<()> {
+    let params = state.public_params();
+    write_atomically(path, params.to_json().as_bytes())?;
+    let fingerprint: String = params.fingerprint().iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    println!(
+        "Published parameters of round {} to {} (fingerprint {})",
+        state.round_id,
+        path.display(),
+        fingerprint
+    );
+    Ok(())
+}
+
+/// Load the public parameters once the server has published them.
+fn wait_for_params(config: &DeploymentConfig) -> io::Result<OPAPublicParams> {
+    let deadline = Instant::now() + config.timeout();
+    loop {
+        match OPAPublicParams::from_file(&config.public_params) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound && Instant::now() < deadline => {
+                std::thread::sleep(PARAMS_POLL_INTERVAL);
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocols::opa::client::OUTPUT_LEN;
+use crate::protocols::opa::server::OPAState;
+use crate::protocols::opa::{AggregateProof, OPAPublicParams, RoundId};
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+/// The npy header, magic and lengths included, is padded to a multiple of this.
+const NPY_ALIGNMENT: usize = 64;
+
+/// Encoding of an input or output vector file.
+/// - `csv`: decimal values separated by commas, whitespace or newlines
+/// - `binary`: raw little-endian u32 values
+/// - `npy`: a one-dimensional NumPy array of unsigned or signed integers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    Csv,
+    Binary,
+    Npy,
+}
+
+impl DataFormat {
+    /// The format implied by the extension: `.npy`, `.bin` or `.raw`, and CSV otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("npy") => DataFormat::Npy,
+            Some("bin" | "raw") => DataFormat::Binary,
+            _ => DataFormat::Csv,
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> io::Result<Vec<u32>> {
+        match self {
+            DataFormat::Csv => parse_csv(bytes),
+            DataFormat::Binary => parse_binary(bytes),
+            DataFormat::Npy => parse_npy(bytes),
+        }
+    }
+
+    pub fn encode(self, values: &[u32]) -> Vec<u8> {
+        match self {
+            DataFormat::Csv => {
+                values.iter().map(|value| format!("{}\n", value)).collect::<String>().into_bytes()
+            }
+            DataFormat::Binary => values.iter().flat_map(|value| value.to_le_bytes()).collect(),
+            DataFormat::Npy => encode_npy(values),
+        }
+    }
+}
+
+/// Read a vector in `format`, or the format implied by the file extension.
+pub fn read_vector(path: &Path, format: Option<DataFormat>) -> io::Result<Vec<u32>> {
+    let format = format.unwrap_or_else(|| DataFormat::from_path(path));
+    format.decode(&fs::read(path)?).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Check a client input against the session it is submitted to: it must be non-empty, fit
+/// the masked payload, and every value must keep the aggregate decodable even if it were
+/// the only contribution. `max_value` tightens the value bound further.
+pub fn validate_input(values: &[u32], state: &OPAState, max_value: Option<u32>) -> io::Result<()> {
+    let invalid = |reason: String| Err(io::Error::new(io::ErrorKind::InvalidInput, reason));
+    if values.is_empty() {
+        return invalid("input holds no values".to_string());
+    }
+    if values.len() > OUTPUT_LEN {
+        return invalid(format!("input holds {} values; at most {} fit a submission", values.len(), OUTPUT_LEN));
+    }
+    let bound = state.max_aggregate_value().min(max_value.map_or(u64::MAX, u64::from));
+    if let Some((i, value)) = values.iter().enumerate().find(|&(_, &value)| u64::from(value) > bound) {
+        return invalid(format!("input[{}] = {} exceeds the largest allowed value {}", i, value, bound));
+    }
+    Ok(())
+}
+
+/// What the server knows about an aggregate, written next to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputMetadata {
+    pub round_id: RoundId,
+    /// Number of clients whose inputs were summed.
+    pub contributors: usize,
+    pub length: usize,
+    pub format: DataFormat,
+    /// Hex fingerprint of `params`.
+    pub fingerprint: String,
+    pub params: OPAPublicParams,
+    /// Proof that the aggregate is the sum of the listed clients' inputs, under verifiable
+    /// aggregation; check it with `AggregateProof::verify` against `params`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<AggregateProof>,
+}
+
+impl OutputMetadata {
+    /// Path of the metadata written alongside the aggregate at `output`.
+    pub fn path(output: &Path) -> PathBuf {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".meta.json");
+        PathBuf::from(path)
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?).map_err(io::Error::from)
+    }
+}
+
+/// Write the aggregate to `path` in `format`, and its metadata to `OutputMetadata::path`.
+pub fn write_output(
+    path: &Path,
+    format: DataFormat,
+    aggregate: &[u32],
+    round_id: RoundId,
+    contributors: usize,
+    params: OPAPublicParams,
+    proof: Option<AggregateProof>,
+) -> io::Result<()> {
+    let metadata = OutputMetadata {
+        round_id,
+        contributors,
+        length: aggregate.len(),
+        format,
+        fingerprint: params.fingerprint().iter().map(|b| format!("{:02x}", b)).collect(),
+        params,
+        proof,
+    };
+    fs::write(path, format.encode(aggregate))?;
+    let json = serde_json::to_string_pretty(&metadata).expect("output metadata always serializes");
+    fs::write(OutputMetadata::path(path), json)
+}
+
+fn parse_csv(bytes: &[u8]) -> io::Result<Vec<u32>> {
+    let text = std::str::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            token.parse::<u32>().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("bad value '{}': {}", token, e))
+            })
+        })
+        .collect()
+}
+
+fn parse_binary(bytes: &[u8]) -> io::Result<Vec<u32>> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "binary input is not a whole number of u32 values",
+        ));
+    }
+    Ok(bytes.chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect())
+}
+
+/// Parse an npy file (format version 1 to 3) holding a C-ordered integer array, which is
+/// flattened. Every element must fit a u32.
+fn parse_npy(bytes: &[u8]) -> io::Result<Vec<u32>> {
+    let malformed = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, format!("npy: {}", reason));
+    if !bytes.starts_with(NPY_MAGIC) || bytes.len() < 10 {
+        return Err(malformed("missing magic"));
+    }
+    let (header_len, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize, 12),
+        _ => return Err(malformed("unsupported format version")),
+    };
+    let data_start = header_start + header_len;
+    let header = bytes
+        .get(header_start..data_start)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or_else(|| malformed("truncated header"))?;
+
+    let descr = npy_header_value(header, "descr").ok_or_else(|| malformed("no descr"))?;
+    let descr = descr.trim_matches(|c| c == '\'' || c == '"');
+    let fortran_order = npy_header_value(header, "fortran_order").ok_or_else(|| malformed("no fortran_order"))?;
+    let shape = npy_header_value(header, "shape").ok_or_else(|| malformed("no shape"))?;
+    let dims = shape
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse::<usize>().map_err(|_| malformed("bad shape")))
+        .collect::<io::Result<Vec<usize>>>()?;
+    if fortran_order == "True" && dims.iter().filter(|&&dim| dim > 1).count() > 1 {
+        return Err(malformed("Fortran-ordered arrays are not supported"));
+    }
+    let count = dims
+        .iter()
+        .try_fold(1usize, |count, &dim| count.checked_mul(dim))
+        .ok_or_else(|| malformed("bad shape"))?;
+
+    // little-endian ('<') or single-byte ('|') integers only
+    let (signed, width) = match descr.as_bytes() {
+        [b'<' | b'|', kind @ (b'u' | b'i'), width] if matches!(width, b'1' | b'2' | b'4' | b'8') => {
+            (*kind == b'i', (width - b'0') as usize)
+        }
+        _ => return Err(malformed(&format!("unsupported dtype '{}'", descr))),
+    };
+    let data = &bytes[data_start..];
+    if Some(data.len()) != count.checked_mul(width) {
+        return Err(malformed("data length does not match the shape"));
+    }
+    data.chunks_exact(width)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut raw = [0u8; 8];
+            raw[..width].copy_from_slice(chunk);
+            let value = if signed {
+                // sign-extend from the element width
+                let shift = 64 - 8 * width as u32;
+                i128::from((i64::from_le_bytes(raw) << shift) >> shift)
+            } else {
+                i128::from(u64::from_le_bytes(raw))
+            };
+            u32::try_from(value).map_err(|_| malformed(&format!("element {} = {} does not fit a u32", i, value)))
+        })
+        .collect()
+}
+
+/// The raw text of `key`'s value in an npy header dict, e.g. `'<u4'` or `(3, 4)`.
+fn npy_header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}'", key))? + key.len() + 2;
+    let rest = header[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = if rest.starts_with('(') { rest.find(')')? + 1 } else { rest.find(',')? };
+    Some(rest[..end].trim())
+}
+
+fn encode_npy(values: &[u32]) -> Vec<u8> {
+    let mut header = format!("{{'descr': '<u4', 'fortran_order': False, 'shape': ({},), }}", values.len());
+    // pad with spaces so the data starts aligned, ending the header with a newline
+    let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(NPY_ALIGNMENT) - unpadded));
+    header.push('\n');
+
+    let mut data = Vec::with_capacity(NPY_MAGIC.len() + 4 + header.len() + 4 * values.len());
+    data.extend_from_slice(NPY_MAGIC);
+    data.extend_from_slice(&[1, 0]);
+    data.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    data.extend_from_slice(header.as_bytes());
+    data.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+    data
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::server::Server;
+    use crate::protocols::opa::{OPAServer, OPASetupParameters};
+
+    // an npy file as NumPy writes it for `np.array(values, dtype=descr)`
+    fn npy(descr: &str, shape: &str, data: &[u8]) -> Vec<u8> {
+        let header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}\n", descr, shape);
+        [NPY_MAGIC, &[1, 0], &(header.len() as u16).to_le_bytes(), header.as_bytes(), data].concat()
+    }
+
+    #[test]
+    // test that every format round-trips and npy arrays of other integer types are read
+    fn test_data_formats() {
+        let values = vec![0, 7, 1 << 20, u32::MAX];
+        for format in [DataFormat::Csv, DataFormat::Binary, DataFormat::Npy] {
+            assert_eq!(format.decode(&format.encode(&values)).unwrap(), values);
+        }
+        assert_eq!(DataFormat::Npy.encode(&values).len() % 4, 0);
+        assert_eq!(DataFormat::from_path(Path::new("input.npy")), DataFormat::Npy);
+        assert_eq!(DataFormat::from_path(Path::new("input.bin")), DataFormat::Binary);
+        assert_eq!(DataFormat::Csv.decode(b"1, 2,3\n4").unwrap(), vec![1, 2, 3, 4]);
+
+        let bytes = npy("|u1", "(2, 2)", &[1, 2, 3, 4]);
+        assert_eq!(DataFormat::Npy.decode(&bytes).unwrap(), vec![1, 2, 3, 4]);
+        let bytes = npy("<i8", "(2,)", &[5i64.to_le_bytes(), 6i64.to_le_bytes()].concat());
+        assert_eq!(DataFormat::Npy.decode(&bytes).unwrap(), vec![5, 6]);
+
+        // negative, oversized, mistyped and truncated data is refused
+        assert!(DataFormat::Npy.decode(&npy("<i2", "(1,)", &(-1i16).to_le_bytes())).is_err());
+        assert!(DataFormat::Npy.decode(&npy("<u8", "(1,)", &(1u64 << 32).to_le_bytes())).is_err());
+        assert!(DataFormat::Npy.decode(&npy("<f4", "(1,)", &1f32.to_le_bytes())).is_err());
+        assert!(DataFormat::Npy.decode(&npy("<u4", "(2,)", &1u32.to_le_bytes())).is_err());
+        assert!(DataFormat::Binary.decode(&[1, 2, 3]).is_err());
+        assert!(DataFormat::Csv.decode(b"1,-2").is_err());
+    }
+
+    #[test]
+    // test that inputs are checked against the submission length and the decodable value range
+    fn test_validate_input() {
+        let state = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3)).get_state().clone();
+        assert!(validate_input(&[1, 2, u32::MAX], &state, None).is_ok());
+        assert!(validate_input(&[], &state, None).is_err());
+        assert!(validate_input(&vec![1; OUTPUT_LEN + 1], &state, None).is_err());
+        assert!(validate_input(&[1, 200], &state, Some(100)).is_err());
+
+        // a large security parameter leaves fewer bits for the values
+        let state = OPAServer::new(OPASetupParameters::new(100, 1, 2, 3)).get_state().clone();
+        assert_eq!(state.max_aggregate_value(), 254);
+        let err = validate_input(&[1, 255], &state, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod config;
+pub mod deploy;
+pub mod format;
+
+pub use config::{DeploymentConfig, ProtocolConfig};
+pub use deploy::{run_client, run_committee, run_from_args, run_server};
+pub use format::{DataFormat, OutputMetadata};
@@ -2,6 +2,7 @@ pub mod crypto;
 pub mod util;
 pub mod protocols;
 pub mod communicator;
+pub mod deploy;
 
 #[cfg(feature = "simulator")]
 pub mod simulator;
\ No newline at end of file
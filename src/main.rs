@@ -33,7 +33,12 @@ fn main() {
     println!("Secret: {:?}", secret);
     println!("Opened secret: {:?}", secret);
 
-    let opa_server = OPAServer::new(OPASetupParameters::new(40, 16, 16, 31));
+    let parameters = OPASetupParameters::new(40, 15, 16, 31);
+    if let Err(e) = parameters.validate() {
+        eprintln!("Invalid parameters: {}", e);
+        std::process::exit(1);
+    }
+    let opa_server = OPAServer::new(parameters);
     let state = opa_server.get_state();
     println!("State: {:?}", state);
 
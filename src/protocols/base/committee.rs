@@ -1,3 +1,6 @@
+use std::io;
+use std::path::Path;
+
 pub trait Committee {
     type ServerState;
 
@@ -6,4 +9,16 @@ pub trait Committee {
     fn retrieve_inputs(&mut self);
     fn aggregate(&mut self);
     fn send_output(&mut self);
+
+    /// Optionally persist aggregated shares under `dir` so a restarted member can resume.
+    /// Default implementation keeps no checkpoints.
+    fn set_checkpoint_dir(&mut self, _dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Reload the aggregated share of the current round from a checkpoint instead of
+    /// retrieving and aggregating again. Returns whether there was one to resume from.
+    fn resume(&mut self) -> bool {
+        false
+    }
 }
@@ -1,9 +1,11 @@
 pub mod client;
 pub mod server;
 pub mod committee;
+pub mod party;
 pub mod protocol;
 
 pub use client::Client;
 pub use server::Server;
 pub use committee::Committee;
+pub use party::Party;
 pub use protocol::Protocol;
@@ -0,0 +1,33 @@
+use std::io;
+use std::path::Path;
+
+/// A device that submits an input like a `Client` and, when the server puts it on the
+/// committee, also serves as a member like a `Committee`, under one identity (its port)
+/// and over one connection.
+pub trait Party<T> {
+    type ServerState;
+
+    fn new(port: u16) -> Self;
+    fn port(&self) -> u16;
+    fn set_server_state(&mut self, state: Self::ServerState);
+
+    // client role
+    fn set_input(&mut self, input: Vec<T>);
+    fn encrypt_input(&mut self);
+    fn send_input(&mut self);
+
+    /// Whether the current server state puts this party on the committee. The committee
+    /// role's methods may only be called if it does.
+    fn on_committee(&self) -> bool;
+
+    // committee role
+    fn retrieve_inputs(&mut self);
+    fn aggregate(&mut self);
+    fn send_output(&mut self);
+
+    /// Optionally persist the committee role's aggregated shares under `dir`.
+    /// Default implementation keeps no checkpoints.
+    fn set_checkpoint_dir(&mut self, _dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 use super::client::Client;
 use super::committee::Committee;
+use super::party::Party;
 use super::server::Server;
 
-// a protocol binds compatible Server, Client, and Committee implementations together
+// a protocol binds compatible Server, Client, and Committee implementations together,
+// and a Party playing both the client and committee roles
 pub trait Protocol {
     type Input;
     type Server: Server;
     type Client: Client<Self::Input, ServerState = <Self::Server as Server>::State>;
     type Committee: Committee<ServerState = <Self::Server as Server>::State>;
+    type Party: Party<Self::Input, ServerState = <Self::Server as Server>::State>;
 }
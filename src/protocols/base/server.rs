@@ -1,4 +1,6 @@
 use crate::communicator::Communicator;
+use std::io;
+use std::path::Path;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::sync::mpsc::Sender;
@@ -15,12 +17,32 @@ pub trait Server: Sized {
     /// without needing access to the server instance.
     fn aggregate(state: &Self::State);
     fn get_committee_port_offsets(&self) -> Vec<u16>;
+    /// Committee port offsets in `state`, which a committee drawn from the clients only has
+    /// once the server listens.
+    fn committee_port_offsets(state: &Self::State) -> Vec<u16>;
+
+    /// Stop accepting client inputs and fix the set that aggregation will cover.
+    /// Default implementation is a no-op for servers without an explicit input phase.
+    fn close_inputs(_state: &Self::State) {}
 
     /// Optionally allow the caller (e.g., simulator) to install an output channel
     /// that the server can use to send results back to the main thread.
     /// Default implementation is a no-op so servers that don't use it can ignore it.
     fn set_output_channel(&mut self, _sender: Sender<Vec<u32>>) {}
 
+    /// Optionally persist round state under `dir` and resume an unfinished round found there
+    /// when the communicator is set up. Default implementation keeps no checkpoints.
+    fn set_checkpoint_dir(&mut self, _dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Draw the committee from the clients with ids in `population`, ranked by a hash of
+    /// `beacon` (or the population) and the round, instead of assigning it. Must be called
+    /// before the communicator is set up. Default implementation does not support it.
+    fn select_committee(&mut self, _population: &[u64], _beacon: Option<[u8; 32]>) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "this server cannot draw its committee"))
+    }
+
     fn set_communicator(&mut self, comm: Communicator);
     fn get_communicator(&mut self) -> &mut Communicator;
 
@@ -44,7 +66,7 @@ pub trait Server: Sized {
         println!("Server listening on port {}", port);
         
         // send the state back to signal that the server is ready
-        let _ = state_sender.send(self.get_state().clone()).expect("failed to send state");
+        state_sender.send(self.get_state().clone()).expect("failed to send state");
         
         self.get_communicator().listen_loop().expect("failed to listen");
     }
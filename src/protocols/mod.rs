@@ -5,9 +5,11 @@ pub mod base;
 pub use base::client as client;
 pub use base::server as server;
 pub use base::committee as committee;
+pub use base::party as party;
 
 pub use base::client::Client;
 pub use base::server::Server;
 pub use base::committee::Committee;
+pub use base::party::Party;
 
 pub use base::protocol::Protocol;
@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+use std::io;
+use std::sync::Mutex;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::communicator::SessionId;
+use crate::crypto::prg::{default_prg, populate_random_bytes};
+use crate::protocols::opa::client_set::{ClientId, Commitment};
+use crate::protocols::opa::round::RoundId;
+
+const ENROLL_DOMAIN: &[u8] = b"jagguar/opa/admission/enroll/v1";
+const ADMIT_DOMAIN: &[u8] = b"jagguar/opa/admission/admit/v1";
+const TOKEN_DOMAIN: &[u8] = b"jagguar/opa/admission/token/v1";
+const SUBMISSION_DOMAIN: &[u8] = b"jagguar/opa/admission/submission/v1";
+const CLIENT_ID_DOMAIN: &[u8] = b"jagguar/opa/admission/client-id/v1";
+
+/// Request tags, the first byte of a request to the server's registry.
+const ENROLL: u8 = 0;
+const ADMIT: u8 = 1;
+
+/// A client's public Ed25519 key, which identifies it to the registry.
+pub type IdentityKey = [u8; 32];
+/// Proof that the registry admitted an identity to one round.
+pub type AdmissionToken = [u8; 32];
+
+/// Length of the credentials a client sends in front of its submission.
+pub const CREDENTIALS_LEN: usize = 32 + 32 + 64;
+
+/// What a client proves about a submission: the identity it is made under, the token
+/// admitting that identity to the round, and a signature over the submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credentials {
+    pub identity: IdentityKey,
+    pub token: AdmissionToken,
+    pub signature: [u8; 64],
+}
+
+impl Credentials {
+    /// Sign the submission with digest `digest` under `key`, presenting `token`.
+    pub fn sign(key: &SigningKey, session_id: SessionId, token: AdmissionToken, digest: &Commitment) -> Self {
+        let message = signed_message(SUBMISSION_DOMAIN, session_id, &[&token, digest]);
+        Self {
+            identity: key.verifying_key().to_bytes(),
+            token,
+            signature: key.sign(&message).to_bytes(),
+        }
+    }
+
+    /// `[identity][token][signature]`.
+    pub fn to_bytes(&self) -> [u8; CREDENTIALS_LEN] {
+        let mut bytes = [0u8; CREDENTIALS_LEN];
+        bytes[..32].copy_from_slice(&self.identity);
+        bytes[32..64].copy_from_slice(&self.token);
+        bytes[64..].copy_from_slice(&self.signature);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; CREDENTIALS_LEN]) -> Self {
+        Self {
+            identity: bytes[..32].try_into().unwrap(),
+            token: bytes[32..64].try_into().unwrap(),
+            signature: bytes[64..].try_into().unwrap(),
+        }
+    }
+}
+
+/// The id a client enrolled under `identity` is listed by in client sets.
+pub fn client_id(identity: &IdentityKey) -> ClientId {
+    let digest = Sha256::new().chain_update(CLIENT_ID_DOMAIN).chain_update(identity).finalize();
+    ClientId::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+/// Request enrolling `key`'s identity in session `session_id`:
+/// `[0][identity][signature]`. Enrolling again is harmless.
+pub fn enroll_request(key: &SigningKey, session_id: SessionId) -> Vec<u8> {
+    let identity = key.verifying_key().to_bytes();
+    let signature = key.sign(&signed_message(ENROLL_DOMAIN, session_id, &[&identity]));
+    [&[ENROLL][..], &identity, &signature.to_bytes()].concat()
+}
+
+/// Request a token admitting `key`'s identity to round `round_id`:
+/// `[1][identity][round_id u64][signature]`. The reply is the token.
+pub fn admit_request(key: &SigningKey, session_id: SessionId, round_id: RoundId) -> Vec<u8> {
+    let identity = key.verifying_key().to_bytes();
+    let round = round_id.to_le_bytes();
+    let signature = key.sign(&signed_message(ADMIT_DOMAIN, session_id, &[&identity, &round]));
+    [&[ADMIT][..], &identity, &round, &signature.to_bytes()].concat()
+}
+
+/// The server's record of enrolled client identities. It answers enrollment and admission
+/// requests and checks the credentials of every submission, so each identity contributes
+/// at most once per round. Without an allowlist anyone may enroll, which stops replayed
+/// and duplicated submissions but not a party enrolling many identities.
+pub struct ClientRegistry {
+    session_id: SessionId,
+    /// Key tokens are derived under; they are not stored, so none survive a restart.
+    secret: [u8; 32],
+    enrollment: Mutex<Enrollment>,
+}
+
+// the token secret stays out of logs
+impl std::fmt::Debug for ClientRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientRegistry").field("session_id", &self.session_id).finish_non_exhaustive()
+    }
+}
+
+#[derive(Default)]
+struct Enrollment {
+    allowlist: Option<HashSet<IdentityKey>>,
+    enrolled: HashSet<IdentityKey>,
+}
+
+impl ClientRegistry {
+    pub fn new(session_id: SessionId) -> Self {
+        let mut secret = [0u8; 32];
+        populate_random_bytes(&mut secret, &mut default_prg());
+        Self { session_id, secret, enrollment: Mutex::new(Enrollment::default()) }
+    }
+
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    /// Only let `identities` enroll, in addition to those allowed before.
+    pub fn allow(&self, identities: impl IntoIterator<Item = IdentityKey>) {
+        let mut enrollment = self.enrollment.lock().unwrap();
+        enrollment.allowlist.get_or_insert_with(HashSet::new).extend(identities);
+    }
+
+    pub fn is_enrolled(&self, identity: &IdentityKey) -> bool {
+        self.enrollment.lock().unwrap().enrolled.contains(identity)
+    }
+
+    /// Answer an enrollment or admission request, replying with nothing or the token.
+    pub fn answer(&self, request: &[u8]) -> io::Result<Vec<u8>> {
+        let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed admission request");
+        let (&tag, body) = request.split_first().ok_or_else(malformed)?;
+        match (tag, body.len()) {
+            (ENROLL, 96) => {
+                let identity: IdentityKey = body[..32].try_into().unwrap();
+                verify(&identity, &signed_message(ENROLL_DOMAIN, self.session_id, &[&identity]), &body[32..])?;
+                self.enroll(identity)?;
+                Ok(Vec::new())
+            }
+            (ADMIT, 104) => {
+                let identity: IdentityKey = body[..32].try_into().unwrap();
+                let round = &body[32..40];
+                let message = signed_message(ADMIT_DOMAIN, self.session_id, &[&identity, round]);
+                verify(&identity, &message, &body[40..])?;
+                if !self.is_enrolled(&identity) {
+                    return Err(io::Error::new(io::ErrorKind::PermissionDenied, "identity is not enrolled"));
+                }
+                let round_id = RoundId::from_le_bytes(round.try_into().unwrap());
+                Ok(self.token(&identity, round_id).to_vec())
+            }
+            _ => Err(malformed()),
+        }
+    }
+
+    fn enroll(&self, identity: IdentityKey) -> io::Result<()> {
+        let mut enrollment = self.enrollment.lock().unwrap();
+        if enrollment.allowlist.as_ref().is_some_and(|allowed| !allowed.contains(&identity)) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "identity is not on the allowlist"));
+        }
+        enrollment.enrolled.insert(identity);
+        Ok(())
+    }
+
+    /// Check that credentials carry a token admitting their identity to round `round_id`,
+    /// before the submission behind them is read.
+    pub fn check_admitted(&self, credentials: &Credentials, round_id: RoundId) -> io::Result<()> {
+        if credentials.token != self.token(&credentials.identity, round_id) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "submission is not admitted to the round",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that a submission to round `round_id` with digest `digest` carries a token
+    /// for that round and is signed by the identity it names, returning the identity.
+    pub fn verify_submission(
+        &self,
+        credentials: &Credentials,
+        round_id: RoundId,
+        digest: &Commitment,
+    ) -> io::Result<IdentityKey> {
+        self.check_admitted(credentials, round_id)?;
+        let message = signed_message(SUBMISSION_DOMAIN, self.session_id, &[&credentials.token, digest]);
+        verify(&credentials.identity, &message, &credentials.signature)?;
+        Ok(credentials.identity)
+    }
+
+    fn token(&self, identity: &IdentityKey, round_id: RoundId) -> AdmissionToken {
+        Sha256::new()
+            .chain_update(TOKEN_DOMAIN)
+            .chain_update(self.secret)
+            .chain_update(self.session_id.to_le_bytes())
+            .chain_update(round_id.to_le_bytes())
+            .chain_update(identity)
+            .finalize()
+            .into()
+    }
+}
+
+/// What gets signed: a domain, the session and the request's fields, so a signature is
+/// good for nothing but the request it was made for.
+fn signed_message(domain: &[u8], session_id: SessionId, parts: &[&[u8]]) -> Vec<u8> {
+    let mut message = [domain, &session_id.to_le_bytes()].concat();
+    for part in parts {
+        message.extend_from_slice(part);
+    }
+    message
+}
+
+fn verify(identity: &IdentityKey, message: &[u8], signature: &[u8]) -> io::Result<()> {
+    let denied = || io::Error::new(io::ErrorKind::PermissionDenied, "invalid signature");
+    let key = VerifyingKey::from_bytes(identity).map_err(|_| denied())?;
+    let signature = Signature::from_slice(signature).map_err(|_| denied())?;
+    key.verify_strict(message, &signature).map_err(|_| denied())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> SigningKey {
+        SigningKey::generate(&mut default_prg())
+    }
+
+    #[test]
+    // test that only enrolled identities get tokens, and only for the round and session they asked for
+    fn test_admission() {
+        let registry = ClientRegistry::new(4);
+        let key = identity();
+        let refused = registry.answer(&admit_request(&key, 4, 0)).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::PermissionDenied);
+
+        // an enrollment signed for another session does not count
+        assert!(registry.answer(&enroll_request(&key, 5)).is_err());
+        registry.answer(&enroll_request(&key, 4)).unwrap();
+        assert!(registry.is_enrolled(&key.verifying_key().to_bytes()));
+        let token: AdmissionToken = registry.answer(&admit_request(&key, 4, 0)).unwrap().try_into().unwrap();
+
+        let digest = [7u8; 32];
+        let credentials = Credentials::sign(&key, 4, token, &digest);
+        assert_eq!(Credentials::from_bytes(&credentials.to_bytes()), credentials);
+        assert_eq!(registry.verify_submission(&credentials, 0, &digest).unwrap(), credentials.identity);
+        // the token is for round 0 only, and the signature for this submission only
+        assert!(registry.verify_submission(&credentials, 1, &digest).is_err());
+        assert!(registry.verify_submission(&credentials, 0, &[8u8; 32]).is_err());
+        // another identity cannot present the token
+        let thief = Credentials::sign(&identity(), 4, token, &digest);
+        assert!(registry.verify_submission(&thief, 0, &digest).is_err());
+        assert!(registry.answer(&[ADMIT, 1, 2]).is_err());
+    }
+
+    #[test]
+    // test that an allowlist keeps every other identity from enrolling
+    fn test_allowlist() {
+        let registry = ClientRegistry::new(0);
+        let (allowed, other) = (identity(), identity());
+        registry.allow([allowed.verifying_key().to_bytes()]);
+        registry.answer(&enroll_request(&allowed, 0)).unwrap();
+        let refused = registry.answer(&enroll_request(&other, 0)).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::PermissionDenied);
+        assert!(!registry.is_enrolled(&other.verifying_key().to_bytes()));
+    }
+}
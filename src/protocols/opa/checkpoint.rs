@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::communicator::SessionId;
+use crate::crypto::{FieldBytes, FIELD_ELEMENT_BYTES};
+use crate::protocols::opa::client_set::{ClientEntry, ClientSet};
+use crate::protocols::opa::round::RoundId;
+use crate::protocols::opa::server::CiphertextSum;
+use crate::protocols::opa::verification::ClientCommitments;
+use curve25519_dalek::ristretto::CompressedRistretto;
+
+/// Journal records of the in-flight round: `[tag u8][len u32][payload]`.
+const RECORD_SUBMISSION: u8 = 1;
+const RECORD_INPUTS_CLOSED: u8 = 2;
+const RECORD_COMMITTEE_OUTPUT: u8 = 3;
+const RECORD_COMMITMENT: u8 = 4;
+/// Record of the keys journal: `[tag u8][len u32][client id u64][key shares]`.
+const RECORD_KEY: u8 = 5;
+
+/// Everything the server had durably recorded about an unfinished round.
+#[derive(Debug, Default)]
+pub struct RoundCheckpoint {
+    pub round_id: RoundId,
+    /// Accepted clients in arrival order, with the share section retained for each.
+    pub accepted: Vec<(ClientEntry, Vec<u8>)>,
+    /// Running sum over exactly the `accepted` clients.
+    pub ciphertext_sum: CiphertextSum,
+    pub inputs_closed: bool,
+    /// Committee output messages admitted before the crash.
+    pub committee_outputs: Vec<Vec<u8>>,
+    /// Commitments of the clients to their inputs and seeds, under verifiable aggregation.
+    pub commitments: HashMap<u64, ClientCommitments>,
+}
+
+/// Durable record of a server session in a local directory, so a restarted server can
+/// resume its in-flight round. Files are named after the session:
+/// - `server-<session>.setup`: the public seed and current round id
+/// - `server-<session>.round`: append-only journal of accepted clients, input closing,
+///   and committee outputs of the in-flight round
+/// - `server-<session>.aggregate`: snapshot of the running ciphertext sum, its carries and
+///   the sum of range-proven commitments
+/// - `server-<session>.keys`: journal of registered client keys, appended to as clients
+///   register and compacted to the latest key of each client at every round
+#[derive(Debug)]
+pub struct ServerCheckpoint {
+    dir: PathBuf,
+    session_id: SessionId,
+}
+
+impl ServerCheckpoint {
+    pub fn new(dir: &Path, session_id: SessionId) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_path_buf(), session_id })
+    }
+
+    fn path(&self, kind: &str) -> PathBuf {
+        self.dir.join(format!("server-{}.{}", self.session_id, kind))
+    }
+
+    /// Start journaling round `round_id`, discarding whatever was recorded for earlier rounds
+    /// and every key a client has since registered again.
+    pub fn begin_round(&self, succinct_seed: [u8; 32], round_id: RoundId) -> io::Result<()> {
+        let mut setup = succinct_seed.to_vec();
+        setup.extend_from_slice(&round_id.to_le_bytes());
+        write_atomically(&self.path("setup"), &setup)?;
+        self.compact_keys()?;
+        remove_if_exists(&self.path("aggregate"))?;
+        write_atomically(&self.path("round"), &round_id.to_le_bytes())
+    }
+
+    /// The public seed and round id recorded by the last `begin_round`, if any.
+    pub fn load_setup(&self) -> io::Result<Option<([u8; 32], RoundId)>> {
+        let Some(setup) = read_if_exists(&self.path("setup"))? else {
+            return Ok(None);
+        };
+        if setup.len() != 40 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed setup checkpoint"));
+        }
+        let round_id = RoundId::from_le_bytes(setup[32..].try_into().unwrap());
+        Ok(Some((setup[..32].try_into().unwrap(), round_id)))
+    }
+
+    /// Record an accepted client and the running sum that now includes it. The journal
+    /// entry is written first, so a crash in between only loses the entry, never the sum.
+    ///
+    /// The whole sum is rewritten for every client, which costs I/O linear in the vector
+    /// length per client. Snapshotting less often would drop the clients accepted since
+    /// the last snapshot on a restart, although they were told their input counts.
+    pub fn record_submission(&self, entry: &ClientEntry, section: &[u8], sum: &CiphertextSum) -> io::Result<()> {
+        let mut payload = Vec::with_capacity(40 + section.len());
+        payload.extend_from_slice(&entry.id.to_le_bytes());
+        payload.extend_from_slice(&entry.digest);
+        payload.extend_from_slice(section);
+        append_record(&self.path("round"), RECORD_SUBMISSION, &payload)?;
+
+        // [num_clients u64][payload_len u64][slots u64][sum u128 x slots][carry u32 x slots]
+        // [range commitment x rest]
+        let mut aggregate = Vec::with_capacity(24 + sum.sum.len() * 20 + sum.range_commitments.len() * 32);
+        aggregate.extend_from_slice(&(sum.num_clients as u64).to_le_bytes());
+        aggregate.extend_from_slice(&sum.payload_len.map_or(u64::MAX, |len| len as u64).to_le_bytes());
+        aggregate.extend_from_slice(&(sum.sum.len() as u64).to_le_bytes());
+        for x in &sum.sum {
+            aggregate.extend_from_slice(&x.to_le_bytes());
+        }
+        for carry in &sum.carries {
+            aggregate.extend_from_slice(&carry.to_le_bytes());
+        }
+        for commitment in &sum.range_commitments {
+            aggregate.extend_from_slice(commitment.compress().as_bytes());
+        }
+        write_atomically(&self.path("aggregate"), &aggregate)
+    }
+
+    pub fn record_inputs_closed(&self) -> io::Result<()> {
+        append_record(&self.path("round"), RECORD_INPUTS_CLOSED, &[])
+    }
+
+    pub fn record_committee_output(&self, message: &[u8]) -> io::Result<()> {
+        append_record(&self.path("round"), RECORD_COMMITTEE_OUTPUT, message)
+    }
+
+    /// Record a client's commitments, ahead of its submission.
+    pub fn record_commitments(&self, id: u64, commitments: &ClientCommitments) -> io::Result<()> {
+        let payload = [&id.to_le_bytes()[..], &commitments.to_bytes()].concat();
+        append_record(&self.path("round"), RECORD_COMMITMENT, &payload)
+    }
+
+    /// Record the key shares a client registered; later registrations replace earlier ones.
+    pub fn record_key(&self, id: u64, section: &[u8]) -> io::Result<()> {
+        let mut payload = id.to_le_bytes().to_vec();
+        payload.extend_from_slice(section);
+        let mut file = OpenOptions::new().create(true).append(true).open(self.path("keys"))?;
+        file.write_all(&record(RECORD_KEY, &payload))?;
+        file.sync_data()
+    }
+
+    /// Rewrite the keys journal with only the latest key of each client.
+    fn compact_keys(&self) -> io::Result<()> {
+        let keys = self.load_keys()?;
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut ids: Vec<&u64> = keys.keys().collect();
+        ids.sort_unstable();
+        let journal: Vec<u8> = ids
+            .into_iter()
+            .flat_map(|id| record(RECORD_KEY, &[&id.to_le_bytes()[..], &keys[id]].concat()))
+            .collect();
+        write_atomically(&self.path("keys"), &journal)
+    }
+
+    /// Drop the journal of a finished round.
+    pub fn finish_round(&self) -> io::Result<()> {
+        remove_if_exists(&self.path("aggregate"))?;
+        remove_if_exists(&self.path("round"))
+    }
+
+    /// The unfinished round, if one was being journaled. Clients journaled after the last
+    /// sum snapshot are dropped, since their ciphertext never made it into the sum. The
+    /// journal is rewritten to the records kept, so that records appended from now on
+    /// follow them rather than a dropped client or a torn record.
+    pub fn load_round(&self) -> io::Result<Option<RoundCheckpoint>> {
+        let Some(journal) = read_if_exists(&self.path("round"))? else {
+            return Ok(None);
+        };
+        if journal.len() < 8 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed round checkpoint"));
+        }
+        let mut round = RoundCheckpoint {
+            round_id: RoundId::from_le_bytes(journal[..8].try_into().unwrap()),
+            ..RoundCheckpoint::default()
+        };
+        let records = records(&journal[8..]);
+        for &(tag, payload) in &records {
+            match tag {
+                RECORD_SUBMISSION if payload.len() >= 40 => {
+                    let entry = ClientEntry {
+                        id: u64::from_le_bytes(payload[..8].try_into().unwrap()),
+                        digest: payload[8..40].try_into().unwrap(),
+                    };
+                    round.accepted.push((entry, payload[40..].to_vec()));
+                }
+                RECORD_INPUTS_CLOSED => round.inputs_closed = true,
+                RECORD_COMMITTEE_OUTPUT => round.committee_outputs.push(payload.to_vec()),
+                RECORD_COMMITMENT if payload.len() == 8 + ClientCommitments::LEN => {
+                    let id = u64::from_le_bytes(payload[..8].try_into().unwrap());
+                    let commitments = ClientCommitments::from_bytes(payload[8..].try_into().unwrap());
+                    round.commitments.insert(id, commitments);
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown round checkpoint record")),
+            }
+        }
+
+        if let Some(aggregate) = read_if_exists(&self.path("aggregate"))? {
+            let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed aggregate checkpoint");
+            if aggregate.len() < 24 {
+                return Err(malformed());
+            }
+            let num_clients = u64::from_le_bytes(aggregate[..8].try_into().unwrap()) as usize;
+            let payload_len = u64::from_le_bytes(aggregate[8..16].try_into().unwrap());
+            let slots = u64::from_le_bytes(aggregate[16..24].try_into().unwrap()) as usize;
+            // each slot of the sum takes 16 bytes and its carry 4, each commitment 32
+            let rest = aggregate.len() - 24;
+            if slots > rest / 20 || !(rest - slots * 20).is_multiple_of(32) {
+                return Err(malformed());
+            }
+            let (sum, rest) = aggregate[24..].split_at(slots * 16);
+            let (carries, commitments) = rest.split_at(slots * 4);
+            round.ciphertext_sum = CiphertextSum {
+                payload_len: (payload_len != u64::MAX).then_some(payload_len as usize),
+                sum: sum.chunks_exact(16).map(|chunk| u128::from_le_bytes(chunk.try_into().unwrap())).collect(),
+                carries: carries
+                    .chunks_exact(4)
+                    .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect(),
+                range_commitments: commitments
+                    .chunks_exact(32)
+                    .map(|chunk| {
+                        CompressedRistretto(chunk.try_into().unwrap())
+                            .decompress()
+                            .ok_or_else(malformed)
+                    })
+                    .collect::<io::Result<Vec<_>>>()?,
+                num_clients,
+            };
+        }
+        if round.ciphertext_sum.num_clients > round.accepted.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "aggregate covers unrecorded clients"));
+        }
+        round.accepted.truncate(round.ciphertext_sum.num_clients);
+
+        let mut kept = journal[..8].to_vec();
+        let mut submissions = 0;
+        for &(tag, payload) in &records {
+            if tag == RECORD_SUBMISSION {
+                submissions += 1;
+                if submissions > round.accepted.len() {
+                    continue;
+                }
+            }
+            kept.extend_from_slice(&record(tag, payload));
+        }
+        if kept.len() != journal.len() {
+            write_atomically(&self.path("round"), &kept)?;
+        }
+        Ok(Some(round))
+    }
+
+    /// The latest key share section registered by each client.
+    pub fn load_keys(&self) -> io::Result<HashMap<u64, Vec<u8>>> {
+        let mut keys = HashMap::new();
+        if let Some(journal) = read_if_exists(&self.path("keys"))? {
+            for (tag, payload) in records(&journal) {
+                if tag != RECORD_KEY || payload.len() < 8 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown key checkpoint record"));
+                }
+                keys.insert(u64::from_le_bytes(payload[..8].try_into().unwrap()), payload[8..].to_vec());
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// A committee member's aggregated share for the current round, as restored from disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitteeCheckpoint {
+    pub round_id: RoundId,
+    pub client_set: ClientSet,
+    pub output_share: Vec<FieldBytes>,
+}
+
+impl CommitteeCheckpoint {
+    fn path(dir: &Path, session_id: SessionId, port: u16) -> PathBuf {
+        dir.join(format!("committee-{}-{}.share", session_id, port))
+    }
+
+    /// Persist the aggregated share of the member on `port`, replacing any earlier round's.
+    pub fn save(&self, dir: &Path, session_id: SessionId, port: u16) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let client_set = self.client_set.to_bytes();
+        let mut data = Vec::new();
+        data.extend_from_slice(&self.round_id.to_le_bytes());
+        data.extend_from_slice(&(client_set.len() as u32).to_le_bytes());
+        data.extend_from_slice(&client_set);
+        for x in &self.output_share {
+            data.extend_from_slice(x);
+        }
+        write_atomically(&Self::path(dir, session_id, port), &data)
+    }
+
+    pub fn load(dir: &Path, session_id: SessionId, port: u16) -> io::Result<Option<Self>> {
+        let Some(data) = read_if_exists(&Self::path(dir, session_id, port))? else {
+            return Ok(None);
+        };
+        let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed committee checkpoint");
+        let mut cursor = Cursor::new(&data);
+        let mut round_id = [0u8; 8];
+        cursor.read_exact(&mut round_id)?;
+        let mut set_len = [0u8; 4];
+        cursor.read_exact(&mut set_len)?;
+        let mut set_bytes = vec![0u8; u32::from_le_bytes(set_len) as usize];
+        cursor.read_exact(&mut set_bytes)?;
+        let client_set = ClientSet::from_bytes(&set_bytes)?;
+        let shares = &data[cursor.position() as usize..];
+        if shares.len() % FIELD_ELEMENT_BYTES != 0 {
+            return Err(malformed());
+        }
+        Ok(Some(Self {
+            round_id: RoundId::from_le_bytes(round_id),
+            client_set,
+            output_share: shares.chunks_exact(FIELD_ELEMENT_BYTES).map(|x| x.try_into().unwrap()).collect(),
+        }))
+    }
+}
+
+fn record(tag: u8, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(5 + payload.len());
+    data.push(tag);
+    data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    data.extend_from_slice(payload);
+    data
+}
+
+fn append_record(path: &Path, tag: u8, payload: &[u8]) -> io::Result<()> {
+    // the journal is created by begin_round, so appending to a missing one is an error
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    file.write_all(&record(tag, payload))?;
+    file.sync_data()
+}
+
+/// Complete records of a journal; a record torn by a crash mid-append ends the journal.
+fn records(mut journal: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut records = Vec::new();
+    while journal.len() >= 5 {
+        let len = u32::from_le_bytes(journal[1..5].try_into().unwrap()) as usize;
+        let Some(payload) = journal.get(5..5 + len) else {
+            break;
+        };
+        records.push((journal[0], payload));
+        journal = &journal[5 + len..];
+    }
+    records
+}
+
+/// Replace `path` with `data`, so readers see either the old or the new contents.
+pub(crate) fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+    use curve25519_dalek::scalar::Scalar;
+
+    fn checkpoint_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jagguar-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn entry(id: u64) -> ClientEntry {
+        ClientEntry { id, digest: [id as u8; 32] }
+    }
+
+    #[test]
+    // test that a journaled round is restored up to the last sum snapshot and the last whole record
+    fn test_server_checkpoint() {
+        let dir = checkpoint_dir("server-checkpoint");
+        let checkpoint = ServerCheckpoint::new(&dir, 3).unwrap();
+        assert!(checkpoint.load_round().unwrap().is_none());
+        checkpoint.begin_round([9u8; 32], 4).unwrap();
+        assert_eq!(checkpoint.load_setup().unwrap(), Some(([9u8; 32], 4)));
+
+        let mut sum = CiphertextSum::default();
+        sum.add(8, &[5, 6]).unwrap();
+        checkpoint.record_submission(&entry(2), b"shares of 2", &sum).unwrap();
+        sum.add(8, &[1, u128::MAX]).unwrap();
+        sum.add_range_commitments(&[RISTRETTO_BASEPOINT_POINT, RISTRETTO_BASEPOINT_POINT * Scalar::from(2u8)]);
+        let commitments = ClientCommitments { input: [7u8; 32], seed: [8u8; 32] };
+        checkpoint.record_commitments(7, &commitments).unwrap();
+        checkpoint.record_submission(&entry(7), b"shares of 7", &sum).unwrap();
+        checkpoint.record_key(7, b"key of 7").unwrap();
+        checkpoint.record_inputs_closed().unwrap();
+        checkpoint.record_committee_output(b"committee output").unwrap();
+        let round = checkpoint.load_round().unwrap().unwrap();
+        assert_eq!(round.round_id, 4);
+        assert_eq!(
+            round.accepted,
+            vec![(entry(2), b"shares of 2".to_vec()), (entry(7), b"shares of 7".to_vec())]
+        );
+        assert_eq!(round.ciphertext_sum.sum, vec![6, 5]);
+        assert_eq!(round.ciphertext_sum.carries, vec![0, 1]);
+        assert_eq!(round.ciphertext_sum.range_commitments, sum.range_commitments);
+        assert_eq!(round.ciphertext_sum.payload_len, Some(8));
+        assert_eq!(round.commitments.get(&7), Some(&commitments));
+        assert!(round.inputs_closed);
+        assert_eq!(round.committee_outputs, vec![b"committee output".to_vec()]);
+        assert_eq!(checkpoint.load_keys().unwrap().get(&7), Some(&b"key of 7".to_vec()));
+
+        // a client journaled right before a crash, without its ciphertext in the sum snapshot, is dropped
+        let mut payload = 9u64.to_le_bytes().to_vec();
+        payload.extend_from_slice(&[9u8; 32]);
+        append_record(&checkpoint.path("round"), RECORD_SUBMISSION, &payload).unwrap();
+        assert_eq!(checkpoint.load_round().unwrap().unwrap().accepted.len(), 2);
+
+        // as is a record torn halfway through being appended
+        let mut journal = OpenOptions::new().append(true).open(checkpoint.path("round")).unwrap();
+        journal.write_all(&record(RECORD_COMMITTEE_OUTPUT, b"torn")[..6]).unwrap();
+        assert_eq!(checkpoint.load_round().unwrap().unwrap().committee_outputs.len(), 1);
+
+        checkpoint.finish_round().unwrap();
+        assert!(checkpoint.load_round().unwrap().is_none());
+        assert_eq!(checkpoint.load_keys().unwrap().len(), 1);
+
+        // keys outlive rounds, and the next round compacts them to the latest of each client
+        checkpoint.record_key(3, b"key of 3").unwrap();
+        checkpoint.record_key(7, b"new key of 7").unwrap();
+        checkpoint.begin_round([9u8; 32], 5).unwrap();
+        let journal = fs::read(checkpoint.path("keys")).unwrap();
+        assert_eq!(records(&journal).iter().map(|(tag, _)| *tag).collect::<Vec<_>>(), vec![RECORD_KEY; 2]);
+        let keys = checkpoint.load_keys().unwrap();
+        assert_eq!((&keys[&3][..], &keys[&7][..]), (&b"key of 3"[..], &b"new key of 7"[..]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    // test that records appended after a restart that dropped a torn record survive the next restart
+    fn test_restart_after_torn_write() {
+        let dir = checkpoint_dir("torn-write");
+        let checkpoint = ServerCheckpoint::new(&dir, 3).unwrap();
+        checkpoint.begin_round([9u8; 32], 4).unwrap();
+        let mut sum = CiphertextSum::default();
+        sum.add(8, &[5, 6]).unwrap();
+        checkpoint.record_submission(&entry(2), b"shares of 2", &sum).unwrap();
+
+        // a client journaled without its sum snapshot, then a record torn by the crash
+        let mut payload = 9u64.to_le_bytes().to_vec();
+        payload.extend_from_slice(&[9u8; 32]);
+        append_record(&checkpoint.path("round"), RECORD_SUBMISSION, &payload).unwrap();
+        let mut journal = OpenOptions::new().append(true).open(checkpoint.path("round")).unwrap();
+        journal.write_all(&record(RECORD_INPUTS_CLOSED, b"torn")[..6]).unwrap();
+        drop(journal);
+
+        // the first restart keeps client 2 only, and the server carries on from there
+        let restarted = ServerCheckpoint::new(&dir, 3).unwrap();
+        assert_eq!(restarted.load_round().unwrap().unwrap().accepted, vec![(entry(2), b"shares of 2".to_vec())]);
+        sum.add(8, &[1, 1]).unwrap();
+        restarted.record_submission(&entry(7), b"shares of 7", &sum).unwrap();
+        restarted.record_inputs_closed().unwrap();
+        restarted.record_committee_output(b"committee output").unwrap();
+
+        // the second restart sees every record appended since, paired with the right sum
+        let round = ServerCheckpoint::new(&dir, 3).unwrap().load_round().unwrap().unwrap();
+        assert_eq!(
+            round.accepted,
+            vec![(entry(2), b"shares of 2".to_vec()), (entry(7), b"shares of 7".to_vec())]
+        );
+        assert_eq!(round.ciphertext_sum.num_clients, 2);
+        assert_eq!(round.ciphertext_sum.sum, vec![6, 7]);
+        assert!(round.inputs_closed);
+        assert_eq!(round.committee_outputs, vec![b"committee output".to_vec()]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    // test that a committee member's aggregated share survives a restart
+    fn test_committee_checkpoint() {
+        let dir = checkpoint_dir("committee-checkpoint");
+        assert!(CommitteeCheckpoint::load(&dir, 0, 10001).unwrap().is_none());
+        let checkpoint = CommitteeCheckpoint {
+            round_id: 2,
+            client_set: ClientSet::new(vec![entry(5), entry(3)]),
+            output_share: vec![[1u8; FIELD_ELEMENT_BYTES], [2u8; FIELD_ELEMENT_BYTES]],
+        };
+        checkpoint.save(&dir, 0, 10001).unwrap();
+        assert_eq!(CommitteeCheckpoint::load(&dir, 0, 10001).unwrap(), Some(checkpoint));
+        assert!(CommitteeCheckpoint::load(&dir, 1, 10001).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,23 @@
-use std::io::Write;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+
+use ed25519_dalek::SigningKey;
 
 use crate::protocols::client::Client;
+use crate::protocols::opa::admission::{self, AdmissionToken, Credentials, IdentityKey};
+use crate::protocols::opa::client_set::{ClientId, submission_digest};
+use crate::protocols::opa::params::OPAPublicParams;
+use crate::protocols::opa::norm_proof::NormProof;
+use crate::protocols::opa::range_proof::{self, InputRangeProof, RangeProofError};
+use crate::protocols::opa::round::RoundId;
 use crate::protocols::opa::server::OPAState;
-use crate::crypto::{F256, FieldBytes, OUTER_MODULUS_BITS, SeedHomomorphicPRG, Shamir, field_to_bytes};
+use crate::protocols::opa::verification::{
+    AggregateProof, ClientCommitments, VerificationError, commit_masked_input, commit_seed,
+};
+use crate::crypto::{
+    F256, FieldBytes, KeyHomomorphicPRF, OUTER_MODULUS_BITS, SeedHomomorphicPRG, Shamir, field_to_bytes,
+};
 use crate::crypto::prg::{populate_random, default_prg};
 use crate::util::packing::pack_vector;
 use crate::communicator::Communicator;
@@ -17,6 +32,26 @@ pub struct OPAClient<T> {
     input: Option<Vec<T>>,
     server_state: Option<OPAState>,
     encrypted_output: Option<(Vec<u128>, Vec<Vec<FieldBytes>>)>,
+    /// Long-term masking key when the server uses key-homomorphic masks.
+    key: Option<KeyHomomorphicPRF>,
+    /// Whether the server accepted a submission carrying shares of `key`.
+    key_registered: bool,
+    /// Host the server runs on.
+    server_ip: IpAddr,
+    /// Key the client enrolls with and signs submissions under, when the server runs
+    /// admission control; generated on first use unless set.
+    identity: Option<SigningKey>,
+    /// Token admitting the client to a round.
+    admission: Option<(RoundId, AdmissionToken)>,
+    /// Id the client submits under while it has no identity; see `id`.
+    id: ClientId,
+    /// Key the client signs its messages with, if the server checks signatures.
+    signing_key: Option<SigningKey>,
+    /// Proof that the encrypted input is in the declared range, if the server asks for one,
+    /// and within the norm bound, if the server declares one.
+    range_proof: Option<Result<(InputRangeProof, Option<NormProof>), RangeProofError>>,
+    /// Commitments to the masked input and its seed, under verifiable aggregation.
+    commitments: Option<ClientCommitments>,
     #[cfg(test)]
     last_seed: Option<Vec<u128>>,
 }
@@ -26,6 +61,79 @@ impl<T: Copy + Into<u32> + num_traits::FromPrimitive> OPAClient<T> {
         self.input.as_ref()
     }
 
+    pub fn server_state(&self) -> Option<&OPAState> {
+        self.server_state.as_ref()
+    }
+
+    /// Submit to a server on another host instead of this one.
+    pub fn set_server_ip(&mut self, ip: IpAddr) {
+        self.server_ip = ip;
+    }
+
+    /// Enroll with and sign submissions under `key`, e.g. one on the server's allowlist.
+    pub fn set_identity(&mut self, key: SigningKey) {
+        self.identity = Some(key);
+        self.admission = None;
+    }
+
+    /// Sign every message to the server with `key`, which the server's address book lists
+    /// for the client's port.
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.signing_key = Some(key);
+    }
+
+    /// The public key identifying the client under admission control, once it has one.
+    pub fn identity(&self) -> Option<IdentityKey> {
+        self.identity.as_ref().map(|key| key.verifying_key().to_bytes())
+    }
+
+    /// The id the client submits under and seals its shares to: derived from its identity
+    /// once it has one, so the server cannot list it under another, and otherwise drawn at
+    /// random, so clients on different hosts do not collide.
+    pub fn id(&self) -> ClientId {
+        self.identity().map_or(self.id, |identity| admission::client_id(&identity))
+    }
+
+    /// Enroll the client's identity and get a token admitting it to the current round,
+    /// if the server runs admission control. Fails with `PermissionDenied` if the server
+    /// does not allow the identity.
+    pub fn admit(&mut self, port: u16) -> io::Result<()> {
+        self.admit_with(&self.communicator(port))
+    }
+
+    /// Get admitted through `communicator`; see `admit`.
+    pub fn admit_with(&mut self, communicator: &Communicator) -> io::Result<()> {
+        let state = self.server_state.as_ref().unwrap();
+        if !state.admission_control {
+            return Ok(());
+        }
+        let (session_id, round_id, server_port) = (state.session_id, state.round_id, state.port);
+        let key = self.identity.get_or_insert_with(|| SigningKey::generate(&mut default_prg()));
+        // enrolling again is harmless, and covers a server that restarted since
+        communicator.request_from_server(server_port, &admission::enroll_request(key, session_id))?;
+        let request = admission::admit_request(key, session_id, round_id);
+        let token = communicator.request_from_server(server_port, &request)?;
+        let token = token
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed admission token"))?;
+        self.admission = Some((round_id, token));
+        Ok(())
+    }
+
+    /// Join the session described by public parameters the server published, in either
+    /// encoding, instead of a server state handed over in process.
+    pub fn load_public_params(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let params = OPAPublicParams::decode(bytes)?;
+        self.set_server_state(params.into());
+        Ok(())
+    }
+
+    pub fn load_public_params_file(&mut self, path: &Path) -> io::Result<()> {
+        let params = OPAPublicParams::from_file(path)?;
+        self.set_server_state(params.into());
+        Ok(())
+    }
+
     /// Helper for tests and debugging: decode an encoded (already unmasked)
     /// aggregate using the client's view of the server state.
     pub fn decode_output(&self, output: Vec<u128>) -> Vec<u32> {
@@ -35,16 +143,211 @@ impl<T: Copy + Into<u32> + num_traits::FromPrimitive> OPAClient<T> {
             .decode_output(output)
     }
 
+    /// The commitments to the masked input and its seed the client submits under verifiable
+    /// aggregation.
+    pub fn commitments(&self) -> Option<ClientCommitments> {
+        self.commitments
+    }
+
+    /// Check an aggregate the server published against its proof: the client's own input
+    /// and seed must be among the committed ones, and the commitments must account for
+    /// `output`.
+    pub fn verify_aggregate(&self, proof: &AggregateProof, output: &[u32]) -> Result<(), VerificationError> {
+        let state = self.server_state.as_ref().expect("OPA client server state must be set.");
+        let commitments = self.commitments.expect("Must call encrypt_input before verify_aggregate");
+        let included = proof
+            .clients
+            .iter()
+            .any(|client| client.commitment == commitments.input && client.seed_commitment == commitments.seed);
+        if proof.round_id != state.round_id || !included {
+            return Err(VerificationError::NotIncluded);
+        }
+        proof.verify(&state.public_params(), output)
+    }
+
+    fn payload_len(&self) -> usize {
+        pack_vector(self.input.as_ref().unwrap()).len()
+    }
+
+    /// Send the encrypted input and wait for the server to accept it. Submissions after
+    /// the input deadline fail with `PermissionDenied`, repeated ones with `AlreadyExists`.
+    /// The seed shares are only sent if the committee is the one its selection draws, and
+    /// nothing is sent if the public seed is not the one its derivation yields. Under
+    /// admission control the client must have been admitted to the round.
+    pub fn submit(&self, port: u16) -> io::Result<()> {
+        self.submit_with(&self.communicator(port))
+    }
+
+    /// Submit through `communicator`, e.g. the connection a party also serves on the
+    /// committee with, instead of one of the client's own.
+    pub fn submit_with(&self, communicator: &Communicator) -> io::Result<()> {
+        let state = self.server_state.as_ref().unwrap();
+        state.verify_committee()?;
+
+        let data = self.submission(self.id())?;
+        communicator.send_to_server_with_reply(state.port, &data)
+    }
+
+    /// The submission of client `client_id`, with each share sealed to its committee
+    /// member if the committee published keys.
+    fn submission(&self, client_id: ClientId) -> io::Result<Vec<u8>> {
+        let state = self.server_state.as_ref().unwrap();
+        // nothing masked under a public matrix the server may have picked leaves the client
+        state.verify_public_seed()?;
+        let (masked_input, shares) = self.encrypted_output.as_ref()
+            .expect("Must call encrypt_input before send_input");
+
+        // serialize: [round_id u64][client_id u64][payload_len u32][masked_input...]
+        // [commitments?][range proof?][norm proof?][num_shares u32][share...]
+        let mut data = Vec::new();
+        data.write_all(&state.round_id.to_le_bytes()).unwrap();
+        data.write_all(&client_id.to_le_bytes()).unwrap();
+        let payload_len = self.payload_len();
+        data.write_all(&(payload_len as u32).to_le_bytes()).unwrap();
+        for &x in masked_input {
+            data.write_all(&x.to_le_bytes()).unwrap();
+        }
+        if state.verifiable_aggregation {
+            let commitments = self.commitments.expect("the input was encrypted without commitments");
+            data.write_all(&commitments.to_bytes()).unwrap();
+        }
+        if state.range_proof_bits.is_some() {
+            match &self.range_proof {
+                Some(Ok((proof, norm_proof))) => {
+                    data.write_all(&proof.to_bytes()).unwrap();
+                    if let Some(norm_proof) = norm_proof {
+                        data.write_all(&norm_proof.to_bytes()).unwrap();
+                    }
+                }
+                Some(Err(e)) => return Err(e.clone().into()),
+                None => return Err(io::Error::other("the input was encrypted without a range proof")),
+            }
+        }
+        // record the number of shares (committee members)
+        data.write_all(&(shares.len() as u32).to_le_bytes()).unwrap();
+        for (committee_index, share) in shares.iter().enumerate() {
+            // each share: length + 32-byte field elements, sealed so only its member reads them
+            data.write_all(&(share.len() as u32).to_le_bytes()).unwrap();
+            let share = share.concat();
+            if state.seals_shares() {
+                data.write_all(&state.seal_share(client_id, committee_index, &share)?).unwrap();
+            } else {
+                data.write_all(&share).unwrap();
+            }
+        }
+
+        // under admission control, sign the submission and present the round's token
+        if state.admission_control {
+            let (Some(key), Some((round_id, token))) = (&self.identity, self.admission) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "client is not admitted; call admit first",
+                ));
+            };
+            if round_id != state.round_id {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "client is not admitted to this round",
+                ));
+            }
+            let credentials = Credentials::sign(key, state.session_id, token, &submission_digest(&[&data]));
+            data.splice(0..0, credentials.to_bytes());
+        }
+        Ok(data)
+    }
+
+    /// Get admitted and submit through `communicator`, sharing the key again if the server
+    /// lost it, and report the server's verdict.
+    pub fn send_input_with(&mut self, communicator: &Communicator) {
+        let port = communicator.port();
+        if let Err(e) = self.admit_with(communicator) {
+            eprintln!("Server did not admit the client on port {}: {}", port, e);
+            return;
+        }
+        let mut result = self.submit_with(communicator);
+        // the server lost (or never saw) our key: share it and try once more
+        if let (Err(e), Some(key)) = (&result, &self.key)
+            && e.kind() == io::ErrorKind::NotFound
+        {
+            println!("Server has no key registered for client {}; resending with key shares", self.id());
+            let shares = self.split_key(key.get_key());
+            self.encrypted_output.as_mut().unwrap().1 = shares;
+            result = self.submit_with(communicator);
+        }
+        if result.is_ok() && self.key.is_some() {
+            self.key_registered = true;
+        }
+        match result {
+            Ok(()) => println!("Server accepted input from port {}", port),
+            Err(e) => eprintln!("Server rejected input from port {}: {}", port, e),
+        }
+    }
+
+    /// A communicator on `port`, in the server's session and round.
+    fn communicator(&self, port: u16) -> Communicator {
+        let state = self.server_state.as_ref().unwrap();
+        let mut communicator = Communicator::new(port);
+        communicator.set_session_id(state.session_id);
+        communicator.set_round(state.round_id);
+        communicator.set_server_ip(self.server_ip);
+        if let Some(ref key) = self.signing_key {
+            communicator.set_signing_key(key.clone());
+        }
+        communicator
+    }
+
     pub fn setup(&self) {
         // setup the client
     }
 
+    /// Split the long-term `key` into one additive share mod 2^128 per committee member,
+    /// organized like `share_secrets`. Each member evaluates the PRF under the sum of its
+    /// shares, so the server only ever adds up the round's mask and never learns a key sum.
+    fn split_key(&self, key: &[u128]) -> Vec<Vec<FieldBytes>> {
+        let num_shares = self.server_state.as_ref().unwrap().committee_size as usize;
+        let mut rng = default_prg();
+        let mut first = key.to_vec();
+        let mut rest = Vec::with_capacity(num_shares.saturating_sub(1));
+        for _ in 1..num_shares {
+            let mut share = vec![0u128; key.len()];
+            populate_random(&mut share, &mut rng);
+            for (acc, &s) in first.iter_mut().zip(&share) {
+                *acc = acc.wrapping_sub(s);
+            }
+            rest.push(share);
+        }
+        std::iter::once(first)
+            .chain(rest)
+            .map(|share| share.into_iter().map(|s| field_to_bytes(F256::from(s))).collect())
+            .collect()
+    }
+
+    /// Secret share `secrets` to the committee using Shamir secret sharing over F256,
+    /// organized as shares[party_index][secret_index] = y (stored as 32-byte field element).
+    fn share_secrets(&self, secrets: &[F256]) -> Vec<Vec<FieldBytes>> {
+        let state = self.server_state.as_ref().unwrap();
+        let num_shares = state.committee_size as usize;
+        let threshold = state.reconstruction_threshold as usize;
+        let shamir = Shamir::<F256>::new(num_shares, threshold);
+
+        let mut shares: Vec<Vec<FieldBytes>> = vec![Vec::with_capacity(secrets.len()); num_shares];
+        for &secret in secrets {
+            let secret_shares = shamir.share(secret, &mut default_prg())
+                .expect("Shamir share failed");
+            for i in 0..num_shares {
+                let (_x, y) = secret_shares[i];
+                shares[i].push(field_to_bytes(y));
+            }
+        }
+        shares
+    }
+
     fn encode_input(&self) -> Vec<u128>
     where
         T: Copy + Into<u32> + num_traits::FromPrimitive,
     {
         let input = self.input.as_ref().expect("OPA client input must be set.");
-        let packed = pack_vector(&input);
+        let packed = pack_vector(input);
         
         // compute 2^kappa and (2^kappa * n)
         let kappa: u32 = self.server_state.as_ref().unwrap().security_parameter as u32;
@@ -77,10 +380,22 @@ impl<T: Copy + Into<u32> + num_traits::FromPrimitive> Client<T> for OPAClient<T>
     type ServerState = OPAState;
 
     fn new() -> Self {
+        let mut id = [0 as ClientId];
+        populate_random(&mut id, &mut default_prg());
+
         Self {
             input: None,
             server_state: None,
             encrypted_output: None,
+            key: None,
+            key_registered: false,
+            server_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            identity: None,
+            admission: None,
+            id: id[0],
+            signing_key: None,
+            range_proof: None,
+            commitments: None,
             #[cfg(test)]
             last_seed: None,
         }
@@ -105,81 +420,90 @@ impl<T: Copy + Into<u32> + num_traits::FromPrimitive> Client<T> for OPAClient<T>
         assert!(self.input.is_some(), "OPA client input is not set.");
         assert!(self.server_state.is_some(), "OPA client server state is not set.");
 
-        // instantiate the SHPRG by expanding the seed into the public parameter
-        let shprg = SeedHomomorphicPRG::new_from_public_seed(
-            self.server_state.as_ref().unwrap().succinct_seed);
-        
         let mut encoded_input = self.encode_input();
         let payload_len = encoded_input.len();
         assert!(payload_len <= OUTPUT_LEN, "encoded input exceeds SHPRG output length");
         encoded_input.resize(OUTPUT_LEN, 0);
 
-        let mask = shprg.expand(OUTPUT_LEN);
+        let state = self.server_state.as_ref().unwrap();
+        let (mask, seed) = if state.key_homomorphic_masks {
+            // mask under the long-term key; the committee already holds its shares once registered
+            let key = self.key.get_or_insert_with(KeyHomomorphicPRF::new);
+            let mask = key.evaluate(state.succinct_seed, state.round_id, OUTPUT_LEN);
+            (mask, (!self.key_registered).then(|| key.get_key().clone()))
+        } else {
+            // instantiate the SHPRG by expanding the seed into the public parameter
+            let shprg = SeedHomomorphicPRG::new_from_public_seed(state.round_public_seed());
+            (shprg.expand(OUTPUT_LEN), Some(shprg.get_seed().clone()))
+        };
 
         let masked_input: Vec<u128> = encoded_input
             .iter()
             .zip(mask.iter())
             .map(|(&x, &m)| x.wrapping_add(m))
             .collect();
-        
-        // secret share the SHPRG seed using Shamir secret sharing over F256
-        let state = self.server_state.as_ref().unwrap();
-        let num_shares = state.committee_size as usize;
-        let threshold = state.reconstruction_threshold as usize;
-        let shamir = Shamir::<F256>::new(num_shares, threshold);
-        let seed = shprg.get_seed();
 
-        // organize as shares[party_index][seed_index] = y (stored as 32-byte field element)
-        let mut shares: Vec<Vec<FieldBytes>> = vec![Vec::with_capacity(seed.len()); num_shares];
-        for &secret in seed.iter() {
-            let secret_shares = shamir.share(F256::from(secret), &mut default_prg())
-                .expect("Shamir share failed");
-            for i in 0..num_shares {
-                let (_x, y) = secret_shares[i];
-                shares[i].push(field_to_bytes(y));
+        // prove the coordinates in range, and within the norm bound over the same commitments;
+        // their blinding factors are shared after the seed, so the committee's sums let the
+        // server check the aggregate against the proofs. The proofs cover the words the server
+        // sums, which are the coordinates only if packing leaves one per word, and are bound
+        // to the masked input they are sent with
+        let range_proof = state.range_proof_bits.map(|bits| {
+            if std::mem::size_of::<T>() != std::mem::size_of::<u32>() {
+                return Err(RangeProofError::PackedInput);
             }
-        }
+            let packed = pack_vector(self.input.as_ref().unwrap());
+            let (session_id, round_id) = (state.session_id, state.round_id);
+            let (proof, blindings) =
+                InputRangeProof::prove(&packed, bits, session_id, round_id, &masked_input[..payload_len])?;
+            let norm_proof = state
+                .norm_bound
+                .map(|bound| NormProof::prove(&packed, &blindings, bound, state.session_id, state.round_id))
+                .transpose()?;
+            Ok((proof, norm_proof, blindings))
+        });
+        let blindings = match range_proof {
+            Some(Ok((_, _, ref blindings))) => blindings.as_slice(),
+            _ => &[],
+        };
+
+        // secret share the seed, or split the key, unless the committee already holds its shares
+        let shares = match seed.as_deref() {
+            None => Vec::new(),
+            Some(key) if state.key_homomorphic_masks => self.split_key(key),
+            Some(seed) => {
+                let secrets: Vec<F256> = seed
+                    .iter()
+                    .map(|&s| F256::from(s))
+                    .chain(blindings.iter().map(range_proof::blinding_to_field))
+                    .collect();
+                self.share_secrets(&secrets)
+            }
+        };
+        self.range_proof = range_proof.map(|result| result.map(|(proof, norm_proof, _)| (proof, norm_proof)));
+        // verifiable aggregation rules out registered keys, so the seed is fresh
+        self.commitments = match seed.as_deref() {
+            Some(seed) if state.verifiable_aggregation => Some(ClientCommitments {
+                input: commit_masked_input(&masked_input[..payload_len]),
+                seed: commit_seed(seed),
+            }),
+            _ => None,
+        };
 
         // store the seed for tests only
         #[cfg(test)]
         {
-            self.last_seed = Some(seed.clone());
+            self.last_seed = seed;
         }
 
         // save to state
-        self.encrypted_output = Some((masked_input.clone(), shares.clone()));
+        self.encrypted_output = Some((masked_input, shares));
     }
 
     // send the encrypted input to the server
     fn send_input(&mut self, port: u16) {
-        // create a communicator on the passed port
-        let communicator = Communicator::new(port);
-
-        // establish a connection to the server through the communicator
-        let server_port = self.server_state.as_ref().unwrap().port;
-        let (masked_input, shares) = self.encrypted_output.as_ref()
-            .expect("Must call encrypt_input before send_input");
-
-        // serialize: [payload_len u32][masked_input...][num_shares u32][share...]
-        let mut data = Vec::new();
-        let payload_len = pack_vector(self.input.as_ref().unwrap()).len() as u32;
-        data.write_all(&payload_len.to_le_bytes()).unwrap();
-        for &x in masked_input {
-            data.write_all(&x.to_le_bytes()).unwrap();
-        }
-        // record the number of shares (committee members)
-        data.write_all(&(shares.len() as u32).to_le_bytes()).unwrap();
-        for share in shares {
-            // each share: length + 32-byte field elements
-            data.write_all(&(share.len() as u32).to_le_bytes()).unwrap();
-            for x in share {
-                data.write_all(x).unwrap();
-            }
-        }
-
-        if let Err(e) = communicator.send_to_server(server_port, &data) {
-            eprintln!("Failed to send to server: {}", e);
-        }
+        let communicator = self.communicator(port);
+        self.send_input_with(&communicator);
     }
 }
 
@@ -188,8 +512,26 @@ impl<T: Copy + Into<u32> + num_traits::FromPrimitive> Client<T> for OPAClient<T>
 mod tests {
     use super::*;
     use crate::protocols::server::Server;
-    use crate::protocols::opa::{OPAServer, OPASetupParameters};
-    use crate::crypto::{F256, Shamir, field_from_bytes, field_low_u128};
+    use crate::protocols::opa::{CommittedClient, NormBound, OPAServer, OPASetupParameters, RoundError};
+    use crate::crypto::{
+        DecryptionKey, F256, FIELD_ELEMENT_BYTES, SEALED_OVERHEAD, Shamir, field_from_bytes, field_low_u128,
+    };
+
+    // shares[party_index][seed_index] = y; reconstruct each seed component j from all
+    // parties' shares at index j
+    fn reconstruct(state: &OPAState, shares: &[Vec<FieldBytes>]) -> Vec<u128> {
+        let shamir = Shamir::<F256>::new(state.committee_size as usize, state.reconstruction_threshold as usize);
+        (0..shares[0].len())
+            .map(|j| {
+                let pairs: Vec<(F256, F256)> = shares
+                    .iter()
+                    .enumerate()
+                    .map(|(i, party_shares)| (F256::from((i as u64) + 1), field_from_bytes(&party_shares[j])))
+                    .collect();
+                field_low_u128(shamir.reconstruct(&pairs).unwrap())
+            })
+            .collect()
+    }
 
     #[test]
     // test that decode(encode(x)) = x
@@ -197,7 +539,7 @@ mod tests {
         let input : Vec<u32> = vec![1, 2, 3, 4, 5, 6, 7, 8];
         let expected = input.clone();
 
-        let opa_server = OPAServer::new(OPASetupParameters::new(40, 16, 16, 31));
+        let opa_server = OPAServer::new(OPASetupParameters::new(40, 15, 16, 31));
         let state = opa_server.get_state();
 
         let mut opa_client = OPAClient::<u32>::new();
@@ -214,7 +556,7 @@ mod tests {
     fn test_encryption() {
         let input : Vec<u32> = vec![1, 2, 3, 4, 5, 6, 7, 8];
     
-        let opa_server = OPAServer::new(OPASetupParameters::new(40, 16, 16, 31));
+        let opa_server = OPAServer::new(OPASetupParameters::new(40, 15, 16, 31));
         let state = opa_server.get_state();
         
         let mut opa_client = OPAClient::<u32>::new();
@@ -226,29 +568,375 @@ mod tests {
         let (_masked_input, shares) = opa_client.encrypted_output.as_ref().unwrap();
         
         // reconstruct the seed from the secret shares
-        let shamir = Shamir::<F256>::new(
-            state.committee_size as usize,
-            state.reconstruction_threshold as usize
-        );
-        // shares is organized as shares[party_index][seed_index] = y.
-        // reconstruct each seed component j from all parties' shares at index j.
-        let num_parties = shares.len();
-        let seed_len = shares[0].len();
-        let mut reconstructed_seed: Vec<u128> = Vec::with_capacity(seed_len);
-        for j in 0..seed_len {
-            let mut pairs: Vec<(F256, F256)> = Vec::with_capacity(num_parties);
-            for i in 0..num_parties {
-                let x = F256::from((i as u64) + 1);
-                let y = field_from_bytes(&shares[i][j]);
-                pairs.push((x, y));
-            }
-            let s = shamir.reconstruct(&pairs).unwrap();
-            reconstructed_seed.push(field_low_u128(s));
-        }
+        let reconstructed_seed = reconstruct(state, shares);
 
         // get the last seed from the client, generated during encryption
         let last_seed = opa_client.last_seed.unwrap();
         
         assert_eq!(last_seed, reconstructed_seed);
     }
-}
\ No newline at end of file
+
+    #[test]
+    // test that a registered key is split among the committee once and still masks each round differently
+    fn test_registered_key() {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_key_homomorphic_masks(true);
+        let opa_server = OPAServer::new(parameters);
+        let state = opa_server.get_state();
+
+        let mut opa_client = OPAClient::<u32>::new();
+        opa_client.set_input(vec![1, 2, 3, 4]);
+        opa_client.set_server_state(state.clone());
+        opa_client.encrypt_input();
+        let (first, shares) = opa_client.encrypted_output.clone().unwrap();
+        assert_eq!(shares.len(), state.committee_size as usize);
+        assert_eq!(opa_client.last_seed.as_ref(), Some(opa_client.key.as_ref().unwrap().get_key()));
+
+        // the key is split additively, and the members' evaluations add up to the client's mask
+        let key = opa_client.key.clone().unwrap();
+        let member_keys: Vec<Vec<u128>> = shares
+            .iter()
+            .map(|share| share.iter().map(|x| field_low_u128(field_from_bytes::<F256>(x))).collect())
+            .collect();
+        let key_sum = member_keys.iter().fold(vec![0u128; key.get_key().len()], |acc, share| {
+            acc.iter().zip(share).map(|(&a, &s)| a.wrapping_add(s)).collect()
+        });
+        assert_eq!(&key_sum, key.get_key());
+        let mut mask_sum = vec![0u128; 64];
+        for member_key in member_keys {
+            let mask = KeyHomomorphicPRF::from_key(member_key).evaluate(state.succinct_seed, state.round_id, 64);
+            for (acc, m) in mask_sum.iter_mut().zip(mask) {
+                *acc = acc.wrapping_add(m);
+            }
+        }
+        let modulus = 1u128 << crate::crypto::seed_homomorphic_prg::DEFAULT_INNER_MODULUS_BITS;
+        let mask = key.evaluate(state.succinct_seed, state.round_id, 64);
+        assert!(mask
+            .iter()
+            .zip(&mask_sum)
+            .all(|(&m, &s)| m.wrapping_sub(s) % modulus < state.committee_size as u128));
+
+        opa_client.key_registered = true;
+        opa_client.set_server_state(OPAState { round_id: 1, ..state.clone() });
+        opa_client.encrypt_input();
+        let (second, shares) = opa_client.encrypted_output.clone().unwrap();
+        assert!(shares.is_empty());
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    // test that the server cannot reconstruct a client's seed from the sealed shares it forwards
+    fn test_sealed_shares() {
+        let mut opa_server = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3));
+        let members: Vec<DecryptionKey> = (0..3).map(|_| DecryptionKey::generate()).collect();
+        let ports: Vec<u16> = (0..3).map(|i| opa_server.get_state().committee_port(i).unwrap()).collect();
+        opa_server
+            .set_committee_keys(ports.iter().zip(&members).map(|(&port, key)| (port, key.public_key())))
+            .unwrap();
+        let state = opa_server.get_state().clone();
+
+        let mut opa_client = OPAClient::<u32>::new();
+        opa_client.set_input(vec![1, 2, 3, 4]);
+        opa_client.set_server_state(state.clone());
+        opa_client.encrypt_input();
+        let (_masked_input, shares) = opa_client.encrypted_output.clone().unwrap();
+        let seed = opa_client.last_seed.clone().unwrap();
+        let submission = opa_client.submission(7).unwrap();
+
+        // the server sees [num_shares u32] and then [share_len u32][sealed share] per member
+        let mut section = &submission[20 + OUTPUT_LEN * 16 + 4..];
+        let mut forwarded = Vec::new();
+        while !section.is_empty() {
+            let share_len = u32::from_le_bytes(section[..4].try_into().unwrap()) as usize;
+            let (sealed, rest) = section[4..].split_at(share_len * FIELD_ELEMENT_BYTES + SEALED_OVERHEAD);
+            forwarded.push(sealed);
+            section = rest;
+        }
+        assert_eq!(forwarded.len(), 3);
+
+        // no share appears in the clear, and reading the ciphertexts as shares gives another seed
+        for x in shares.iter().flatten() {
+            assert!(!submission.windows(FIELD_ELEMENT_BYTES).any(|window| window == x));
+        }
+        let guessed: Vec<Vec<FieldBytes>> = forwarded
+            .iter()
+            .map(|sealed| {
+                sealed
+                    .chunks_exact(FIELD_ELEMENT_BYTES)
+                    .take(seed.len())
+                    .map(|x| x.try_into().unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_ne!(reconstruct(&state, &guessed), seed);
+
+        // each member opens its own share, and only as this client's
+        for (i, sealed) in forwarded.iter().enumerate() {
+            assert_eq!(members[i].open(&state.share_context(7, ports[i]), sealed).unwrap(), shares[i].concat());
+            assert!(members[i].open(&state.share_context(8, ports[i]), sealed).is_err());
+            assert!(members[(i + 1) % 3].open(&state.share_context(7, ports[i]), sealed).is_err());
+        }
+    }
+
+    #[test]
+    // test that clients submit under an id of their own, whatever port they connect from
+    fn test_client_id() {
+        let state = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3)).get_state().clone();
+        let mut clients: Vec<OPAClient<u32>> = (0..2).map(|_| OPAClient::new()).collect();
+        assert_ne!(clients[0].id(), clients[1].id());
+        for opa_client in &mut clients {
+            opa_client.set_input(vec![1, 2]);
+            opa_client.set_server_state(state.clone());
+            opa_client.encrypt_input();
+        }
+        let submission = clients[0].submission(clients[0].id()).unwrap();
+        assert_eq!(submission[8..16], clients[0].id().to_le_bytes());
+
+        // an enrolled client is known by its identity, so the server cannot list it under another id
+        let key = SigningKey::generate(&mut default_prg());
+        clients[1].set_identity(key.clone());
+        assert_eq!(clients[1].id(), admission::client_id(&key.verifying_key().to_bytes()));
+    }
+
+    #[test]
+    // test that clients prove their input in range, share the blindings, and cannot submit otherwise
+    fn test_range_proofs() {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_range_proofs(8);
+        let state = OPAServer::new(parameters).get_state().clone();
+        let mut opa_client = OPAClient::<u32>::new();
+        opa_client.set_input(vec![1, 2, 255, 4]);
+        opa_client.set_server_state(state.clone());
+        opa_client.encrypt_input();
+
+        // the proof follows the masked input and verifies; one blinding share per coordinate follows the seed
+        let submission = opa_client.submission(7).unwrap();
+        let proof = InputRangeProof::read_from(&mut &submission[20 + OUTPUT_LEN * 16..], 4).unwrap();
+        let (masked_input, shares) = opa_client.encrypted_output.as_ref().unwrap();
+        assert!(proof.verify(8, 4, state.session_id, state.round_id, &masked_input[..4]).is_ok());
+        let shared = reconstruct(&state, shares);
+        let seed = opa_client.last_seed.as_ref().unwrap();
+        assert_eq!(shared.len(), seed.len() + 4);
+        assert_eq!(&shared[..seed.len()], &seed[..]);
+
+        opa_client.set_input(vec![1, 256, 3, 4]);
+        opa_client.encrypt_input();
+        let refused = opa_client.submission(7).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            *refused.into_inner().unwrap().downcast::<RangeProofError>().unwrap(),
+            RangeProofError::OutOfRange { index: 1 }
+        );
+
+        // narrower inputs share words, so a word's range would not bound each coordinate
+        let mut narrow_client = OPAClient::<u16>::new();
+        narrow_client.set_input(vec![1, 2, 3]);
+        narrow_client.set_server_state(state);
+        narrow_client.encrypt_input();
+        let refused = narrow_client.submission(7).unwrap_err();
+        let refused = refused.into_inner().unwrap().downcast::<RangeProofError>().unwrap();
+        assert_eq!(*refused, RangeProofError::PackedInput);
+    }
+
+    #[test]
+    // test that clients prove their input within the norm bound next to the range proof
+    fn test_norm_bound() {
+        let parameters =
+            OPASetupParameters::new(40, 1, 2, 3).with_range_proofs(8).with_norm_bound(NormBound::L2(13));
+        let state = OPAServer::new(parameters).get_state().clone();
+        let mut opa_client = OPAClient::<u32>::new();
+        opa_client.set_input(vec![3, 4, 12]);
+        opa_client.set_server_state(state.clone());
+        opa_client.encrypt_input();
+
+        let submission = opa_client.submission(7).unwrap();
+        let mut reader = &submission[20 + OUTPUT_LEN * 16..];
+        let range = InputRangeProof::read_from(&mut reader, 3).unwrap();
+        let norm = NormProof::read_from(&mut reader, 3, NormBound::L2(13)).unwrap();
+        let (masked_input, _) = opa_client.encrypted_output.as_ref().unwrap();
+        let committed = range.verify(8, 3, state.session_id, state.round_id, &masked_input[..3]).unwrap();
+        assert!(norm.verify(&committed, NormBound::L2(13), state.session_id, state.round_id).is_ok());
+
+        // (3, 4, 12, 1) is in range but its norm exceeds 13
+        opa_client.set_input(vec![3, 4, 12, 1]);
+        opa_client.encrypt_input();
+        let refused = opa_client.submission(7).unwrap_err();
+        assert_eq!(
+            *refused.into_inner().unwrap().downcast::<RangeProofError>().unwrap(),
+            RangeProofError::NormExceeded
+        );
+
+        // (3, 4) packed into one u16 word would be 0x40003, far above 13: narrow inputs cannot prove
+        let mut narrow_client = OPAClient::<u16>::new();
+        narrow_client.set_input(vec![3, 4]);
+        narrow_client.set_server_state(state);
+        narrow_client.encrypt_input();
+        let refused = narrow_client.submission(7).unwrap_err();
+        let refused = refused.into_inner().unwrap().downcast::<RangeProofError>().unwrap();
+        assert_eq!(*refused, RangeProofError::PackedInput);
+    }
+
+    #[test]
+    // test that an aggregate unmasked with the right seed passes the integrity check and a wrong one fails it
+    fn test_check_unmasked() {
+        let state = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3)).get_state().clone();
+        let clients: Vec<OPAClient<u32>> = [vec![1, 2, 3, 4], vec![u32::MAX, 6, 7, 8]]
+            .into_iter()
+            .map(|input| {
+                let mut opa_client = OPAClient::<u32>::new();
+                opa_client.set_input(input);
+                opa_client.set_server_state(state.clone());
+                opa_client.encrypt_input();
+                opa_client
+            })
+            .collect();
+        let mask = |seed: Vec<u128>| {
+            SeedHomomorphicPRG::new_from_both_seeds(state.round_public_seed(), seed).expand(OUTPUT_LEN)
+        };
+        let unmask = |clients: &[OPAClient<u32>], mask: &[u128]| -> Vec<u128> {
+            let mut sum: Vec<u128> = mask.iter().map(|m| m.wrapping_neg()).collect();
+            for opa_client in clients {
+                let (masked_input, _) = opa_client.encrypted_output.as_ref().unwrap();
+                for (acc, &x) in sum.iter_mut().zip(masked_input) {
+                    *acc = acc.wrapping_add(x);
+                }
+            }
+            sum
+        };
+        let mut seed_sum = vec![0u128; clients[0].last_seed.as_ref().unwrap().len()];
+        for opa_client in &clients {
+            for (acc, &x) in seed_sum.iter_mut().zip(opa_client.last_seed.as_ref().unwrap()) {
+                *acc = acc.wrapping_add(x);
+            }
+        }
+
+        // the first word wraps around to 0
+        let right = mask(seed_sum.clone());
+        let unmasked = unmask(&clients, &right);
+        assert!(state.check_unmasked(&unmasked, 4, 2).is_ok());
+        assert_eq!(state.decode_output_with_len(unmasked.clone(), 4), vec![0, 8, 10, 12]);
+
+        // a wrong seed, or the seeds of another client set, leave slots outside the encoding
+        seed_sum[0] = seed_sum[0].wrapping_add(1);
+        let wrong = mask(seed_sum);
+        assert!(matches!(
+            state.check_unmasked(&unmask(&clients, &wrong), 4, 2),
+            Err(RoundError::UnmaskingFailed { .. })
+        ));
+        assert!(matches!(
+            state.check_unmasked(&unmask(&clients[..1], &right), 4, 1),
+            Err(RoundError::UnmaskingFailed { .. })
+        ));
+        assert!(matches!(state.check_unmasked(&unmasked, 4, 1), Err(RoundError::UnmaskingFailed { .. })));
+
+        // words are only defined mod 2^32, so decoding a zero slot wraps instead of underflowing
+        assert_eq!(state.decode_output_with_len(vec![0, 1], 2), vec![u32::MAX, 0]);
+    }
+
+    #[test]
+    // test that clients accept an aggregate their commitments account for, and nothing else
+    fn test_verify_aggregate() {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_verifiable_aggregation(true);
+        let state = OPAServer::new(parameters).get_state().clone();
+        let clients: Vec<OPAClient<u32>> = [vec![1, 2, 3, 4], vec![5, 6, 7, 8]]
+            .into_iter()
+            .map(|input| {
+                let mut opa_client = OPAClient::<u32>::new();
+                opa_client.set_input(input);
+                opa_client.set_server_state(state.clone());
+                opa_client.encrypt_input();
+                opa_client
+            })
+            .collect();
+
+        // the submission carries the commitments right after the masked input
+        let commitments = clients[0].commitments().unwrap();
+        let (masked_input, _) = clients[0].encrypted_output.as_ref().unwrap();
+        assert_eq!(commitments.input, commit_masked_input(&masked_input[..4]));
+        assert_eq!(commitments.seed, commit_seed(clients[0].last_seed.as_ref().unwrap()));
+        let submission = clients[0].submission(7).unwrap();
+        assert_eq!(submission[20 + OUTPUT_LEN * 16..][..64], commitments.to_bytes());
+
+        // what the server publishes: the sums of the masked inputs and of the seeds
+        let payload_len = clients[0].payload_len();
+        let seed_len = clients[0].last_seed.as_ref().unwrap().len();
+        let mut proof = AggregateProof {
+            round_id: state.round_id,
+            clients: clients
+                .iter()
+                .enumerate()
+                .map(|(id, opa_client)| CommittedClient {
+                    id: id as u64,
+                    digest: [0u8; 32],
+                    commitment: opa_client.commitments().unwrap().input,
+                    seed_commitment: opa_client.commitments().unwrap().seed,
+                })
+                .collect(),
+            payload_len,
+            masked_sum: vec![0; payload_len],
+            carries: vec![0; payload_len],
+            seed_sum: vec![0; seed_len],
+            seed_carries: vec![0; seed_len],
+        };
+        let add = |sums: &mut Vec<u128>, carries: &mut Vec<u32>, values: &[u128]| {
+            for ((acc, carry), &x) in sums.iter_mut().zip(carries.iter_mut()).zip(values) {
+                let wrapped;
+                (*acc, wrapped) = acc.overflowing_add(x);
+                *carry += wrapped as u32;
+            }
+        };
+        for opa_client in &clients {
+            let (masked_input, _) = opa_client.encrypted_output.as_ref().unwrap();
+            add(&mut proof.masked_sum, &mut proof.carries, masked_input);
+            add(&mut proof.seed_sum, &mut proof.seed_carries, opa_client.last_seed.as_ref().unwrap());
+        }
+
+        let output = [6, 8, 10, 12];
+        for opa_client in &clients {
+            assert_eq!(opa_client.verify_aggregate(&proof, &output), Ok(()));
+        }
+        assert_eq!(clients[0].verify_aggregate(&proof, &[6, 8, 10, 13]), Err(VerificationError::OutputMismatch));
+
+        // a sum that is not what the commitments add up to, or an aggregate without the client
+        let mut tampered = proof.clone();
+        tampered.masked_sum[0] = tampered.masked_sum[0].wrapping_add(1 << 60);
+        assert_eq!(
+            clients[0].verify_aggregate(&tampered, &output),
+            Err(VerificationError::CommitmentsDoNotAddUp)
+        );
+        let mut without = proof.clone();
+        without.clients.remove(1);
+        assert_eq!(clients[1].verify_aggregate(&without, &output), Err(VerificationError::NotIncluded));
+        assert_eq!(
+            without.verify(&state.public_params(), &output),
+            Err(VerificationError::CommitmentsDoNotAddUp)
+        );
+
+        // a seed sum the seed commitments do not add up to, which could unmask to anything
+        let mut tampered = proof.clone();
+        tampered.seed_sum[0] = tampered.seed_sum[0].wrapping_add(1);
+        assert_eq!(
+            clients[0].verify_aggregate(&tampered, &output),
+            Err(VerificationError::SeedCommitmentsDoNotAddUp)
+        );
+        let mut unwrapped = proof.clone();
+        unwrapped.seed_carries[0] += 1;
+        assert_eq!(
+            clients[0].verify_aggregate(&unwrapped, &output),
+            Err(VerificationError::SeedCommitmentsDoNotAddUp)
+        );
+        // a seed commitment swapped to match leaves the client out, and the sum unmasked
+        // under the other seed outside the encoding
+        let mut forged_seed = clients[1].last_seed.clone().unwrap();
+        forged_seed[0] = forged_seed[0].wrapping_add(1);
+        let mut forged = proof.clone();
+        forged.clients[1].seed_commitment = commit_seed(&forged_seed);
+        (forged.seed_sum, forged.seed_carries) = (vec![0; seed_len], vec![0; seed_len]);
+        for seed in [clients[0].last_seed.as_ref().unwrap(), &forged_seed] {
+            add(&mut forged.seed_sum, &mut forged.seed_carries, seed);
+        }
+        assert_eq!(clients[1].verify_aggregate(&forged, &output), Err(VerificationError::NotIncluded));
+        assert!(matches!(
+            clients[0].verify_aggregate(&forged, &output),
+            Err(VerificationError::OutsideEncoding { .. })
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::{self, Cursor, Read};
+
+use sha2::{Digest, Sha256};
+
+/// Identifies a client: a hash of its identity under admission control, otherwise an id
+/// the client draws once and keeps across rounds.
+pub type ClientId = u64;
+/// SHA-256 digest binding a client set or a single submission.
+pub type Commitment = [u8; 32];
+
+const COMMITMENT_DOMAIN: &[u8] = b"jagguar/opa/client-set/v1";
+const ENTRY_BYTES: usize = 8 + 32;
+
+/// A client accepted into a round, together with the digest of its submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientEntry {
+    pub id: ClientId,
+    pub digest: Commitment,
+}
+
+/// The ordered set of clients whose inputs make up a round, fixed when input collection closes.
+/// Every committee member is sent the same set and echoes its commitment with its output,
+/// so shares summed over a different set are detected instead of silently unmasking garbage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientSet {
+    entries: Vec<ClientEntry>,
+}
+
+impl ClientSet {
+    /// Build a set ordered by client id; ids must be unique.
+    pub fn new(mut entries: Vec<ClientEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.id);
+        debug_assert!(entries.windows(2).all(|w| w[0].id != w[1].id), "duplicate client id");
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[ClientEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, id: ClientId) -> bool {
+        self.entries.binary_search_by_key(&id, |entry| entry.id).is_ok()
+    }
+
+    /// Hash of the ordered (id, digest) list, domain-separated and length-prefixed.
+    pub fn commitment(&self) -> Commitment {
+        let mut hasher = Sha256::new();
+        hasher.update(COMMITMENT_DOMAIN);
+        hasher.update((self.entries.len() as u32).to_le_bytes());
+        for entry in &self.entries {
+            hasher.update(entry.id.to_le_bytes());
+            hasher.update(entry.digest);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Serialize as `[num_clients u32][id u64 || digest 32 bytes]...`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(4 + self.entries.len() * ENTRY_BYTES);
+        data.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for entry in &self.entries {
+            data.extend_from_slice(&entry.id.to_le_bytes());
+            data.extend_from_slice(&entry.digest);
+        }
+        data
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut cursor = Cursor::new(bytes);
+        let mut buf = [0u8; 4];
+        cursor.read_exact(&mut buf)?;
+        let num_clients = u32::from_le_bytes(buf) as usize;
+        if bytes.len() != 4 + num_clients * ENTRY_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "client set length mismatch"));
+        }
+
+        let mut entries = Vec::with_capacity(num_clients);
+        for _ in 0..num_clients {
+            let mut id = [0u8; 8];
+            let mut digest = [0u8; 32];
+            cursor.read_exact(&mut id)?;
+            cursor.read_exact(&mut digest)?;
+            entries.push(ClientEntry { id: u64::from_le_bytes(id), digest });
+        }
+        if entries.windows(2).any(|w| w[0].id >= w[1].id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "client set is not strictly ordered"));
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// Digest of one client submission, as recorded in the client set.
+pub fn submission_digest(parts: &[&[u8]]) -> Commitment {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // test that the encoding round-trips and the commitment binds exactly the set of clients
+    fn test_client_set_commitment() {
+        let a = ClientEntry { id: 7, digest: submission_digest(&[b"a"]) };
+        let b = ClientEntry { id: 3, digest: submission_digest(&[b"b"]) };
+
+        let set = ClientSet::new(vec![a, b]);
+        assert_eq!(set.entries()[0].id, 3);
+        assert_eq!(set, ClientSet::from_bytes(&set.to_bytes()).unwrap());
+        assert_eq!(set.commitment(), ClientSet::new(vec![b, a]).commitment());
+
+        // dropping a client or changing its submission changes the commitment
+        assert_ne!(set.commitment(), ClientSet::new(vec![a]).commitment());
+        let altered = ClientEntry { id: 7, digest: submission_digest(&[b"c"]) };
+        assert_ne!(set.commitment(), ClientSet::new(vec![altered, b]).commitment());
+    }
+}
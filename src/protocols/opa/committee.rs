@@ -1,17 +1,76 @@
-use std::io::{Cursor, Read, Write};
+use std::io::{self, Cursor, Read, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 
 use crate::protocols::committee::Committee;
 use crate::protocols::opa::server::OPAState;
+use crate::protocols::opa::checkpoint::CommitteeCheckpoint;
+use crate::protocols::opa::params::OPAPublicParams;
+use crate::protocols::opa::client_set::ClientSet;
 use crate::communicator::Communicator;
+use ed25519_dalek::SigningKey;
+use crate::protocols::opa::client::OUTPUT_LEN;
 use crate::crypto::{
-    F256, FieldBytes, FIELD_ELEMENT_BYTES, field_from_bytes, field_to_bytes,
+    DecryptionKey, EncryptionKey, F256, FieldBytes, FIELD_ELEMENT_BYTES, KeyHomomorphicPRF, field_from_bytes,
+    field_low_u128, field_to_bytes,
 };
 
 pub struct OPACommittee {
     server_state: Option<OPAState>,
     communicator: Communicator,
+    client_set: Option<ClientSet>,
     input_shares: Option<Vec<Vec<FieldBytes>>>,
     output_share: Option<Vec<FieldBytes>>,
+    /// Key the member opens the shares clients sealed to it with; generated unless set.
+    decryption_key: DecryptionKey,
+    /// Where the aggregated share is saved, if the member checkpoints.
+    checkpoint_dir: Option<PathBuf>,
+}
+
+impl OPACommittee {
+    pub fn server_state(&self) -> Option<&OPAState> {
+        self.server_state.as_ref()
+    }
+
+    /// Reach a server on another host instead of this one.
+    pub fn set_server_ip(&mut self, ip: IpAddr) {
+        self.communicator.set_server_ip(ip);
+    }
+
+    /// Join the session described by public parameters the server published, in either
+    /// encoding, instead of a server state handed over in process.
+    pub fn load_public_params(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let params = OPAPublicParams::decode(bytes)?;
+        self.set_server_state(params.into());
+        Ok(())
+    }
+
+    pub fn load_public_params_file(&mut self, path: &Path) -> io::Result<()> {
+        let params = OPAPublicParams::from_file(path)?;
+        self.set_server_state(params.into());
+        Ok(())
+    }
+
+    /// Sign every message to the server with `key`, which the server's address book lists
+    /// for this member's port.
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.communicator.set_signing_key(key);
+    }
+
+    /// Open shares with `key`, whose public key the server publishes for this member's port.
+    pub fn set_decryption_key(&mut self, key: DecryptionKey) {
+        self.decryption_key = key;
+    }
+
+    /// The key clients seal this member's shares to, for the server to publish.
+    pub fn encryption_key(&self) -> EncryptionKey {
+        self.decryption_key.public_key()
+    }
+
+    /// The connection the member reaches the server through.
+    pub fn communicator(&self) -> &Communicator {
+        &self.communicator
+    }
 }
 
 impl Committee for OPACommittee {
@@ -23,12 +82,17 @@ impl Committee for OPACommittee {
         Self {
             server_state: None,
             communicator,
+            client_set: None,
             input_shares: None,
             output_share: None,
+            decryption_key: DecryptionKey::generate(),
+            checkpoint_dir: None,
         }
     }
 
     fn set_server_state(&mut self, state: Self::ServerState) {
+        self.communicator.set_session_id(state.session_id);
+        self.communicator.set_round(state.round_id);
         self.server_state = Some(state.clone());
     }
 
@@ -36,68 +100,171 @@ impl Committee for OPACommittee {
         let server_port = self.server_state.as_ref().unwrap().port;
         let inputs = self.communicator.receive_from_server(server_port)
             .expect("Failed to receive inputs from server");
+        if inputs.is_empty() {
+            // the server closes the stream without sending anything when the round is aborted
+            println!("Server aborted the round; nothing to aggregate");
+            return;
+        }
 
-        // Format from send_on_stream: [byte_len u32][byte_len bytes of field elements LE]...
+        // Format from send_on_stream: [byte_len u32][client set]
+        // followed by [byte_len u32][byte_len bytes of field elements LE]... in client-set order,
+        // each share sealed to this member if the committee published keys
         let mut cursor = Cursor::new(&inputs);
+        let mut len_bytes = [0u8; 4];
+        cursor.read_exact(&mut len_bytes)
+            .expect("Failed to read client set length");
+        let mut set_bytes = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+        cursor.read_exact(&mut set_bytes)
+            .expect("Failed to read client set");
+        let client_set = ClientSet::from_bytes(&set_bytes)
+            .expect("Malformed client set from server");
+
+        // take no part in a committee the server may have picked
+        let state = self.server_state.as_ref().unwrap();
+        if let Err(e) = state.verify_committee() {
+            eprintln!("Refusing the committee the server published: {}; not aggregating", e);
+            return;
+        }
         let mut shares = Vec::new();
         
         while cursor.position() < inputs.len() as u64 {
             let mut len_bytes = [0u8; 4];
             cursor.read_exact(&mut len_bytes)
                 .expect("Failed to read length prefix");
-            let byte_len = u32::from_le_bytes(len_bytes) as usize;
-            let share_len = byte_len / FIELD_ELEMENT_BYTES;
-            
-            let mut share = Vec::with_capacity(share_len);
-            for _ in 0..share_len {
-                let mut xbuf = [0u8; FIELD_ELEMENT_BYTES];
-                cursor.read_exact(&mut xbuf)
-                    .expect("Failed to read share element");
-                share.push(xbuf);
+            let mut bytes = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+            cursor.read_exact(&mut bytes)
+                .expect("Failed to read share");
+
+            if state.seals_shares() {
+                let client_id = client_set.entries()
+                    .get(shares.len())
+                    .expect("Server sent shares for a different client set")
+                    .id;
+                let context = state.share_context(client_id, self.communicator.port());
+                match self.decryption_key.open(&context, &bytes) {
+                    Ok(opened) => bytes = opened,
+                    Err(e) => {
+                        // without every share the output would not unmask the aggregate
+                        eprintln!("Share from client {} does not open: {}; not aggregating", client_id, e);
+                        return;
+                    }
+                }
             }
+            let share: Vec<FieldBytes> = bytes
+                .chunks_exact(FIELD_ELEMENT_BYTES)
+                .map(|x| x.try_into().unwrap())
+                .collect();
             shares.push(share);
         }
         
+        assert_eq!(shares.len(), client_set.len(), "Server sent shares for a different client set");
         println!("Parsed {} secret shares from server", shares.len());
+        self.client_set = Some(client_set);
         self.input_shares = Some(shares);
     }
 
     fn aggregate(&mut self) {
-        let shares = self.input_shares.as_ref()
-            .expect("Must call retrieve_inputs before aggregate");
-        
+        let Some(shares) = self.input_shares.as_ref() else {
+            return;
+        };
         if shares.is_empty() {
             return;
         }
         
         let share_len = shares[0].len();
-        
-        let mut output_share = vec![[0u8; FIELD_ELEMENT_BYTES]; share_len];
-        
-        for share in shares {
-            assert_eq!(share.len(), share_len, "All shares must have the same length");
-            for (i, value) in share.iter().enumerate() {
-                let sum = field_from_bytes::<F256>(&output_share[i])
-                    + field_from_bytes::<F256>(value);
-                output_share[i] = field_to_bytes(sum);
+        let state = self.server_state.as_ref().unwrap();
+
+        let output_share = if state.key_homomorphic_masks {
+            // registered keys are split additively mod 2^128: report the PRF at the round id
+            // under the sum of this member's key shares, never the sum itself
+            let mut key = vec![0u128; share_len];
+            for share in shares {
+                assert_eq!(share.len(), share_len, "All shares must have the same length");
+                for (acc, value) in key.iter_mut().zip(share) {
+                    *acc = acc.wrapping_add(field_low_u128(field_from_bytes::<F256>(value)));
+                }
             }
-        }
+            KeyHomomorphicPRF::from_key(key)
+                .evaluate(state.succinct_seed, state.round_id, OUTPUT_LEN)
+                .into_iter()
+                .map(|m| field_to_bytes(F256::from(m)))
+                .collect()
+        } else {
+            let mut output_share = vec![[0u8; FIELD_ELEMENT_BYTES]; share_len];
+            for share in shares {
+                assert_eq!(share.len(), share_len, "All shares must have the same length");
+                for (i, value) in share.iter().enumerate() {
+                    let sum = field_from_bytes::<F256>(&output_share[i])
+                        + field_from_bytes::<F256>(value);
+                    output_share[i] = field_to_bytes(sum);
+                }
+            }
+            output_share
+        };
 
-        self.output_share = Some(output_share.clone());
-        
         println!("Aggregated {} shares into output share of length {}", shares.len(), output_share.len());
+
+        // save the share before reporting it, so a restarted member can report it again
+        if let (Some(dir), Some(state), Some(client_set)) =
+            (&self.checkpoint_dir, &self.server_state, &self.client_set)
+        {
+            let checkpoint = CommitteeCheckpoint {
+                round_id: state.round_id,
+                client_set: client_set.clone(),
+                output_share: output_share.clone(),
+            };
+            if let Err(e) = checkpoint.save(dir, state.session_id, self.communicator.port()) {
+                eprintln!("Failed to checkpoint output share: {}", e);
+            }
+        }
+        self.output_share = Some(output_share);
+    }
+
+    fn set_checkpoint_dir(&mut self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        self.checkpoint_dir = Some(dir.to_path_buf());
+        Ok(())
+    }
+
+    fn resume(&mut self) -> bool {
+        let (Some(dir), Some(state)) = (&self.checkpoint_dir, &self.server_state) else {
+            return false;
+        };
+        let checkpoint = match CommitteeCheckpoint::load(dir, state.session_id, self.communicator.port()) {
+            Ok(Some(checkpoint)) if checkpoint.round_id == state.round_id => checkpoint,
+            Ok(_) => return false,
+            Err(e) => {
+                eprintln!("Ignoring unreadable committee checkpoint: {}", e);
+                return false;
+            }
+        };
+        println!("Resumed output share of round {} from checkpoint", checkpoint.round_id);
+        self.client_set = Some(checkpoint.client_set);
+        self.output_share = Some(checkpoint.output_share);
+        true
     }
 
     fn send_output(&mut self) {
+        if self.client_set.is_none() {
+            // nothing was retrieved, so there is nothing to report for an aborted round
+            return;
+        }
         let mut data = Vec::new();
         data.extend_from_slice(b"committee");
 
-        let server_port = self.server_state.as_ref().unwrap().port;
-        let local_port = self.communicator.port();
-        let committee_index: u16 = local_port
-            .checked_sub(server_port + 1)
+        let committee_index = self.server_state
+            .as_ref()
+            .unwrap()
+            .committee_index(self.communicator.port())
             .expect("Invalid committee port configuration") as u16;
         data.extend_from_slice(&committee_index.to_le_bytes());
+        data.extend_from_slice(&self.server_state.as_ref().unwrap().round_id.to_le_bytes());
+
+        // bind the output to the client set the shares were summed over
+        let client_set = self.client_set
+            .as_ref()
+            .expect("Must call retrieve_inputs before send_output");
+        data.extend_from_slice(&client_set.commitment());
         
         let share = self.output_share
             .as_ref()
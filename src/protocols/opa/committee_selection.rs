@@ -0,0 +1,243 @@
+use std::fmt;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::protocols::opa::client_set::{ClientId, Commitment};
+use crate::protocols::opa::params::hex_seed;
+use crate::protocols::opa::round::RoundId;
+
+/// Public randomness the committee is drawn with, e.g. a randomness beacon's output for
+/// an agreed time published after the population was fixed.
+pub type Beacon = [u8; 32];
+
+const SELECTION_DOMAIN: &[u8] = b"jagguar/opa/committee-selection/v1";
+const POPULATION_DOMAIN: &[u8] = b"jagguar/opa/committee-population/v1";
+
+/// How a session's committee is drawn from its registered clients. Everything that goes
+/// into the draw is public, so anyone holding the selection recomputes the same committee
+/// and a server cannot hand-pick members: each candidate is ranked by a hash of the
+/// randomness, the round and its id, and the lowest ranks serve.
+///
+/// The committee is drawn once, for `round_id`, and serves every round of the session
+/// like the port-offset committee it replaces; with key-homomorphic masks it has to, since
+/// clients register their key with it only once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitteeSelection {
+    pub round_id: RoundId,
+    /// External randomness; without it the draw is seeded by the population itself, which
+    /// whoever fixes the population can grind by adding or leaving out candidates.
+    #[serde(default, with = "hex_seed::option")]
+    pub beacon: Option<Beacon>,
+    /// Ids of the clients eligible to serve, sorted and without duplicates.
+    pub population: Vec<ClientId>,
+}
+
+impl CommitteeSelection {
+    pub fn new(mut population: Vec<ClientId>, beacon: Option<Beacon>, round_id: RoundId) -> Self {
+        population.sort_unstable();
+        population.dedup();
+        Self { round_id, beacon, population }
+    }
+
+    /// Hash of the sorted population, binding the candidates the committee came from.
+    pub fn population_commitment(&self) -> Commitment {
+        let mut hasher = Sha256::new();
+        hasher.update(POPULATION_DOMAIN);
+        hasher.update((self.population.len() as u64).to_le_bytes());
+        for id in &self.population {
+            hasher.update(id.to_le_bytes());
+        }
+        hasher.finalize().into()
+    }
+
+    /// The randomness every candidate is ranked with.
+    fn seed(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(SELECTION_DOMAIN);
+        match self.beacon {
+            Some(beacon) => {
+                hasher.update([1]);
+                hasher.update(beacon);
+            }
+            None => {
+                hasher.update([0]);
+                hasher.update(self.population_commitment());
+            }
+        }
+        hasher.update(self.round_id.to_le_bytes());
+        hasher.finalize().into()
+    }
+
+    /// The `committee_size` members, in committee index order.
+    pub fn draw(&self, committee_size: u64) -> Result<Vec<ClientId>, SelectionError> {
+        if (self.population.len() as u64) < committee_size {
+            return Err(SelectionError::PopulationTooSmall { population: self.population.len(), committee_size });
+        }
+        let seed = self.seed();
+        let mut ranked: Vec<([u8; 32], ClientId)> = self
+            .population
+            .iter()
+            .map(|&id| {
+                let rank: [u8; 32] = Sha256::new()
+                    .chain_update(seed)
+                    .chain_update(id.to_le_bytes())
+                    .finalize()
+                    .into();
+                (rank, id)
+            })
+            .collect();
+        ranked.sort_unstable();
+        Ok(ranked.into_iter().take(committee_size as usize).map(|(_, id)| id).collect())
+    }
+
+    /// Port offsets from the server's `port` at which the drawn members connect, since
+    /// candidates are drawn by the port they serve on.
+    pub fn port_offsets(&self, committee_size: u64, port: u16) -> Result<Vec<u16>, SelectionError> {
+        self.draw(committee_size)?
+            .into_iter()
+            .map(|id| match u16::try_from(id) {
+                Ok(member_port) if member_port != port => Ok(member_port.wrapping_sub(port)),
+                _ => Err(SelectionError::InvalidCommitteeCandidate { id }),
+            })
+            .collect()
+    }
+}
+
+/// Check the committee port offsets a server published: the ones `selection` draws if the
+/// committee is drawn, otherwise `committee_size` consecutive offsets clear of the server's
+/// port, which is the only committee a server sets up without a draw.
+pub fn check_committee(
+    selection: Option<&CommitteeSelection>,
+    committee_size: u64,
+    port: u16,
+    port_offsets: &[u16],
+) -> Result<(), SelectionError> {
+    let consecutive = |offsets: &[u16]| {
+        offsets.len() as u64 == committee_size
+            && offsets.first().is_none_or(|&first| first != 0)
+            && offsets.windows(2).all(|w| w[0].checked_add(1) == Some(w[1]))
+    };
+    match selection {
+        Some(selection) if selection.port_offsets(committee_size, port)? != port_offsets => {
+            Err(SelectionError::CommitteeNotDrawn)
+        }
+        None if !consecutive(port_offsets) => Err(SelectionError::CommitteeNotConsecutive),
+        _ => Ok(()),
+    }
+}
+
+/// Why a committee cannot be drawn, or is not the one its selection draws.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionError {
+    /// Fewer clients are eligible than the committee has seats.
+    PopulationTooSmall { population: usize, committee_size: u64 },
+    /// A drawn client cannot serve: its id is no port other than the server's.
+    InvalidCommitteeCandidate { id: ClientId },
+    /// The committee differs from the one its public selection draws.
+    CommitteeNotDrawn,
+    /// A committee without a selection does not sit at consecutive port offsets.
+    CommitteeNotConsecutive,
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectionError::PopulationTooSmall { population, committee_size } => {
+                write!(f, "cannot draw a committee of {} from {} eligible clients", committee_size, population)
+            }
+            SelectionError::InvalidCommitteeCandidate { id } => {
+                write!(f, "client {} was drawn for the committee but cannot connect as a member", id)
+            }
+            SelectionError::CommitteeNotDrawn => {
+                write!(f, "the committee does not match the one drawn from its public selection")
+            }
+            SelectionError::CommitteeNotConsecutive => {
+                write!(f, "a committee that is not drawn must sit at consecutive port offsets from the server")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectionError {}
+
+impl From<SelectionError> for io::Error {
+    fn from(e: SelectionError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::opa::{OPAPublicParams, OPAServer, OPASetupParameters};
+    use crate::protocols::opa::server::OPAState;
+    use crate::protocols::server::Server;
+
+    #[test]
+    // test that anyone recomputes the same committee and every input of the draw changes it
+    fn test_committee_draw() {
+        let population: Vec<ClientId> = (20_000..20_100).collect();
+        let selection = CommitteeSelection::new(population.clone(), None, 0);
+        let committee = selection.draw(9).unwrap();
+        assert_eq!(committee.len(), 9);
+        assert!(committee.iter().all(|id| population.contains(id)));
+
+        // the order candidates are listed in does not matter
+        let shuffled: Vec<ClientId> = population.iter().rev().copied().chain([20_000]).collect();
+        assert_eq!(CommitteeSelection::new(shuffled, None, 0).draw(9).unwrap(), committee);
+
+        assert_ne!(CommitteeSelection::new(population.clone(), None, 1).draw(9).unwrap(), committee);
+        assert_ne!(CommitteeSelection::new(population.clone(), Some([7; 32]), 0).draw(9).unwrap(), committee);
+        assert_ne!(CommitteeSelection::new(population[1..].to_vec(), None, 0).draw(9).unwrap(), committee);
+
+        // members connect from their own ports, which may lie below the server's
+        let offsets = selection.port_offsets(9, 20_050).unwrap();
+        for (id, offset) in committee.iter().zip(offsets) {
+            assert_eq!(20_050u16.wrapping_add(offset) as ClientId, *id);
+        }
+
+        assert!(matches!(selection.draw(101), Err(SelectionError::PopulationTooSmall { .. })));
+        let unreachable = CommitteeSelection::new(vec![1 << 20, 20_001], None, 0);
+        assert!(matches!(
+            unreachable.port_offsets(2, 20_000),
+            Err(SelectionError::InvalidCommitteeCandidate { .. })
+        ));
+    }
+
+    #[test]
+    // test that the server publishes its draw and a committee it picked itself is refused
+    fn test_server_committee_selection() {
+        let population: Vec<ClientId> = (18_651..18_661).collect();
+        let mut server = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3));
+        server.select_committee(&population, Some([3; 32])).unwrap();
+        let params = server.get_state().public_params();
+        server.get_state().verify_committee().unwrap();
+        assert_eq!(OPAPublicParams::from_json(&params.to_json()).unwrap(), params);
+        assert_eq!(OPAPublicParams::from_bytes(&params.to_bytes()).unwrap(), params);
+
+        // swap a drawn member for another client
+        let drawn = params.committee_selection.as_ref().unwrap().draw(3).unwrap();
+        let outsider = population.iter().find(|id| !drawn.contains(id)).unwrap();
+        let mut picked = params.clone();
+        picked.committee_port_offsets[0] = *outsider as u16;
+        assert!(OPAPublicParams::from_json(&picked.to_json()).is_err());
+        let state: OPAState = picked.into();
+        assert_eq!(state.verify_committee(), Err(SelectionError::CommitteeNotDrawn));
+
+        let mut server = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3));
+        let error = server.select_committee(&population[..2], None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        // without a draw the committee must be the consecutive one the server sets up
+        let params = server.get_state().public_params();
+        server.get_state().verify_committee().unwrap();
+        let mut picked = params.clone();
+        picked.committee_port_offsets[2] = 7;
+        assert!(OPAPublicParams::from_json(&picked.to_json()).is_err());
+        let state: OPAState = picked.into();
+        assert_eq!(state.verify_committee(), Err(SelectionError::CommitteeNotConsecutive));
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, mpsc};
+
+use crate::communicator::{BufferBudget, Communicator, SessionId, SessionRegistry};
+use crate::protocols::server::Server;
+use crate::protocols::opa::server::{OPAServer, OPASetupParameters, OPAState};
+
+/// Hosts many independent OPA sessions on one listening port. Each session has its own
+/// parameters, committee, client set, rounds, and output channel; connections are routed
+/// to a session by the session id they open with.
+pub struct OPAHost {
+    port: u16,
+    /// The listener's own session, which hosted sessions may not use.
+    listener_session: SessionId,
+    sessions: SessionRegistry,
+    budget: BufferBudget,
+    /// Public state of every hosted session, to hand to its clients and committee.
+    states: Mutex<HashMap<SessionId, OPAState>>,
+    /// First committee port offset not yet given to a session; committees get disjoint ports.
+    next_committee_port_offset: Mutex<u16>,
+    /// Directory every session checkpoints its rounds to, if any.
+    checkpoint_dir: Option<PathBuf>,
+}
+
+impl OPAHost {
+    /// Host sessions on the listener of `communicator`. Sessions can be opened and closed
+    /// before or while it runs its `listen_loop`.
+    pub fn new(communicator: &Communicator) -> Self {
+        Self {
+            port: communicator.port(),
+            listener_session: communicator.session_id(),
+            sessions: communicator.sessions(),
+            budget: communicator.buffer_budget(),
+            states: Mutex::new(HashMap::new()),
+            next_committee_port_offset: Mutex::new(1),
+            checkpoint_dir: None,
+        }
+    }
+
+    /// Checkpoint the rounds of sessions opened from now on under `dir`. A session reopened
+    /// with the same id after a restart resumes its unfinished round.
+    pub fn set_checkpoint_dir(&mut self, dir: &Path) {
+        self.checkpoint_dir = Some(dir.to_path_buf());
+    }
+
+    /// Open session `session_id` with its own parameters and start its first round. Decoded
+    /// outputs are sent on `output`, if given. Returns the session's public state.
+    pub fn open_session(
+        &self,
+        session_id: SessionId,
+        parameters: OPASetupParameters,
+        output: Option<mpsc::Sender<Vec<u32>>>,
+    ) -> io::Result<OPAState> {
+        if session_id == self.listener_session {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("session {} is reserved for the listener", session_id),
+            ));
+        }
+        parameters.validate()?;
+        let mut next_offset = self.next_committee_port_offset.lock().unwrap();
+        let first_offset = *next_offset;
+        let end_offset = u16::try_from(parameters.committee_size())
+            .ok()
+            .and_then(|size| first_offset.checked_add(size))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no committee ports left"))?;
+
+        let mut server = OPAServer::new(
+            parameters
+                .with_session_id(session_id)
+                .with_committee_port_offsets_from(first_offset),
+        );
+        if let Some(sender) = output {
+            server.set_output_channel(sender);
+        }
+        if let Some(ref dir) = self.checkpoint_dir {
+            server.set_checkpoint_dir(dir)?;
+        }
+        let session = server.session(self.port, self.budget.clone())?;
+        self.sessions.register(session_id, session)?;
+        *next_offset = end_offset;
+        drop(next_offset);
+
+        server.start();
+        let state = server.get_state().clone();
+        self.states.lock().unwrap().insert(session_id, state.clone());
+        println!("Opened session {} on port {}", session_id, self.port);
+        Ok(state)
+    }
+
+    /// Stop serving `session_id`; its clients and committee are refused from now on.
+    /// Returns whether the session existed.
+    pub fn close_session(&self, session_id: SessionId) -> bool {
+        self.states.lock().unwrap().remove(&session_id);
+        self.sessions.remove(session_id)
+    }
+
+    /// Public state of a hosted session.
+    pub fn state(&self, session_id: SessionId) -> Option<OPAState> {
+        self.states.lock().unwrap().get(&session_id).cloned()
+    }
+
+    pub fn session_ids(&self) -> Vec<SessionId> {
+        let mut ids: Vec<SessionId> = self.states.lock().unwrap().keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::opa::RoundPhase;
+
+    #[test]
+    // test that sessions get their own state and disjoint committees, and ids are unique
+    fn test_open_sessions() {
+        let communicator = Communicator::new(18611);
+        let host = OPAHost::new(&communicator);
+
+        let first = host.open_session(1, OPASetupParameters::new(40, 1, 2, 3), None).unwrap();
+        let second = host.open_session(2, OPASetupParameters::new(40, 2, 3, 5), None).unwrap();
+        assert_eq!((first.session_id, second.session_id), (1, 2));
+        assert_eq!(first.committee_port_offsets, vec![1, 2, 3]);
+        assert_eq!(second.committee_port_offsets, vec![4, 5, 6, 7, 8]);
+        assert_eq!(second.committee_index(18611 + 4), Some(0));
+        assert_eq!(first.committee_index(18611 + 4), None);
+        assert_ne!(first.succinct_seed, second.succinct_seed);
+
+        // closing one session's inputs leaves the other collecting
+        assert!(first.close_inputs().is_err());
+        assert_eq!(first.round.phase(), RoundPhase::Aborted);
+        assert_eq!(second.round.phase(), RoundPhase::CollectingInputs);
+
+        let duplicate = host.open_session(2, OPASetupParameters::new(40, 1, 2, 3), None).unwrap_err();
+        assert_eq!(duplicate.kind(), io::ErrorKind::AlreadyExists);
+        let reserved = host.open_session(0, OPASetupParameters::new(40, 1, 2, 3), None).unwrap_err();
+        assert_eq!(reserved.kind(), io::ErrorKind::InvalidInput);
+
+        assert_eq!(host.session_ids(), vec![1, 2]);
+        assert!(host.close_session(1));
+        assert!(host.state(1).is_none());
+        assert_eq!(host.session_ids(), vec![2]);
+    }
+}
@@ -1,10 +1,35 @@
 pub mod client;
 pub mod server;
 pub mod committee;
+pub mod client_set;
+pub mod round;
+pub mod host;
+pub mod checkpoint;
+pub mod params;
+pub mod planner;
+pub mod committee_selection;
+pub mod party;
+pub mod seed_derivation;
+pub mod admission;
+pub mod range_proof;
+pub mod norm_proof;
+pub mod verification;
 
 pub use client::OPAClient;
 pub use server::{OPAServer, OPASetupParameters};
+pub use client_set::{ClientId, ClientSet, Commitment};
 pub use committee::OPACommittee;
+pub use round::{Round, RoundError, RoundId, RoundPhase};
+pub use host::OPAHost;
+pub use params::{CommitteeKey, OPAPublicParams, PUBLIC_PARAMS_VERSION};
+pub use planner::{DeploymentRequirements, ParameterError, ParameterPlan};
+pub use committee_selection::{Beacon, CommitteeSelection, SelectionError};
+pub use party::OPAParty;
+pub use seed_derivation::{SeedDerivation, SeedDerivationError};
+pub use admission::{AdmissionToken, ClientRegistry, Credentials, IdentityKey};
+pub use range_proof::{InputRangeProof, RangeProofError};
+pub use norm_proof::{NormBound, NormProof};
+pub use verification::{AggregateProof, ClientCommitments, CommittedClient, InputCommitment, VerificationError};
 
 
 // instantiate the OPA protocol
@@ -12,7 +37,7 @@ use crate::protocols::Protocol;
 use num_traits::FromPrimitive;
 use core::marker::PhantomData;
 
-// marker tying together the OPA server, client, and committee under a single Protocol
+// marker tying together the OPA server, client, committee, and party under a single Protocol
 pub struct OPA<T>(pub PhantomData<T>);
 
 impl<T> Protocol for OPA<T>
@@ -23,4 +48,5 @@ where
 	type Server = OPAServer;
 	type Client = OPAClient<T>;
 	type Committee = OPACommittee;
+	type Party = OPAParty<T>;
 }
\ No newline at end of file
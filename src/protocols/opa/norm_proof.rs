@@ -0,0 +1,312 @@
+use std::io::{self, Read};
+
+use bulletproofs::{PedersenGens, RangeProof};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+use crate::communicator::SessionId;
+use crate::protocols::opa::range_proof::{self, RangeProofError};
+use crate::protocols::opa::round::RoundId;
+
+const TRANSCRIPT_LABEL: &[u8] = b"jagguar/opa/norm-proof/v1";
+
+/// Width of the range proof on `bound^2 - sum_j x_j^2`; coordinates proven below 2^32 keep
+/// the sum of squares far below the group order, so the slack cannot wrap around.
+const L2_SLACK_BITS: u32 = 64;
+
+/// A bound on the norm of a client's 32-bit input, proven on top of its range proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormBound {
+    /// The Euclidean norm is at most the bound: `sum_j x_j^2 <= bound^2`.
+    L2(u32),
+    /// No coordinate exceeds the bound.
+    #[serde(rename = "linf")]
+    LInf(u32),
+}
+
+impl NormBound {
+    /// The tag and bound as published with the parameters; tag 0 means no bound.
+    pub fn to_bytes(&self) -> [u8; 5] {
+        let (tag, bound) = match self {
+            NormBound::L2(bound) => (1u8, bound),
+            NormBound::LInf(bound) => (2u8, bound),
+        };
+        let mut bytes = [tag; 5];
+        bytes[1..].copy_from_slice(&bound.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; 5]) -> io::Result<Option<Self>> {
+        let bound = u32::from_le_bytes(bytes[1..].try_into().unwrap());
+        match bytes[0] {
+            0 => Ok(None),
+            1 => Ok(Some(NormBound::L2(bound))),
+            2 => Ok(Some(NormBound::LInf(bound))),
+            tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown norm bound {}", tag))),
+        }
+    }
+}
+
+/// A client's proof, in the style of ACORN, that its input vector stays within a
+/// `NormBound`. It refers to the Pedersen commitments `V_j = x_j * B + gamma_j * B'` of the
+/// client's `InputRangeProof`, so it reveals nothing about the input and is tied to the
+/// masked input by the same aggregate check.
+///
+/// Under L2 the client commits to every square as `S_j = x_j * V_j + rho_j * B'`, proves
+/// each `S_j` was formed from the `x_j` inside `V_j`, and proves `bound^2 - sum_j x_j^2` in
+/// range against `bound^2 * B - sum_j S_j`. Under L-infinity it proves every
+/// `bound - x_j` in range against `bound * B - V_j`.
+#[derive(Debug, Clone)]
+pub enum NormProof {
+    L2 {
+        squares: Vec<CompressedRistretto>,
+        /// Fiat-Shamir challenge of the square proofs, and their responses
+        /// `(a_j + e * x_j, b_j + e * gamma_j, c_j + e * rho_j)`.
+        challenge: Scalar,
+        responses: Vec<[Scalar; 3]>,
+        slack: RangeProof,
+    },
+    LInf {
+        slack: RangeProof,
+    },
+}
+
+impl NormProof {
+    /// Prove that `values`, committed under `blindings` by the client's range proof, stay
+    /// within `bound`.
+    pub fn prove(
+        values: &[u32],
+        blindings: &[Scalar],
+        bound: NormBound,
+        session_id: SessionId,
+        round_id: RoundId,
+    ) -> Result<Self, RangeProofError> {
+        if values.len() != blindings.len() || values.is_empty() {
+            return Err(RangeProofError::WrongLength);
+        }
+        let pedersen = PedersenGens::default();
+        let mut transcript = transcript(bound, session_id, round_id);
+        match bound {
+            NormBound::L2(bound) => {
+                let norm = values.iter().map(|&x| (x as u128).pow(2)).sum::<u128>();
+                let slack = (bound as u128)
+                    .pow(2)
+                    .checked_sub(norm)
+                    .ok_or(RangeProofError::NormExceeded)?;
+
+                // commit to the squares and to the nonces of their proofs
+                let mut squares = Vec::with_capacity(values.len());
+                let mut secrets = Vec::with_capacity(values.len());
+                for (&x, &gamma) in values.iter().zip(blindings) {
+                    let x = Scalar::from(x);
+                    let commitment = pedersen.commit(x, gamma);
+                    let rho = range_proof::random_scalar();
+                    let square = x * commitment + rho * pedersen.B_blinding;
+                    let nonces = [
+                        range_proof::random_scalar(),
+                        range_proof::random_scalar(),
+                        range_proof::random_scalar(),
+                    ];
+                    let first = pedersen.commit(nonces[0], nonces[1]);
+                    let second = nonces[0] * commitment + nonces[2] * pedersen.B_blinding;
+                    append_square(&mut transcript, &commitment, &square.compress(), &first, &second);
+                    squares.push(square.compress());
+                    secrets.push((x, gamma, rho, nonces));
+                }
+                let challenge = challenge(&mut transcript);
+                let responses = secrets
+                    .iter()
+                    .map(|&(x, gamma, rho, [a, b, c])| {
+                        [a + challenge * x, b + challenge * gamma, c + challenge * rho]
+                    })
+                    .collect();
+
+                // sum_j S_j commits to sum_j x_j^2 under sum_j (x_j * gamma_j + rho_j)
+                let blinding = secrets.iter().map(|&(x, gamma, rho, _)| x * gamma + rho).sum::<Scalar>();
+                let (slack, _) =
+                    range_proof::prove_padded(&[slack as u64], &[-blinding], L2_SLACK_BITS, &mut transcript)?;
+                Ok(NormProof::L2 { squares, challenge, responses, slack })
+            }
+            NormBound::LInf(bound) => {
+                let slack = values
+                    .iter()
+                    .map(|&x| bound.checked_sub(x).map(u64::from).ok_or(RangeProofError::NormExceeded))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let blindings: Vec<Scalar> = blindings.iter().map(|gamma| -gamma).collect();
+                let (slack, _) = range_proof::prove_padded(&slack, &blindings, 32, &mut transcript)?;
+                Ok(NormProof::LInf { slack })
+            }
+        }
+    }
+
+    /// Check the proof against the commitments of the client's verified range proof.
+    pub fn verify(
+        &self,
+        committed: &[RistrettoPoint],
+        bound: NormBound,
+        session_id: SessionId,
+        round_id: RoundId,
+    ) -> Result<(), RangeProofError> {
+        let pedersen = PedersenGens::default();
+        let mut transcript = transcript(bound, session_id, round_id);
+        match (self, bound) {
+            (NormProof::L2 { squares, challenge, responses, slack }, NormBound::L2(bound)) => {
+                if squares.len() != committed.len() || responses.len() != committed.len() {
+                    return Err(RangeProofError::WrongLength);
+                }
+                // recompute the nonce commitments from the responses, and the challenge from those
+                let mut sum = RistrettoPoint::default();
+                for ((commitment, square), [z1, z2, z3]) in committed.iter().zip(squares).zip(responses) {
+                    let point = square.decompress().ok_or(RangeProofError::InvalidProof)?;
+                    let first = RistrettoPoint::vartime_multiscalar_mul(
+                        [*z1, *z2, -challenge],
+                        [pedersen.B, pedersen.B_blinding, *commitment],
+                    );
+                    let second = RistrettoPoint::vartime_multiscalar_mul(
+                        [*z1, *z3, -challenge],
+                        [*commitment, pedersen.B_blinding, point],
+                    );
+                    append_square(&mut transcript, commitment, square, &first, &second);
+                    sum += point;
+                }
+                if self::challenge(&mut transcript) != *challenge {
+                    return Err(RangeProofError::InvalidProof);
+                }
+                let slack_commitment = Scalar::from((bound as u128).pow(2)) * pedersen.B - sum;
+                range_proof::verify_padded(slack, &[slack_commitment.compress()], L2_SLACK_BITS, &mut transcript)
+            }
+            (NormProof::LInf { slack }, NormBound::LInf(bound)) => {
+                let bound = Scalar::from(bound) * pedersen.B;
+                let slack_commitments: Vec<CompressedRistretto> = committed
+                    .iter()
+                    .map(|commitment| (bound - commitment).compress())
+                    .collect();
+                range_proof::verify_padded(slack, &slack_commitments, 32, &mut transcript)
+            }
+            _ => Err(RangeProofError::InvalidProof),
+        }
+    }
+
+    /// Under L2 `[square x len][challenge][response x 3 x len][proof_len u32][proof]`,
+    /// under L-infinity only the proof; the length comes from the submission header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let slack = match self {
+            NormProof::L2 { squares, challenge, responses, slack } => {
+                for square in squares {
+                    data.extend_from_slice(square.as_bytes());
+                }
+                data.extend_from_slice(challenge.as_bytes());
+                for response in responses.iter().flatten() {
+                    data.extend_from_slice(response.as_bytes());
+                }
+                slack
+            }
+            NormProof::LInf { slack } => slack,
+        };
+        range_proof::write_proof(&mut data, slack);
+        data
+    }
+
+    /// Read a proof of `bound` over `len` coordinates.
+    pub fn read_from(reader: &mut dyn Read, len: usize, bound: NormBound) -> io::Result<Self> {
+        match bound {
+            NormBound::L2(_) => {
+                let squares = range_proof::read_points(reader, len)?;
+                let challenge = read_scalar(reader)?;
+                let mut responses = Vec::with_capacity(len);
+                for _ in 0..len {
+                    responses.push([read_scalar(reader)?, read_scalar(reader)?, read_scalar(reader)?]);
+                }
+                let slack = range_proof::read_proof(reader)?;
+                Ok(NormProof::L2 { squares, challenge, responses, slack })
+            }
+            NormBound::LInf(_) => Ok(NormProof::LInf { slack: range_proof::read_proof(reader)? }),
+        }
+    }
+}
+
+fn append_square(
+    transcript: &mut Transcript,
+    commitment: &RistrettoPoint,
+    square: &CompressedRistretto,
+    first: &RistrettoPoint,
+    second: &RistrettoPoint,
+) {
+    transcript.append_message(b"commitment", commitment.compress().as_bytes());
+    transcript.append_message(b"square", square.as_bytes());
+    transcript.append_message(b"nonce", first.compress().as_bytes());
+    transcript.append_message(b"nonce", second.compress().as_bytes());
+}
+
+fn challenge(transcript: &mut Transcript) -> Scalar {
+    let mut bytes = [0u8; 64];
+    transcript.challenge_bytes(b"challenge", &mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+fn read_scalar(reader: &mut dyn Read) -> io::Result<Scalar> {
+    let mut bytes = [0u8; 32];
+    reader.read_exact(&mut bytes)?;
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or_else(|| RangeProofError::InvalidProof.into())
+}
+
+fn transcript(bound: NormBound, session_id: SessionId, round_id: RoundId) -> Transcript {
+    let mut transcript = Transcript::new(TRANSCRIPT_LABEL);
+    transcript.append_u64(b"session", session_id);
+    transcript.append_u64(b"round", round_id);
+    transcript.append_message(b"bound", &bound.to_bytes());
+    transcript
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::opa::InputRangeProof;
+
+    #[test]
+    // test that norm proofs verify for inputs within the bound only, against their own commitments
+    fn test_norm_proof() {
+        let values = [3, 4, 0, 12];
+        let (range, blindings) = InputRangeProof::prove(&values, 8, 1, 2, &[]).unwrap();
+        let committed = range.verify(8, 4, 1, 2, &[]).unwrap();
+
+        // the norm of (3, 4, 0, 12) is exactly 13
+        for bound in [NormBound::L2(13), NormBound::L2(100), NormBound::LInf(12)] {
+            let proof = NormProof::prove(&values, &blindings, bound, 1, 2).unwrap();
+            let parsed = NormProof::read_from(&mut &proof.to_bytes()[..], 4, bound).unwrap();
+            assert!(parsed.verify(&committed, bound, 1, 2).is_ok());
+            assert!(proof.verify(&committed, bound, 1, 3).is_err());
+        }
+        assert_eq!(
+            NormProof::prove(&values, &blindings, NormBound::L2(12), 1, 2).unwrap_err(),
+            RangeProofError::NormExceeded
+        );
+        assert_eq!(
+            NormProof::prove(&values, &blindings, NormBound::LInf(11), 1, 2).unwrap_err(),
+            RangeProofError::NormExceeded
+        );
+
+        // a proof of a bound does not pass for a tighter one, nor for other commitments
+        let proof = NormProof::prove(&values, &blindings, NormBound::L2(13), 1, 2).unwrap();
+        assert!(proof.verify(&committed, NormBound::L2(12), 1, 2).is_err());
+        assert!(proof.verify(&committed, NormBound::LInf(13), 1, 2).is_err());
+        let (other, _) = InputRangeProof::prove(&[3, 4, 0, 200], 8, 1, 2, &[]).unwrap();
+        assert!(proof.verify(&other.verify(8, 4, 1, 2, &[]).unwrap(), NormBound::L2(13), 1, 2).is_err());
+
+        // a prover lying about its values gets caught
+        let lie = NormProof::prove(&[3, 4, 0, 1], &blindings, NormBound::L2(13), 1, 2).unwrap();
+        assert!(lie.verify(&committed, NormBound::L2(13), 1, 2).is_err());
+        let lie = NormProof::prove(&[3, 4, 0, 1], &blindings, NormBound::LInf(11), 1, 2).unwrap();
+        assert!(lie.verify(&committed, NormBound::LInf(11), 1, 2).is_err());
+
+        assert_eq!(NormBound::from_bytes(NormBound::LInf(7).to_bytes()).unwrap(), Some(NormBound::LInf(7)));
+        assert_eq!(NormBound::from_bytes([0; 5]).unwrap(), None);
+        assert!(NormBound::from_bytes([3, 0, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,587 @@
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::communicator::SessionId;
+use crate::crypto::EncryptionKey;
+use crate::protocols::opa::committee_selection::{CommitteeSelection, check_committee};
+use crate::protocols::opa::seed_derivation::{SeedDerivation, SeedDerivationError};
+use crate::protocols::opa::planner::ParameterError;
+use crate::protocols::opa::norm_proof::NormBound;
+use crate::protocols::opa::range_proof::RANGE_BITS;
+use crate::protocols::opa::round::{Round, RoundId};
+use crate::protocols::opa::server::{ClientInputs, OPAState};
+
+/// Version written by `to_bytes` and `to_json`; older or newer encodings are refused.
+pub const PUBLIC_PARAMS_VERSION: u16 = 8;
+/// Leading bytes of the binary encoding, which tell it apart from JSON.
+const PUBLIC_PARAMS_MAGIC: &[u8; 4] = b"JGPP";
+const FINGERPRINT_DOMAIN: &[u8] = b"jagguar/opa/public-params/v1";
+/// `input_deadline_ms` value standing for "no deadline" in the binary encoding.
+const NO_DEADLINE: u64 = u64::MAX;
+
+pub type Fingerprint = [u8; 32];
+
+/// The public part of an `OPAState`: everything a client or committee member needs to take
+/// part in a session, without the server's runtime buffers and channels. A server publishes
+/// it out of band, in binary or JSON, and participants compare fingerprints to be sure they
+/// joined the same setup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OPAPublicParams {
+    pub session_id: SessionId,
+    pub round_id: RoundId,
+    #[serde(with = "hex_seed")]
+    pub succinct_seed: [u8; 32],
+    pub reuse_public_seed: bool,
+    #[serde(default)]
+    pub seed_derivation: Option<SeedDerivation>,
+    pub key_homomorphic_masks: bool,
+    #[serde(default)]
+    pub admission_control: bool,
+    /// Whether clients send a commitment to their masked input along with it.
+    #[serde(default)]
+    pub verifiable_aggregation: bool,
+    /// Width of the range clients prove each coordinate in, if they must.
+    #[serde(default)]
+    pub range_proof_bits: Option<u32>,
+    /// Bound clients prove the norm of their input vector within, if they must.
+    #[serde(default)]
+    pub norm_bound: Option<NormBound>,
+    pub security_parameter: u64,
+    pub corruption_threshold: u64,
+    pub reconstruction_threshold: u64,
+    pub committee_size: u64,
+    pub committee_port_offsets: Vec<u16>,
+    pub port: u16,
+    pub input_deadline_ms: Option<u64>,
+    pub committee_timeout_ms: u64,
+    pub min_participants: u64,
+    #[serde(default)]
+    pub committee_selection: Option<CommitteeSelection>,
+    /// Keys clients seal each member's share to, ordered by port; empty if shares are sent
+    /// in the clear.
+    #[serde(default)]
+    pub committee_keys: Vec<CommitteeKey>,
+}
+
+/// The key a committee member opens its shares with, by the port it connects from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitteeKey {
+    pub port: u16,
+    #[serde(with = "hex_seed")]
+    pub key: EncryptionKey,
+}
+
+/// JSON document: the parameters with the encoding version alongside.
+#[derive(Serialize, Deserialize)]
+struct VersionedParams {
+    version: u16,
+    #[serde(flatten)]
+    params: OPAPublicParams,
+}
+
+impl OPAPublicParams {
+    /// Serialize as `JGPP`, the version (u16), then every field in declaration order,
+    /// little-endian, with the port offsets prefixed by their count (u16). A committee
+    /// selection follows a presence byte as its round, a beacon presence byte and beacon,
+    /// and the population prefixed by its count (u64). A seed derivation follows a presence
+    /// byte as a tag byte and a descriptor prefixed by its length (u32). The committee keys follow as
+    /// their count (u16) and each port and key. Flags are single bytes, and the norm bound
+    /// a tag byte and the bound (u32).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(128 + 2 * self.committee_port_offsets.len());
+        data.extend_from_slice(PUBLIC_PARAMS_MAGIC);
+        data.extend_from_slice(&PUBLIC_PARAMS_VERSION.to_le_bytes());
+        data.extend_from_slice(&self.session_id.to_le_bytes());
+        data.extend_from_slice(&self.round_id.to_le_bytes());
+        data.extend_from_slice(&self.succinct_seed);
+        data.push(self.reuse_public_seed as u8);
+        data.push(self.key_homomorphic_masks as u8);
+        data.push(self.admission_control as u8);
+        data.push(self.verifiable_aggregation as u8);
+        data.push(self.range_proof_bits.unwrap_or(0) as u8);
+        data.extend_from_slice(&self.norm_bound.map_or([0; 5], |bound| bound.to_bytes()));
+        data.extend_from_slice(&self.security_parameter.to_le_bytes());
+        data.extend_from_slice(&self.corruption_threshold.to_le_bytes());
+        data.extend_from_slice(&self.reconstruction_threshold.to_le_bytes());
+        data.extend_from_slice(&self.committee_size.to_le_bytes());
+        data.extend_from_slice(&(self.committee_port_offsets.len() as u16).to_le_bytes());
+        for offset in &self.committee_port_offsets {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(&self.port.to_le_bytes());
+        data.extend_from_slice(&self.input_deadline_ms.unwrap_or(NO_DEADLINE).to_le_bytes());
+        data.extend_from_slice(&self.committee_timeout_ms.to_le_bytes());
+        data.extend_from_slice(&self.min_participants.to_le_bytes());
+        data.push(self.committee_selection.is_some() as u8);
+        if let Some(ref selection) = self.committee_selection {
+            data.extend_from_slice(&selection.round_id.to_le_bytes());
+            data.push(selection.beacon.is_some() as u8);
+            data.extend_from_slice(&selection.beacon.unwrap_or_default());
+            data.extend_from_slice(&(selection.population.len() as u64).to_le_bytes());
+            for id in &selection.population {
+                data.extend_from_slice(&id.to_le_bytes());
+            }
+        }
+        data.push(self.seed_derivation.is_some() as u8);
+        match self.seed_derivation {
+            None => {}
+            Some(SeedDerivation::Descriptor(ref descriptor)) => {
+                data.push(0);
+                data.extend_from_slice(&(descriptor.len() as u32).to_le_bytes());
+                data.extend_from_slice(descriptor.as_bytes());
+            }
+        }
+        data.extend_from_slice(&(self.committee_keys.len() as u16).to_le_bytes());
+        for committee_key in &self.committee_keys {
+            data.extend_from_slice(&committee_key.port.to_le_bytes());
+            data.extend_from_slice(&committee_key.key);
+        }
+        data
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut cursor = Cursor::new(bytes);
+        let mut magic = [0u8; 4];
+        cursor.read_exact(&mut magic)?;
+        if &magic != PUBLIC_PARAMS_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an OPA public parameter file"));
+        }
+        check_version(u16::from_le_bytes(read_array(&mut cursor)?))?;
+
+        let session_id = read_u64(&mut cursor)?;
+        let round_id = read_u64(&mut cursor)?;
+        let succinct_seed = read_array(&mut cursor)?;
+        let [reuse_public_seed, key_homomorphic_masks, admission_control, verifiable_aggregation] =
+            read_array::<4>(&mut cursor)?.map(|flag| flag != 0);
+        let [range_proof_bits] = read_array::<1>(&mut cursor)?;
+        let range_proof_bits = (range_proof_bits != 0).then_some(range_proof_bits as u32);
+        let norm_bound = NormBound::from_bytes(read_array(&mut cursor)?)?;
+        let security_parameter = read_u64(&mut cursor)?;
+        let corruption_threshold = read_u64(&mut cursor)?;
+        let reconstruction_threshold = read_u64(&mut cursor)?;
+        let committee_size = read_u64(&mut cursor)?;
+        let num_offsets = u16::from_le_bytes(read_array(&mut cursor)?);
+        let committee_port_offsets = (0..num_offsets)
+            .map(|_| read_array(&mut cursor).map(u16::from_le_bytes))
+            .collect::<io::Result<Vec<u16>>>()?;
+        let port = u16::from_le_bytes(read_array(&mut cursor)?);
+        let input_deadline_ms = Some(read_u64(&mut cursor)?).filter(|&ms| ms != NO_DEADLINE);
+        let committee_timeout_ms = read_u64(&mut cursor)?;
+        let min_participants = read_u64(&mut cursor)?;
+        let committee_selection = match read_array::<1>(&mut cursor)? {
+            [0] => None,
+            _ => {
+                let round_id = read_u64(&mut cursor)?;
+                let [has_beacon] = read_array::<1>(&mut cursor)?;
+                let beacon = Some(read_array(&mut cursor)?).filter(|_| has_beacon != 0);
+                let num_candidates = read_u64(&mut cursor)?;
+                if num_candidates > (bytes.len() as u64) / 8 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "committee population exceeds the data",
+                    ));
+                }
+                let population = (0..num_candidates)
+                    .map(|_| read_u64(&mut cursor))
+                    .collect::<io::Result<Vec<u64>>>()?;
+                Some(CommitteeSelection { round_id, beacon, population })
+            }
+        };
+        let seed_derivation = match read_array::<1>(&mut cursor)? {
+            [0] => None,
+            _ => {
+                let [tag] = read_array::<1>(&mut cursor)?;
+                let len = u32::from_le_bytes(read_array(&mut cursor)?) as usize;
+                let remaining = bytes.len() - cursor.position() as usize;
+                match tag {
+                    0 if len <= remaining => {
+                        let mut descriptor = vec![0u8; len];
+                        cursor.read_exact(&mut descriptor)?;
+                        let descriptor = String::from_utf8(descriptor)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                        Some(SeedDerivation::Descriptor(descriptor))
+                    }
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "malformed public seed derivation",
+                        ));
+                    }
+                }
+            }
+        };
+        let num_keys = u16::from_le_bytes(read_array(&mut cursor)?);
+        let committee_keys = (0..num_keys)
+            .map(|_| {
+                let port = u16::from_le_bytes(read_array(&mut cursor)?);
+                Ok(CommitteeKey { port, key: read_array(&mut cursor)? })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        if cursor.position() != bytes.len() as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "trailing bytes after public parameters"));
+        }
+
+        let params = Self {
+            session_id,
+            round_id,
+            succinct_seed,
+            reuse_public_seed,
+            seed_derivation,
+            key_homomorphic_masks,
+            admission_control,
+            verifiable_aggregation,
+            range_proof_bits,
+            norm_bound,
+            security_parameter,
+            corruption_threshold,
+            reconstruction_threshold,
+            committee_size,
+            committee_port_offsets,
+            port,
+            input_deadline_ms,
+            committee_timeout_ms,
+            min_participants,
+            committee_selection,
+            committee_keys,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    pub fn to_json(&self) -> String {
+        let document = VersionedParams { version: PUBLIC_PARAMS_VERSION, params: self.clone() };
+        serde_json::to_string_pretty(&document).expect("public parameters always serialize")
+    }
+
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        let document: VersionedParams = serde_json::from_str(json)?;
+        check_version(document.version)?;
+        document.params.validate()?;
+        Ok(document.params)
+    }
+
+    /// Decode either encoding, telling them apart by the binary magic.
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.starts_with(PUBLIC_PARAMS_MAGIC) {
+            return Self::from_bytes(bytes);
+        }
+        let json = std::str::from_utf8(bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Self::from_json(json)
+    }
+
+    /// Load parameters published in either encoding.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    /// Hash of the binary encoding. Equal fingerprints mean equal parameters, whichever
+    /// encoding they were loaded from.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut hasher = Sha256::new();
+        hasher.update(FINGERPRINT_DOMAIN);
+        hasher.update(self.to_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Refuse parameters no server could have set up.
+    fn validate(&self) -> io::Result<()> {
+        let invalid = |reason: &str| Err(io::Error::new(io::ErrorKind::InvalidData, reason.to_string()));
+        if self.committee_port_offsets.len() as u64 != self.committee_size {
+            return invalid("committee port offsets do not match the committee size");
+        }
+        if self.reconstruction_threshold == 0 || self.reconstruction_threshold > self.committee_size {
+            return invalid("reconstruction threshold must be between 1 and the committee size");
+        }
+        if self.key_homomorphic_masks && self.reuse_public_seed {
+            return invalid("key-homomorphic masks need a fresh public seed per round");
+        }
+        if let Some(bits) = self.range_proof_bits {
+            if !RANGE_BITS.contains(&bits) {
+                return invalid(&ParameterError::InvalidRangeBits { bits }.to_string());
+            }
+            if self.key_homomorphic_masks {
+                return invalid(&ParameterError::RangeProofsWithRegisteredKeys.to_string());
+            }
+        }
+        if self.verifiable_aggregation && self.key_homomorphic_masks {
+            return invalid(&ParameterError::VerifiableAggregationWithRegisteredKeys.to_string());
+        }
+        if self.norm_bound.is_some() && self.range_proof_bits.is_none() {
+            return invalid(&ParameterError::NormBoundWithoutRangeProofs.to_string());
+        }
+        if let Some(ref selection) = self.committee_selection
+            && selection.population.windows(2).any(|w| w[0] >= w[1])
+        {
+            return invalid("committee population is not strictly ordered");
+        }
+        let selection = self.committee_selection.as_ref();
+        let offsets = &self.committee_port_offsets;
+        if let Err(e) = check_committee(selection, self.committee_size, self.port, offsets) {
+            return invalid(&e.to_string());
+        }
+        if self.seed_derivation.as_ref().is_some_and(|derivation| derivation.seed() != self.succinct_seed) {
+            return invalid(&SeedDerivationError::NotDerived.to_string());
+        }
+        if self.committee_keys.windows(2).any(|w| w[0].port >= w[1].port) {
+            return invalid("committee keys are not strictly ordered by port");
+        }
+        let has_key = |offset: &u16| {
+            let port = self.port.wrapping_add(*offset);
+            self.committee_keys.iter().any(|committee_key| committee_key.port == port)
+        };
+        if !self.committee_keys.is_empty() && !self.committee_port_offsets.iter().all(has_key) {
+            return invalid("a committee member has no key to seal its shares to");
+        }
+        Ok(())
+    }
+}
+
+fn check_version(version: u16) -> io::Result<()> {
+    if version != PUBLIC_PARAMS_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("public parameter version {} (expected {})", version, PUBLIC_PARAMS_VERSION),
+        ));
+    }
+    Ok(())
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    read_array(reader).map(u64::from_le_bytes)
+}
+
+impl OPAState {
+    /// The parameters to publish to this session's clients and committee.
+    pub fn public_params(&self) -> OPAPublicParams {
+        OPAPublicParams {
+            session_id: self.session_id,
+            round_id: self.round_id,
+            succinct_seed: self.succinct_seed,
+            reuse_public_seed: self.reuse_public_seed,
+            seed_derivation: self.seed_derivation.clone(),
+            key_homomorphic_masks: self.key_homomorphic_masks,
+            admission_control: self.admission_control,
+            verifiable_aggregation: self.verifiable_aggregation,
+            range_proof_bits: self.range_proof_bits,
+            norm_bound: self.norm_bound,
+            security_parameter: self.security_parameter,
+            corruption_threshold: self.corruption_threshold,
+            reconstruction_threshold: self.reconstruction_threshold,
+            committee_size: self.committee_size,
+            committee_port_offsets: self.committee_port_offsets.clone(),
+            port: self.port,
+            input_deadline_ms: self.input_deadline.map(|deadline| deadline.as_millis() as u64),
+            committee_timeout_ms: self.committee_timeout.as_millis() as u64,
+            min_participants: self.min_participants,
+            committee_selection: self.committee_selection.clone(),
+            committee_keys: self
+                .committee_keys
+                .iter()
+                .map(|(&port, &key)| CommitteeKey { port, key })
+                .collect(),
+        }
+    }
+}
+
+/// A participant's view of the server state, with empty runtime fields.
+impl From<OPAPublicParams> for OPAState {
+    fn from(params: OPAPublicParams) -> Self {
+        Self {
+            session_id: params.session_id,
+            round_id: params.round_id,
+            succinct_seed: params.succinct_seed,
+            reuse_public_seed: params.reuse_public_seed,
+            seed_derivation: params.seed_derivation,
+            key_homomorphic_masks: params.key_homomorphic_masks,
+            admission_control: params.admission_control,
+            verifiable_aggregation: params.verifiable_aggregation,
+            range_proof_bits: params.range_proof_bits,
+            norm_bound: params.norm_bound,
+            security_parameter: params.security_parameter,
+            corruption_threshold: params.corruption_threshold,
+            reconstruction_threshold: params.reconstruction_threshold,
+            committee_size: params.committee_size,
+            committee_port_offsets: params.committee_port_offsets,
+            port: params.port,
+            input_deadline: params.input_deadline_ms.map(Duration::from_millis),
+            committee_timeout: Duration::from_millis(params.committee_timeout_ms),
+            min_participants: params.min_participants,
+            committee_selection: params.committee_selection,
+            committee_keys: params
+                .committee_keys
+                .into_iter()
+                .map(|committee_key| (committee_key.port, committee_key.key))
+                .collect(),
+            output_sender: None,
+            client_inputs: Arc::new(Mutex::new(ClientInputs::default())),
+            round: Arc::new(Round::new()),
+            buffer_budget: None,
+            checkpoint: None,
+            client_registry: None,
+            address_book: None,
+            committee_outputs: Vec::new(),
+            aggregate_proof: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+/// The succinct seed as a hex string in JSON.
+pub(crate) mod hex_seed {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(seed: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = seed.iter().map(|b| format!("{:02x}", b)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(D::Error::custom("succinct seed must be 64 hex digits"));
+        }
+        let mut seed = [0u8; 32];
+        for (byte, digits) in seed.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            let digits = std::str::from_utf8(digits).map_err(D::Error::custom)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(D::Error::custom)?;
+        }
+        Ok(seed)
+    }
+
+    /// An optional seed, as a hex string or null.
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(seed: &Option<[u8; 32]>, serializer: S) -> Result<S::Ok, S::Error> {
+            match seed {
+                Some(seed) => super::serialize(seed, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<[u8; 32]>, D::Error> {
+            #[derive(Deserialize)]
+            struct Hex(#[serde(with = "super")] [u8; 32]);
+            Ok(Option::<Hex>::deserialize(deserializer)?.map(|Hex(seed)| seed))
+        }
+    }
+
+    /// A list of seeds or keys, as hex strings.
+    pub mod vec {
+        use serde::{Deserialize, Deserializer};
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<[u8; 32]>, D::Error> {
+            #[derive(Deserialize)]
+            struct Hex(#[serde(with = "super")] [u8; 32]);
+            Ok(Vec::<Hex>::deserialize(deserializer)?.into_iter().map(|Hex(seed)| seed).collect())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::client::Client;
+    use crate::protocols::committee::Committee;
+    use crate::protocols::server::Server;
+    use crate::protocols::opa::{OPAClient, OPACommittee, OPAServer, OPASetupParameters};
+
+    fn published() -> OPAPublicParams {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3)
+            .with_session_id(7)
+            .with_input_deadline(Duration::from_secs(5));
+        OPAServer::new(parameters).get_state().public_params()
+    }
+
+    #[test]
+    // test that both encodings round-trip and agree on the fingerprint
+    fn test_public_params_encoding() {
+        let params = published();
+        let from_bytes = OPAPublicParams::decode(&params.to_bytes()).unwrap();
+        let from_json = OPAPublicParams::decode(params.to_json().as_bytes()).unwrap();
+        assert_eq!(from_bytes, params);
+        assert_eq!(from_json, params);
+        assert_eq!(from_json.fingerprint(), params.fingerprint());
+        let version = format!("\"version\": {}", PUBLIC_PARAMS_VERSION);
+        assert!(params.to_json().contains(&version));
+
+        let other = OPAPublicParams { round_id: 1, ..params.clone() };
+        assert_ne!(other.fingerprint(), params.fingerprint());
+
+        // other versions, truncated or trailing bytes, and impossible setups are refused
+        let mut bytes = params.to_bytes();
+        bytes[4] = PUBLIC_PARAMS_VERSION as u8 + 1;
+        assert_eq!(OPAPublicParams::from_bytes(&bytes).unwrap_err().kind(), io::ErrorKind::Unsupported);
+        let json = params.to_json().replace(&version, "\"version\": 1");
+        assert_eq!(OPAPublicParams::from_json(&json).unwrap_err().kind(), io::ErrorKind::Unsupported);
+        let bytes = params.to_bytes();
+        assert!(OPAPublicParams::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(OPAPublicParams::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+
+        // committee keys travel in both encodings, and every member needs one
+        let keys: Vec<CommitteeKey> = (0..3u16)
+            .map(|i| CommitteeKey {
+                port: params.port.wrapping_add(params.committee_port_offsets[i as usize]),
+                key: [i as u8 + 1; 32],
+            })
+            .collect();
+        let sealed = OPAPublicParams { committee_keys: keys.clone(), ..params.clone() };
+        assert_eq!(OPAPublicParams::decode(&sealed.to_bytes()).unwrap(), sealed);
+        assert_eq!(OPAPublicParams::decode(sealed.to_json().as_bytes()).unwrap(), sealed);
+        assert_ne!(sealed.fingerprint(), params.fingerprint());
+        let missing = OPAPublicParams { committee_keys: keys[1..].to_vec(), ..params.clone() };
+        assert!(OPAPublicParams::from_bytes(&missing.to_bytes()).is_err());
+
+        let verifiable = OPAPublicParams { verifiable_aggregation: true, ..params.clone() };
+        assert_eq!(OPAPublicParams::decode(&verifiable.to_bytes()).unwrap(), verifiable);
+        assert_ne!(verifiable.fingerprint(), params.fingerprint());
+        let keyed = OPAPublicParams { key_homomorphic_masks: true, ..verifiable.clone() };
+        assert!(OPAPublicParams::from_bytes(&keyed.to_bytes()).is_err());
+        let proven = OPAPublicParams { range_proof_bits: Some(16), ..params.clone() };
+        assert_eq!(OPAPublicParams::decode(&proven.to_bytes()).unwrap(), proven);
+        assert_eq!(OPAPublicParams::decode(proven.to_json().as_bytes()).unwrap(), proven);
+        let odd_width = OPAPublicParams { range_proof_bits: Some(12), ..params.clone() };
+        assert!(OPAPublicParams::from_bytes(&odd_width.to_bytes()).is_err());
+        let bounded = OPAPublicParams { norm_bound: Some(NormBound::L2(1000)), ..proven.clone() };
+        assert_eq!(OPAPublicParams::decode(&bounded.to_bytes()).unwrap(), bounded);
+        assert_eq!(OPAPublicParams::decode(bounded.to_json().as_bytes()).unwrap(), bounded);
+        assert_ne!(bounded.fingerprint(), proven.fingerprint());
+        let unproven = OPAPublicParams { norm_bound: Some(NormBound::LInf(7)), ..params.clone() };
+        assert!(OPAPublicParams::from_bytes(&unproven.to_bytes()).is_err());
+
+        let broken = OPAPublicParams { committee_size: 4, ..params };
+        assert!(OPAPublicParams::from_bytes(&broken.to_bytes()).is_err());
+    }
+
+    #[test]
+    // test that clients and committee members load published parameters from bytes or a file
+    fn test_load_public_params() {
+        let params = published();
+        let path = std::env::temp_dir().join(format!("jagguar-params-{}.json", std::process::id()));
+        fs::write(&path, params.to_json()).unwrap();
+
+        let mut client = OPAClient::<u32>::new();
+        client.load_public_params_file(&path).unwrap();
+        let mut committee = OPACommittee::new(18621);
+        committee.load_public_params(&params.to_bytes()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let state = client.server_state().unwrap();
+        assert_eq!(state.public_params(), params);
+        assert_eq!(state.input_deadline, Some(Duration::from_secs(5)));
+        assert_eq!(committee.server_state().unwrap().public_params().fingerprint(), params.fingerprint());
+    }
+}
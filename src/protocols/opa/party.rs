@@ -0,0 +1,182 @@
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+use ed25519_dalek::SigningKey;
+
+use crate::crypto::{DecryptionKey, EncryptionKey};
+use crate::protocols::client::Client;
+use crate::protocols::committee::Committee;
+use crate::protocols::party::Party;
+use crate::protocols::opa::client::OPAClient;
+use crate::protocols::opa::committee::OPACommittee;
+use crate::protocols::opa::params::OPAPublicParams;
+use crate::protocols::opa::server::OPAState;
+
+/// An OPA client that also serves on the committee when the server draws it, as OPA
+/// intends: it submits its input and, once inputs close, sums its shares of every
+/// client's seed, through one communicator on its port. The server knows the two roles
+/// as the same client id and committee member.
+pub struct OPAParty<T> {
+    client: OPAClient<T>,
+    /// The committee role, which owns the connection the client role submits through.
+    committee: OPACommittee,
+}
+
+impl<T: Copy + Into<u32> + num_traits::FromPrimitive> OPAParty<T> {
+    pub fn client(&self) -> &OPAClient<T> {
+        &self.client
+    }
+
+    pub fn server_state(&self) -> Option<&OPAState> {
+        self.client.server_state()
+    }
+
+    /// Reach a server on another host instead of this one.
+    pub fn set_server_ip(&mut self, ip: IpAddr) {
+        self.client.set_server_ip(ip);
+        self.committee.set_server_ip(ip);
+    }
+
+    /// Sign every message to the server with `key`, in both roles.
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.committee.set_signing_key(key);
+    }
+
+    /// Open the shares sealed to this party with `key`, should it be drawn to the committee.
+    pub fn set_decryption_key(&mut self, key: DecryptionKey) {
+        self.committee.set_decryption_key(key);
+    }
+
+    /// The key clients seal this party's shares to, for the server to publish.
+    pub fn encryption_key(&self) -> EncryptionKey {
+        self.committee.encryption_key()
+    }
+
+    /// Join the session described by public parameters the server published, in either
+    /// encoding.
+    pub fn load_public_params(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let params = OPAPublicParams::decode(bytes)?;
+        Party::set_server_state(self, params.into());
+        Ok(())
+    }
+
+    pub fn load_public_params_file(&mut self, path: &Path) -> io::Result<()> {
+        let params = OPAPublicParams::from_file(path)?;
+        Party::set_server_state(self, params.into());
+        Ok(())
+    }
+
+    /// Get admitted to the round if the server runs admission control, like
+    /// `OPAClient::admit`.
+    pub fn admit(&mut self) -> io::Result<()> {
+        self.client.admit_with(self.committee.communicator())
+    }
+
+    /// Submit the encrypted input and wait for the server to accept it, like
+    /// `OPAClient::submit`.
+    pub fn submit(&self) -> io::Result<()> {
+        self.client.submit_with(self.committee.communicator())
+    }
+
+    fn assert_on_committee(&self) {
+        assert!(self.on_committee(), "party on port {} is not on the committee", self.port());
+    }
+}
+
+impl<T: Copy + Into<u32> + num_traits::FromPrimitive> Party<T> for OPAParty<T> {
+    type ServerState = OPAState;
+
+    fn new(port: u16) -> Self {
+        Self {
+            client: OPAClient::new(),
+            committee: OPACommittee::new(port),
+        }
+    }
+
+    fn port(&self) -> u16 {
+        self.committee.communicator().port()
+    }
+
+    fn set_server_state(&mut self, state: Self::ServerState) {
+        self.client.set_server_state(state.clone());
+        self.committee.set_server_state(state);
+    }
+
+    fn set_input(&mut self, input: Vec<T>) {
+        self.client.set_input(input);
+    }
+
+    fn encrypt_input(&mut self) {
+        self.client.encrypt_input();
+    }
+
+    fn send_input(&mut self) {
+        self.client.send_input_with(self.committee.communicator());
+    }
+
+    fn on_committee(&self) -> bool {
+        self.server_state().is_some_and(|state| state.committee_index(self.port()).is_some())
+    }
+
+    fn retrieve_inputs(&mut self) {
+        self.assert_on_committee();
+        self.committee.retrieve_inputs();
+    }
+
+    fn aggregate(&mut self) {
+        self.assert_on_committee();
+        self.committee.aggregate();
+    }
+
+    fn send_output(&mut self) {
+        self.assert_on_committee();
+        self.committee.send_output();
+    }
+
+    fn set_checkpoint_dir(&mut self, dir: &Path) -> io::Result<()> {
+        self.committee.set_checkpoint_dir(dir)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::server::Server;
+    use crate::protocols::opa::{OPAServer, OPASetupParameters};
+
+    #[test]
+    // test that exactly the parties the server draws take on committee duty, on their client port
+    fn test_committee_duty() {
+        let ports: Vec<u16> = (18_671..18_676).collect();
+        let mut server = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3));
+        let population: Vec<u64> = ports.iter().map(|&port| port as u64).collect();
+        server.select_committee(&population, Some([5; 32])).unwrap();
+        let state = server.get_state().clone();
+
+        let parties: Vec<OPAParty<u32>> = ports
+            .iter()
+            .map(|&port| {
+                let mut party = OPAParty::new(port);
+                assert!(!party.on_committee());
+                party.set_server_state(state.clone());
+                party
+            })
+            .collect();
+        let members: Vec<u16> = parties
+            .iter()
+            .filter(|party| party.on_committee())
+            .map(|party| party.port())
+            .collect();
+        let drawn = state.committee_selection.as_ref().unwrap().draw(3).unwrap();
+        let mut expected: Vec<u16> = drawn.iter().map(|&id| id as u16).collect();
+        expected.sort_unstable();
+        assert_eq!(members, expected);
+
+        // a party left off the committee cannot act as a member
+        let mut outsider = parties.into_iter().find(|party| !party.on_committee()).unwrap();
+        let refused = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| outsider.retrieve_inputs()));
+        assert!(refused.is_err());
+    }
+}
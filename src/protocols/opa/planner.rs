@@ -0,0 +1,527 @@
+use std::fmt;
+use std::io;
+
+use crate::crypto::SeedHomomorphicPRG;
+use crate::crypto::seed_homomorphic_prg::{DEFAULT_INNER_MODULUS_BITS, MIN_SECURITY_BITS};
+use crate::protocols::opa::client::{NUM_PARTIES_UPPER_BOUND, OUTPUT_LEN};
+use crate::protocols::opa::server::OPASetupParameters;
+
+/// Default statistical security, in bits, of the masked encoding.
+pub const DEFAULT_STATISTICAL_SECURITY: u32 = 40;
+/// Default bound on the probability that the sampled committee breaks privacy or cannot
+/// reconstruct the seed.
+pub const DEFAULT_FAILURE_PROBABILITY: f64 = 1.0 / (1u64 << 40) as f64;
+/// Largest committee the planner considers.
+pub const MAX_PLANNED_COMMITTEE_SIZE: u64 = 1024;
+/// Most clients one round can aggregate: the offset and noise of `n` clients add up to
+/// less than `2 * n * 2^kappa`, which must stay below one unit `2^kappa * NUM_PARTIES_UPPER_BOUND`
+/// of the encoding.
+pub const MAX_CLIENTS: u64 = NUM_PARTIES_UPPER_BOUND / 2;
+/// Widest security parameter that still leaves room to decode an aggregate of one.
+pub const MAX_SECURITY_PARAMETER: u64 = 128 - NUM_PARTIES_UPPER_BOUND.ilog2() as u64 - 2;
+
+/// Why a set of protocol parameters, or the requirements they are planned from, cannot work.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterError {
+    /// Shamir sharing needs at least two shares to reconstruct.
+    ReconstructionThresholdTooSmall { reconstruction_threshold: u64 },
+    /// More shares are needed to reconstruct than there are committee members.
+    ReconstructionThresholdExceedsCommittee { reconstruction_threshold: u64, committee_size: u64 },
+    /// The tolerated corrupt members could reconstruct every seed between them.
+    CorruptionThresholdTooLarge { corruption_threshold: u64, reconstruction_threshold: u64 },
+    /// The committee's port offsets run past the last port.
+    CommitteeTooLarge { committee_size: u64, max: u64 },
+    /// 2^kappa * NUM_PARTIES_UPPER_BOUND leaves no room for the aggregate in Z_{2^128}.
+    SecurityParameterTooLarge { security_parameter: u64, max: u64 },
+    /// The round requires more clients than the encoding can aggregate.
+    TooManyClients { num_clients: u64, max: u64 },
+    /// A fraction or probability outside its range.
+    InvalidProbability { name: &'static str, value: f64 },
+    /// Inputs must be between 1 and 32 bits wide.
+    InvalidInputBits { input_bits: u32 },
+    /// A client vector must have between one and `OUTPUT_LEN` values.
+    InvalidVectorLength { vector_len: usize, max: usize },
+    /// The largest possible aggregate does not decode under the security parameter needed.
+    AggregateTooLarge { max_aggregate: u128, max_decodable: u64 },
+    /// No committee within the size limit keeps both failure probabilities below the bound.
+    NoCommittee { max_committee_size: u64, failure_probability: f64 },
+    /// The SHPRG's LWR instance is estimated weaker than required.
+    InsufficientComputationalSecurity { estimated: u32, required: u32 },
+    /// Range proofs cover a width from `RANGE_BITS` only.
+    InvalidRangeBits { bits: u32 },
+    /// Range proofs need blinding shares every round, which clients masking under a
+    /// registered key do not send.
+    RangeProofsWithRegisteredKeys,
+    /// An aggregate proof publishes the summed seed, which under registered keys would be
+    /// a long-term key sum.
+    VerifiableAggregationWithRegisteredKeys,
+    /// Masks under registered keys are evaluated at the round's public seed, so a seed
+    /// reused across rounds would repeat them.
+    PublicSeedReuseWithRegisteredKeys,
+    /// Norm proofs refer to the commitments of the range proofs.
+    NormBoundWithoutRangeProofs,
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterError::ReconstructionThresholdTooSmall { reconstruction_threshold } => write!(
+                f,
+                "reconstruction threshold {} is below 2, so a single member would learn every seed",
+                reconstruction_threshold
+            ),
+            ParameterError::ReconstructionThresholdExceedsCommittee {
+                reconstruction_threshold,
+                committee_size,
+            } => write!(
+                f,
+                "reconstruction threshold {} exceeds the committee size {}, so the seed can never be \
+                 reconstructed",
+                reconstruction_threshold, committee_size
+            ),
+            ParameterError::CorruptionThresholdTooLarge {
+                corruption_threshold,
+                reconstruction_threshold,
+            } => write!(
+                f,
+                "{} corrupt members reach the reconstruction threshold {}; it must exceed the corruption \
+                 threshold",
+                corruption_threshold, reconstruction_threshold
+            ),
+            ParameterError::CommitteeTooLarge { committee_size, max } => write!(
+                f,
+                "committee of {} members does not fit the port range (at most {})",
+                committee_size, max
+            ),
+            ParameterError::SecurityParameterTooLarge { security_parameter, max } => write!(
+                f,
+                "security parameter {} leaves no room for the aggregate in 128 bits (at most {})",
+                security_parameter, max
+            ),
+            ParameterError::TooManyClients { num_clients, max } => {
+                write!(f, "{} clients exceed the {} the encoding can aggregate", num_clients, max)
+            }
+            ParameterError::InvalidProbability { name, value } => {
+                write!(f, "{} must be in [0, 1), got {}", name, value)
+            }
+            ParameterError::InvalidInputBits { input_bits } => {
+                write!(f, "inputs of {} bits do not fit a 32-bit slot", input_bits)
+            }
+            ParameterError::InvalidVectorLength { vector_len, max } => {
+                write!(f, "vector length {} is not between 1 and {}", vector_len, max)
+            }
+            ParameterError::AggregateTooLarge { max_aggregate, max_decodable } => write!(
+                f,
+                "aggregate may reach {} but at most {} decodes; use fewer clients or narrower inputs",
+                max_aggregate, max_decodable
+            ),
+            ParameterError::NoCommittee { max_committee_size, failure_probability } => write!(
+                f,
+                "no committee of at most {} members fails with probability below {:e}; \
+                 lower the corruption fraction or dropout rate",
+                max_committee_size, failure_probability
+            ),
+            ParameterError::InsufficientComputationalSecurity { estimated, required } => write!(
+                f,
+                "the SHPRG masks are estimated at {} bits of security, below the {} required",
+                estimated, required
+            ),
+            ParameterError::InvalidRangeBits { bits } => {
+                write!(f, "range proofs cover 8, 16 or 32 bits, not {}", bits)
+            }
+            ParameterError::RangeProofsWithRegisteredKeys => {
+                write!(f, "range proofs cannot be combined with key-homomorphic masks")
+            }
+            ParameterError::VerifiableAggregationWithRegisteredKeys => {
+                write!(f, "verifiable aggregation cannot be combined with key-homomorphic masks")
+            }
+            ParameterError::PublicSeedReuseWithRegisteredKeys => {
+                write!(f, "key-homomorphic masks need a fresh public seed per round")
+            }
+            ParameterError::NormBoundWithoutRangeProofs => {
+                write!(f, "a norm bound needs range proofs")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParameterError {}
+
+impl From<ParameterError> for io::Error {
+    fn from(e: ParameterError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+/// What a deployment needs from the protocol. `plan` derives the committee, the thresholds
+/// and kappa from it.
+///
+/// The committee is modelled as sampled from a population in which every member is
+/// independently corrupt with probability `corruption_fraction` and drops out with
+/// probability `dropout_rate`; corrupt members may withhold their output.
+#[derive(Debug, Clone, Copy)]
+pub struct DeploymentRequirements {
+    num_clients: u64,
+    input_bits: u32,
+    vector_len: usize,
+    corruption_fraction: f64,
+    dropout_rate: f64,
+    statistical_security: u32,
+    computational_security: u32,
+    failure_probability: f64,
+}
+
+/// Parameters derived by `DeploymentRequirements::plan`, with the security they achieve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterPlan {
+    pub security_parameter: u64,
+    pub corruption_threshold: u64,
+    pub reconstruction_threshold: u64,
+    pub committee_size: u64,
+    /// Statistical distance, in bits, between the masked encoding of the aggregate and one
+    /// carrying no SHPRG rounding error, over every slot.
+    pub statistical_security: f64,
+    /// Estimated hardness, in bits, of the LWR problem behind the SHPRG masks.
+    pub computational_security: u32,
+    /// Probability that more than `corruption_threshold` members are corrupt.
+    pub privacy_failure_probability: f64,
+    /// Probability that fewer than `reconstruction_threshold` members return their output.
+    pub liveness_failure_probability: f64,
+    /// Largest value an aggregate may reach.
+    pub max_aggregate: u128,
+}
+
+impl DeploymentRequirements {
+    /// Requirements for `num_clients` clients each submitting `vector_len` values of at
+    /// most `input_bits` bits, with an honest committee that never drops out.
+    pub fn new(num_clients: u64, input_bits: u32, vector_len: usize) -> Self {
+        Self {
+            num_clients,
+            input_bits,
+            vector_len,
+            corruption_fraction: 0.0,
+            dropout_rate: 0.0,
+            statistical_security: DEFAULT_STATISTICAL_SECURITY,
+            computational_security: MIN_SECURITY_BITS as u32,
+            failure_probability: DEFAULT_FAILURE_PROBABILITY,
+        }
+    }
+
+    /// Tolerate this fraction of corrupt committee candidates.
+    pub fn with_corruption_fraction(mut self, fraction: f64) -> Self {
+        self.corruption_fraction = fraction;
+        self
+    }
+
+    /// Tolerate committee members dropping out at this rate.
+    pub fn with_dropout_rate(mut self, rate: f64) -> Self {
+        self.dropout_rate = rate;
+        self
+    }
+
+    /// Require this many bits of statistical security from the encoding.
+    pub fn with_statistical_security(mut self, bits: u32) -> Self {
+        self.statistical_security = bits;
+        self
+    }
+
+    /// Require the SHPRG masks to resist attacks costing fewer than 2^bits operations.
+    pub fn with_computational_security(mut self, bits: u32) -> Self {
+        self.computational_security = bits;
+        self
+    }
+
+    /// Bound the probability that the committee breaks privacy, and separately that it
+    /// cannot finish the round.
+    pub fn with_failure_probability(mut self, probability: f64) -> Self {
+        self.failure_probability = probability;
+        self
+    }
+
+    /// Pick the smallest committee and kappa meeting the requirements.
+    pub fn plan(&self) -> Result<ParameterPlan, ParameterError> {
+        self.check()?;
+        let max_aggregate = self.num_clients as u128 * ((1u128 << self.input_bits) - 1);
+
+        // the rounding error of the SHPRG masks is at most one per client and slot, and
+        // the noise of 2^kappa smudges it within num_clients * vector_len / 2^kappa
+        let smudged_bits = (self.num_clients as f64).log2() + (self.vector_len as f64).log2();
+        let security_parameter = (self.statistical_security as f64 + smudged_bits).ceil().max(1.0) as u64;
+        if security_parameter > MAX_SECURITY_PARAMETER {
+            return Err(ParameterError::SecurityParameterTooLarge {
+                security_parameter,
+                max: MAX_SECURITY_PARAMETER,
+            });
+        }
+        let max_decodable = max_aggregate_value(security_parameter);
+        if max_aggregate > max_decodable as u128 {
+            return Err(ParameterError::AggregateTooLarge { max_aggregate, max_decodable });
+        }
+        let computational_security = SeedHomomorphicPRG::lwr_parameters(DEFAULT_INNER_MODULUS_BITS)
+            .estimate(Default::default())
+            .bits()
+            .floor() as u32;
+        if computational_security < self.computational_security {
+            return Err(ParameterError::InsufficientComputationalSecurity {
+                estimated: computational_security,
+                required: self.computational_security,
+            });
+        }
+
+        let available = (1.0 - self.corruption_fraction) * (1.0 - self.dropout_rate);
+        for committee_size in 2..=MAX_PLANNED_COMMITTEE_SIZE {
+            // the fewest shares that more corrupt members than likely still cannot reach
+            let tail = |shares| binomial_upper_tail(committee_size, self.corruption_fraction, shares);
+            let corruption_threshold = (0..committee_size).find(|&t| tail(t + 1) <= self.failure_probability);
+            let Some(corruption_threshold) = corruption_threshold else { continue };
+            let reconstruction_threshold = (corruption_threshold + 1).max(2);
+            if reconstruction_threshold > committee_size {
+                continue;
+            }
+            let liveness_failure_probability =
+                1.0 - binomial_upper_tail(committee_size, available, reconstruction_threshold);
+            if liveness_failure_probability > self.failure_probability {
+                continue;
+            }
+            let plan = ParameterPlan {
+                security_parameter,
+                corruption_threshold,
+                reconstruction_threshold,
+                committee_size,
+                statistical_security: security_parameter as f64 - smudged_bits,
+                computational_security,
+                privacy_failure_probability: binomial_upper_tail(
+                    committee_size,
+                    self.corruption_fraction,
+                    corruption_threshold + 1,
+                ),
+                liveness_failure_probability: liveness_failure_probability.max(0.0),
+                max_aggregate,
+            };
+            plan.setup_parameters().validate()?;
+            return Ok(plan);
+        }
+        Err(ParameterError::NoCommittee {
+            max_committee_size: MAX_PLANNED_COMMITTEE_SIZE,
+            failure_probability: self.failure_probability,
+        })
+    }
+
+    fn check(&self) -> Result<(), ParameterError> {
+        if self.num_clients > MAX_CLIENTS {
+            return Err(ParameterError::TooManyClients { num_clients: self.num_clients, max: MAX_CLIENTS });
+        }
+        if self.input_bits == 0 || self.input_bits > 32 {
+            return Err(ParameterError::InvalidInputBits { input_bits: self.input_bits });
+        }
+        if self.vector_len == 0 || self.vector_len > OUTPUT_LEN {
+            return Err(ParameterError::InvalidVectorLength { vector_len: self.vector_len, max: OUTPUT_LEN });
+        }
+        for (name, value) in [
+            ("corruption fraction", self.corruption_fraction),
+            ("dropout rate", self.dropout_rate),
+            ("failure probability", self.failure_probability),
+        ] {
+            if !(0.0..1.0).contains(&value) {
+                return Err(ParameterError::InvalidProbability { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ParameterPlan {
+    /// Setup parameters carrying the planned committee and kappa, with every other
+    /// setting at its default.
+    pub fn setup_parameters(&self) -> OPASetupParameters {
+        OPASetupParameters::new(
+            self.security_parameter,
+            self.corruption_threshold,
+            self.reconstruction_threshold,
+            self.committee_size,
+        )
+    }
+}
+
+/// Largest aggregate coordinate that decodes under `security_parameter`; see
+/// `OPAState::max_aggregate_value`.
+pub(crate) fn max_aggregate_value(security_parameter: u64) -> u64 {
+    let scaling_bits = security_parameter + NUM_PARTIES_UPPER_BOUND.ilog2() as u64;
+    let encoding_bound = 128u64
+        .checked_sub(scaling_bits)
+        .map_or(0, |bits| (1u128 << bits.min(64)).saturating_sub(2));
+    encoding_bound.min(u32::MAX as u128) as u64
+}
+
+/// P[X >= k] for X ~ Binomial(n, p), summed in log space so large committees do not underflow.
+fn binomial_upper_tail(n: u64, p: f64, k: u64) -> f64 {
+    if k == 0 {
+        return 1.0;
+    }
+    if k > n || p <= 0.0 {
+        return 0.0;
+    }
+    if p >= 1.0 {
+        return 1.0;
+    }
+    let (ln_p, ln_q) = (p.ln(), (1.0 - p).ln());
+    // ln of C(n, i) p^i q^(n-i), starting from i = 0
+    let mut ln_pmf = n as f64 * ln_q;
+    let mut tail = 0.0;
+    for i in 0..=n {
+        if i > 0 {
+            ln_pmf += ((n - i + 1) as f64).ln() - (i as f64).ln() + ln_p - ln_q;
+        }
+        if i >= k {
+            tail += ln_pmf.exp();
+        }
+    }
+    tail.min(1.0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::opa::{NormBound, OPAServer};
+    use crate::protocols::server::Server;
+
+    #[test]
+    // test that the planner picks committees meeting the failure bound and a kappa that decodes
+    fn test_plan() {
+        let honest = DeploymentRequirements::new(1000, 16, 1024).plan().unwrap();
+        assert_eq!(
+            (honest.committee_size, honest.corruption_threshold, honest.reconstruction_threshold),
+            (2, 0, 2)
+        );
+        assert_eq!(honest.security_parameter, 40 + 10 + 10);
+        assert!(honest.statistical_security >= 40.0);
+
+        let requirements = DeploymentRequirements::new(10_000, 8, OUTPUT_LEN)
+            .with_corruption_fraction(0.1)
+            .with_dropout_rate(0.05);
+        let plan = requirements.plan().unwrap();
+        assert!(plan.privacy_failure_probability <= DEFAULT_FAILURE_PROBABILITY);
+        assert!(plan.liveness_failure_probability <= DEFAULT_FAILURE_PROBABILITY);
+        assert!(plan.corruption_threshold < plan.reconstruction_threshold);
+        assert!(plan.reconstruction_threshold <= plan.committee_size);
+        // corrupt candidates need more than the smallest committee
+        assert!(plan.committee_size > 2);
+        assert!(plan.computational_security >= MIN_SECURITY_BITS as u32);
+
+        let parameters = plan.setup_parameters();
+        parameters.validate().unwrap();
+        let state = OPAServer::new(parameters).get_state().clone();
+        assert!(state.max_aggregate_value() as u128 >= plan.max_aggregate);
+        assert_eq!(state.max_aggregate_value(), max_aggregate_value(plan.security_parameter));
+    }
+
+    #[test]
+    // test that inconsistent requirements and setup parameters are refused with the reason
+    fn test_parameter_errors() {
+        let plan = |requirements: DeploymentRequirements| requirements.plan().unwrap_err();
+        assert!(matches!(
+            plan(DeploymentRequirements::new(MAX_CLIENTS + 1, 8, 16)),
+            ParameterError::TooManyClients { .. }
+        ));
+        assert!(matches!(
+            plan(DeploymentRequirements::new(10, 33, 16)),
+            ParameterError::InvalidInputBits { .. }
+        ));
+        assert!(matches!(
+            plan(DeploymentRequirements::new(10, 8, OUTPUT_LEN + 1)),
+            ParameterError::InvalidVectorLength { .. }
+        ));
+        assert!(matches!(
+            plan(DeploymentRequirements::new(10, 8, 16).with_dropout_rate(1.0)),
+            ParameterError::InvalidProbability { name: "dropout rate", .. }
+        ));
+        assert!(matches!(
+            plan(DeploymentRequirements::new(100_000, 32, 16)),
+            ParameterError::AggregateTooLarge { .. }
+        ));
+        assert!(matches!(
+            plan(DeploymentRequirements::new(10, 8, 16).with_statistical_security(100)),
+            ParameterError::SecurityParameterTooLarge { .. }
+        ));
+        assert!(matches!(
+            plan(DeploymentRequirements::new(10, 8, 16).with_corruption_fraction(0.6)),
+            ParameterError::NoCommittee { .. }
+        ));
+        assert!(matches!(
+            plan(DeploymentRequirements::new(10, 8, 16).with_computational_security(256)),
+            ParameterError::InsufficientComputationalSecurity { required: 256, .. }
+        ));
+
+        assert!(OPASetupParameters::new(40, 1, 2, 3).validate().is_ok());
+        assert_eq!(
+            OPASetupParameters::new(40, 2, 2, 3).validate(),
+            Err(ParameterError::CorruptionThresholdTooLarge {
+                corruption_threshold: 2,
+                reconstruction_threshold: 2,
+            })
+        );
+        assert!(matches!(
+            OPASetupParameters::new(40, 1, 4, 3).validate(),
+            Err(ParameterError::ReconstructionThresholdExceedsCommittee { .. })
+        ));
+        assert!(matches!(
+            OPASetupParameters::new(40, 0, 1, 3).validate(),
+            Err(ParameterError::ReconstructionThresholdTooSmall { .. })
+        ));
+        assert!(matches!(
+            OPASetupParameters::new(MAX_SECURITY_PARAMETER + 1, 1, 2, 3).validate(),
+            Err(ParameterError::SecurityParameterTooLarge { .. })
+        ));
+        assert_eq!(
+            OPASetupParameters::new(40, 1, 2, 3).with_range_proofs(12).validate(),
+            Err(ParameterError::InvalidRangeBits { bits: 12 })
+        );
+        assert_eq!(
+            OPASetupParameters::new(40, 1, 2, 3)
+                .with_range_proofs(8)
+                .with_key_homomorphic_masks(true)
+                .validate(),
+            Err(ParameterError::RangeProofsWithRegisteredKeys)
+        );
+        assert_eq!(
+            OPASetupParameters::new(40, 1, 2, 3)
+                .with_verifiable_aggregation(true)
+                .with_key_homomorphic_masks(true)
+                .validate(),
+            Err(ParameterError::VerifiableAggregationWithRegisteredKeys)
+        );
+        assert_eq!(
+            OPASetupParameters::new(40, 1, 2, 3)
+                .with_public_seed_reuse(true)
+                .with_key_homomorphic_masks(true)
+                .validate(),
+            Err(ParameterError::PublicSeedReuseWithRegisteredKeys)
+        );
+        assert_eq!(
+            OPASetupParameters::new(40, 1, 2, 3).with_norm_bound(NormBound::L2(100)).validate(),
+            Err(ParameterError::NormBoundWithoutRangeProofs)
+        );
+        assert!(OPASetupParameters::new(40, 1, 2, 3)
+            .with_range_proofs(8)
+            .with_norm_bound(NormBound::L2(100))
+            .validate()
+            .is_ok());
+        let error: io::Error = OPASetupParameters::new(40, 1, 4, 3).validate().unwrap_err().into();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        // a server is never set up with parameters that fail the check
+        assert!(std::panic::catch_unwind(|| OPAServer::new(OPASetupParameters::new(40, 2, 2, 3))).is_err());
+    }
+
+    #[test]
+    // test that binomial tails match direct computation and stay finite for large committees
+    fn test_binomial_upper_tail() {
+        assert!((binomial_upper_tail(3, 0.5, 2) - 0.5).abs() < 1e-12);
+        assert!((binomial_upper_tail(4, 0.1, 1) - (1.0 - 0.9f64.powi(4))).abs() < 1e-12);
+        assert_eq!(binomial_upper_tail(5, 0.0, 1), 0.0);
+        assert_eq!(binomial_upper_tail(5, 0.3, 0), 1.0);
+        let tail = binomial_upper_tail(MAX_PLANNED_COMMITTEE_SIZE, 0.5, MAX_PLANNED_COMMITTEE_SIZE / 2);
+        assert!(tail > 0.5 && tail < 0.55);
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity, IsIdentity, VartimeMultiscalarMul};
+use merlin::Transcript;
+
+use crate::communicator::SessionId;
+use crate::crypto::prg::{default_prg, populate_random_bytes};
+use crate::crypto::{F256, field_from_bytes, field_to_bytes};
+use crate::protocols::opa::client::{NUM_PARTIES_UPPER_BOUND, OUTPUT_LEN};
+use crate::protocols::opa::round::RoundId;
+
+const TRANSCRIPT_LABEL: &[u8] = b"jagguar/opa/range-proof/v1";
+
+/// Widths a range can be declared with: bulletproofs prove ranges of 8, 16, 32 or 64 bits,
+/// and 32-bit coordinates, the only ones proven, have 32.
+pub const RANGE_BITS: [u32; 3] = [8, 16, 32];
+
+/// Blinding factors are drawn below 2^232, so the committee's sum of up to
+/// `NUM_PARTIES_UPPER_BOUND` of them stays below the Shamir field modulus 2^255 - 19 and
+/// reconstructs to the integer sum.
+const BLINDING_BYTES: usize = 29;
+const _: () = assert!(BLINDING_BYTES * 8 + NUM_PARTIES_UPPER_BOUND.ilog2() as usize <= 254);
+
+/// A client's proof that every coordinate of its 32-bit input lies in `[0, 2^bits)`: a
+/// Pedersen commitment `x_j * B + gamma_j * B'` per coordinate and one aggregated
+/// bulletproof over all of them, bound to the session, the round and the masked input it
+/// is submitted with, so it cannot be lifted onto another submission.
+///
+/// That the commitments open to the input under the mask cannot be checked on receipt:
+/// the mask is rounded, so nothing relates it linearly to the commitments. The client also
+/// shares each `gamma_j` with the committee along with its seed, so once the aggregate is
+/// unmasked the server learns only the blinding sums and checks that the summed
+/// commitments open to the aggregate (see `check_aggregate`). A client that proves one
+/// vector and masks another makes that check fail for the whole round, and since only
+/// sums are ever unmasked, it is not identified.
+#[derive(Debug, Clone)]
+pub struct InputRangeProof {
+    pub commitments: Vec<CompressedRistretto>,
+    pub proof: RangeProof,
+}
+
+impl InputRangeProof {
+    /// Prove that each of `values` is below 2^`bits` for the submission of `masked_input`,
+    /// its payload slots, returning the proof and the blinding factors to share with the
+    /// committee.
+    pub fn prove(
+        values: &[u32],
+        bits: u32,
+        session_id: SessionId,
+        round_id: RoundId,
+        masked_input: &[u128],
+    ) -> Result<(Self, Vec<Scalar>), RangeProofError> {
+        if !RANGE_BITS.contains(&bits) {
+            return Err(RangeProofError::UnsupportedWidth { bits });
+        }
+        if let Some(index) = values.iter().position(|&x| (x as u64) >> bits != 0) {
+            return Err(RangeProofError::OutOfRange { index });
+        }
+        if values.is_empty() || values.len() > OUTPUT_LEN {
+            return Err(RangeProofError::WrongLength);
+        }
+        let blindings: Vec<Scalar> = values.iter().map(|_| random_blinding()).collect();
+        let values: Vec<u64> = values.iter().map(|&x| x as u64).collect();
+        let mut transcript = transcript(session_id, round_id, masked_input);
+        let (proof, commitments) = prove_padded(&values, &blindings, bits, &mut transcript)?;
+        Ok((Self { commitments, proof }, blindings))
+    }
+
+    /// Check the proof for a payload of `len` coordinates submitted with `masked_input`,
+    /// returning the decompressed commitments to fold into the running sum.
+    pub fn verify(
+        &self,
+        bits: u32,
+        len: usize,
+        session_id: SessionId,
+        round_id: RoundId,
+        masked_input: &[u128],
+    ) -> Result<Vec<RistrettoPoint>, RangeProofError> {
+        if self.commitments.len() != len || len == 0 {
+            return Err(RangeProofError::WrongLength);
+        }
+        let points = self
+            .commitments
+            .iter()
+            .map(|commitment| commitment.decompress().ok_or(RangeProofError::InvalidProof))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut transcript = transcript(session_id, round_id, masked_input);
+        verify_padded(&self.proof, &self.commitments, bits, &mut transcript)?;
+        Ok(points)
+    }
+
+    /// `[commitment x len][proof_len u32][proof]`; the length comes from the submission header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.commitments.len() * 32 + 4 + max_proof_len());
+        for commitment in &self.commitments {
+            data.extend_from_slice(commitment.as_bytes());
+        }
+        write_proof(&mut data, &self.proof);
+        data
+    }
+
+    /// Read a proof over `len` coordinates, refusing a proof longer than one over
+    /// `OUTPUT_LEN` 32-bit values can be.
+    pub fn read_from(reader: &mut dyn Read, len: usize) -> io::Result<Self> {
+        let commitments = read_points(reader, len)?;
+        let proof = read_proof(reader)?;
+        Ok(Self { commitments, proof })
+    }
+}
+
+/// Prove each of `values` below 2^`bits` under its blinding. Aggregated proofs cover a
+/// power of two of values; the padding commits to zero with a zero blinding, which is the
+/// identity and left for the verifier to fill in.
+pub(super) fn prove_padded(
+    values: &[u64],
+    blindings: &[Scalar],
+    bits: u32,
+    transcript: &mut Transcript,
+) -> Result<(RangeProof, Vec<CompressedRistretto>), RangeProofError> {
+    let parties = values.len().next_power_of_two();
+    let mut padded_values = values.to_vec();
+    padded_values.resize(parties, 0);
+    let mut padded_blindings = blindings.to_vec();
+    padded_blindings.resize(parties, Scalar::ZERO);
+
+    let (proof, mut commitments) = RangeProof::prove_multiple(
+        &generators(parties),
+        &PedersenGens::default(),
+        transcript,
+        &padded_values,
+        &padded_blindings,
+        bits as usize,
+    )
+    .map_err(|_| RangeProofError::InvalidProof)?;
+    commitments.truncate(values.len());
+    Ok((proof, commitments))
+}
+
+/// Check a proof from `prove_padded` over `commitments`.
+pub(super) fn verify_padded(
+    proof: &RangeProof,
+    commitments: &[CompressedRistretto],
+    bits: u32,
+    transcript: &mut Transcript,
+) -> Result<(), RangeProofError> {
+    let parties = commitments.len().next_power_of_two();
+    let mut padded = commitments.to_vec();
+    padded.resize(parties, RistrettoPoint::identity().compress());
+    proof
+        .verify_multiple(&generators(parties), &PedersenGens::default(), transcript, &padded, bits as usize)
+        .map_err(|_| RangeProofError::InvalidProof)
+}
+
+/// `[proof_len u32][proof]`
+pub(super) fn write_proof(data: &mut Vec<u8>, proof: &RangeProof) {
+    let proof = proof.to_bytes();
+    data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
+    data.extend_from_slice(&proof);
+}
+
+/// Read what `write_proof` wrote, refusing a proof longer than one over `OUTPUT_LEN`
+/// 64-bit values can be.
+pub(super) fn read_proof(reader: &mut dyn Read) -> io::Result<RangeProof> {
+    let mut proof_len = [0u8; 4];
+    reader.read_exact(&mut proof_len)?;
+    let proof_len = u32::from_le_bytes(proof_len) as usize;
+    if proof_len > max_proof_len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "range proof is too long"));
+    }
+    let mut proof = vec![0u8; proof_len];
+    reader.read_exact(&mut proof)?;
+    Ok(RangeProof::from_bytes(&proof).map_err(|_| RangeProofError::InvalidProof)?)
+}
+
+/// Read `len` compressed points; they are only decompressed when the proof is checked.
+pub(super) fn read_points(reader: &mut dyn Read, len: usize) -> io::Result<Vec<CompressedRistretto>> {
+    let mut points = vec![0u8; len * 32];
+    reader.read_exact(&mut points)?;
+    Ok(points.chunks_exact(32).map(|chunk| CompressedRistretto(chunk.try_into().unwrap())).collect())
+}
+
+/// Check that `aggregate`, the unmasked encoded sum over the payload, opens the summed
+/// commitments `committed` under the committee's `blinding_sums`: for every coordinate,
+/// `sum_i V_ij = (y_j + k_j * 2^32) * B + Gamma_j * B'` with `y_j` the decoded word.
+///
+/// Unmasking leaves an error in multiples of `2^32 * denominator`, so the aggregate only
+/// determines each word mod 2^32, exactly as the decoder reads it; the carry `k_j` is what
+/// `num_clients` words proven below `2^bits` can add up to beyond that. Without a possible
+/// carry all coordinates are checked at once under random weights, otherwise each one is
+/// looked up among its possible carries.
+pub fn check_aggregate(
+    committed: &[RistrettoPoint],
+    aggregate: &[u128],
+    denominator: u128,
+    blinding_sums: &[Scalar],
+    bits: u32,
+    num_clients: usize,
+) -> bool {
+    if committed.len() != aggregate.len() || committed.len() != blinding_sums.len() {
+        return false;
+    }
+    let values: Vec<u32> = aggregate
+        .iter()
+        .map(|&x| (x.div_ceil(denominator) as u32).wrapping_sub(1))
+        .collect();
+    let pedersen = PedersenGens::default();
+    let max_carry = (num_clients as u128 * ((1u128 << bits) - 1)) >> 32;
+    if max_carry == 0 {
+        let weights: Vec<Scalar> = committed.iter().map(|_| random_scalar()).collect();
+        let value = weights.iter().zip(&values).map(|(w, &y)| w * Scalar::from(y)).sum::<Scalar>();
+        let blinding = weights.iter().zip(blinding_sums).map(|(w, gamma)| w * gamma).sum::<Scalar>();
+        let scalars = weights.iter().copied().chain([-value, -blinding]);
+        let points = committed.iter().copied().chain([pedersen.B, pedersen.B_blinding]);
+        return RistrettoPoint::vartime_multiscalar_mul(scalars, points).is_identity();
+    }
+    let wrap = pedersen.B * Scalar::from(1u64 << 32);
+    let carries: HashSet<CompressedRistretto> = (0..=max_carry)
+        .scan(RistrettoPoint::identity(), |carry, _| {
+            let point = *carry;
+            *carry += wrap;
+            Some(point.compress())
+        })
+        .collect();
+    committed
+        .iter()
+        .zip(values)
+        .zip(blinding_sums)
+        .all(|((commitment, y), &gamma)| {
+            carries.contains(&(commitment - pedersen.commit(Scalar::from(y), gamma)).compress())
+        })
+}
+
+/// A blinding factor as the Shamir field element the client shares.
+pub fn blinding_to_field(blinding: &Scalar) -> F256 {
+    field_from_bytes(&blinding.to_bytes())
+}
+
+/// A reconstructed sum of blinding factors, which is the integer sum and so reduces to
+/// the sum of the scalars.
+pub fn field_to_blinding(sum: F256) -> Scalar {
+    Scalar::from_bytes_mod_order(field_to_bytes(sum))
+}
+
+fn random_blinding() -> Scalar {
+    let mut bytes = [0u8; 32];
+    populate_random_bytes(&mut bytes[..BLINDING_BYTES], &mut default_prg());
+    Scalar::from_bytes_mod_order(bytes)
+}
+
+pub(super) fn random_scalar() -> Scalar {
+    let mut bytes = [0u8; 64];
+    populate_random_bytes(&mut bytes, &mut default_prg());
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+fn transcript(session_id: SessionId, round_id: RoundId, masked_input: &[u128]) -> Transcript {
+    let mut transcript = Transcript::new(TRANSCRIPT_LABEL);
+    transcript.append_u64(b"session", session_id);
+    transcript.append_u64(b"round", round_id);
+    let masked_input: Vec<u8> = masked_input.iter().flat_map(|x| x.to_le_bytes()).collect();
+    transcript.append_message(b"masked input", &masked_input);
+    transcript
+}
+
+/// Serialized size of the largest proof: 9 elements plus two per halving of
+/// `64 * OUTPUT_LEN` bit commitments, each 32 bytes.
+fn max_proof_len() -> usize {
+    (9 + 2 * (64 * OUTPUT_LEN).ilog2() as usize) * 32
+}
+
+/// Generators for aggregated proofs over `parties` values of up to 64 bits, built once
+/// per size.
+pub(super) fn generators(parties: usize) -> Arc<BulletproofGens> {
+    static CACHE: OnceLock<Mutex<HashMap<usize, Arc<BulletproofGens>>>> = OnceLock::new();
+    let mut cache = CACHE.get_or_init(Default::default).lock().unwrap();
+    Arc::clone(cache.entry(parties).or_insert_with(|| Arc::new(BulletproofGens::new(64, parties))))
+}
+
+/// Why a client's range proof was not produced or not accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeProofError {
+    /// Ranges can only be declared with a width from `RANGE_BITS`.
+    UnsupportedWidth { bits: u32 },
+    /// The coordinate at `index` lies outside the declared range.
+    OutOfRange { index: usize },
+    /// The input exceeds the declared norm bound.
+    NormExceeded,
+    /// The proof covers another number of coordinates than the payload has.
+    WrongLength,
+    /// The proof does not verify, or does not parse.
+    InvalidProof,
+    /// The input is narrower than 32 bits, so packing puts several coordinates in a word.
+    PackedInput,
+}
+
+impl fmt::Display for RangeProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeProofError::UnsupportedWidth { bits } => {
+                write!(f, "ranges of {} bits are not supported; use one of {:?}", bits, RANGE_BITS)
+            }
+            RangeProofError::OutOfRange { index } => {
+                write!(f, "coordinate {} lies outside the declared range", index)
+            }
+            RangeProofError::NormExceeded => write!(f, "the input exceeds the declared norm bound"),
+            RangeProofError::WrongLength => write!(f, "the range proof does not cover the payload"),
+            RangeProofError::InvalidProof => write!(f, "the range proof does not verify"),
+            RangeProofError::PackedInput => {
+                write!(f, "range and norm proofs need 32-bit inputs, which are not packed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RangeProofError {}
+
+impl From<RangeProofError> for io::Error {
+    fn from(e: RangeProofError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // test that in-range inputs prove and verify, and that nothing else does
+    fn test_range_proof() {
+        let values = [0, 1, 200, 255, 17];
+        let masked = [5u128 << 100, 6, 7, 8, 9];
+        let (proof, blindings) = InputRangeProof::prove(&values, 8, 3, 4, &masked).unwrap();
+        assert_eq!((proof.commitments.len(), blindings.len()), (5, 5));
+        let points = proof.verify(8, 5, 3, 4, &masked).unwrap();
+        let pedersen = PedersenGens::default();
+        assert_eq!(points[2], pedersen.commit(Scalar::from(200u32), blindings[2]));
+
+        // the proof is bound to its round, masked input, width and payload, and survives the wire
+        assert_eq!(proof.verify(8, 5, 3, 5, &masked).unwrap_err(), RangeProofError::InvalidProof);
+        let other = [5 << 100, 6, 7, 8, 10];
+        assert_eq!(proof.verify(8, 5, 3, 4, &other).unwrap_err(), RangeProofError::InvalidProof);
+        assert_eq!(proof.verify(16, 5, 3, 4, &masked).unwrap_err(), RangeProofError::InvalidProof);
+        assert_eq!(proof.verify(8, 4, 3, 4, &masked).unwrap_err(), RangeProofError::WrongLength);
+        let bytes = proof.to_bytes();
+        let parsed = InputRangeProof::read_from(&mut &bytes[..], 5).unwrap();
+        assert!(parsed.verify(8, 5, 3, 4, &masked).is_ok());
+        let mut swapped = parsed.clone();
+        swapped.commitments.swap(0, 2);
+        assert!(swapped.verify(8, 5, 3, 4, &masked).is_err());
+
+        let out_of_range = InputRangeProof::prove(&[1, 256], 8, 3, 4, &masked).unwrap_err();
+        assert_eq!(out_of_range, RangeProofError::OutOfRange { index: 1 });
+        let unsupported = InputRangeProof::prove(&[1], 12, 3, 4, &masked).unwrap_err();
+        assert_eq!(unsupported, RangeProofError::UnsupportedWidth { bits: 12 });
+    }
+
+    #[test]
+    // test that the summed commitments open to the aggregate under the summed blindings only
+    fn test_check_aggregate() {
+        let denominator = 1u128 << 60;
+        let (a, gamma_a) = InputRangeProof::prove(&[3, 9], 8, 0, 0, &[]).unwrap();
+        let (b, gamma_b) = InputRangeProof::prove(&[4, 1], 8, 0, 0, &[]).unwrap();
+        let committed: Vec<RistrettoPoint> = a
+            .verify(8, 2, 0, 0, &[])
+            .unwrap()
+            .iter()
+            .zip(b.verify(8, 2, 0, 0, &[]).unwrap())
+            .map(|(x, y)| x + y)
+            .collect();
+        // the blinding sums as the committee reconstructs them in the Shamir field
+        let blinding_sums: Vec<Scalar> = gamma_a
+            .iter()
+            .zip(&gamma_b)
+            .map(|(x, y)| field_to_blinding(blinding_to_field(x) + blinding_to_field(y)))
+            .collect();
+        // an encoded sum decodes to ceil(x / denominator) - 1
+        let encode = |y: u128| y * denominator + 12345;
+
+        let check = |aggregate: &[u128], blinding_sums: &[Scalar]| {
+            check_aggregate(&committed, aggregate, denominator, blinding_sums, 8, 2)
+        };
+        assert!(check(&[encode(7), encode(10)], &blinding_sums));
+        assert!(!check(&[encode(7), encode(11)], &blinding_sums));
+        assert!(!check(&[encode(7), encode(10)], &[blinding_sums[0], gamma_a[1]]));
+        assert!(!check(&[0, encode(10)], &blinding_sums));
+        // unmasking errors above the word are ignored, like the decoder does
+        assert!(check(&[encode(7) + (denominator << 32), encode(10)], &blinding_sums));
+
+        // 32-bit words can carry out of the aggregate word, up to once per client beyond the first
+        let (c, gamma_c) = InputRangeProof::prove(&[u32::MAX, 9], 32, 0, 0, &[]).unwrap();
+        let (d, gamma_d) = InputRangeProof::prove(&[4, u32::MAX], 32, 0, 0, &[]).unwrap();
+        let committed: Vec<RistrettoPoint> = c
+            .verify(32, 2, 0, 0, &[])
+            .unwrap()
+            .iter()
+            .zip(d.verify(32, 2, 0, 0, &[]).unwrap())
+            .map(|(x, y)| x + y)
+            .collect();
+        let blinding_sums: Vec<Scalar> = gamma_c.iter().zip(&gamma_d).map(|(x, y)| x + y).collect();
+        assert!(check_aggregate(&committed, &[encode(3), encode(8)], denominator, &blinding_sums, 32, 2));
+        assert!(!check_aggregate(&committed, &[encode(3), encode(9)], denominator, &blinding_sums, 32, 2));
+        assert!(!check_aggregate(&committed, &[encode(3), encode(8)], denominator, &blinding_sums, 32, 1));
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt;
+use std::io;
+use std::sync::{Condvar, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+/// Identifies one aggregation round of a long-running server.
+pub type RoundId = u64;
+
+/// Phases of one OPA round, in the only order the server may move through them.
+///
+/// `Setup -> CollectingInputs -> InputsClosed -> CollectingCommitteeOutputs -> Done`,
+/// with `Aborted` reachable from every non-terminal phase after setup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RoundPhase {
+    Setup,
+    CollectingInputs,
+    InputsClosed,
+    CollectingCommitteeOutputs,
+    Done,
+    Aborted,
+}
+
+impl RoundPhase {
+    pub fn is_terminal(self) -> bool {
+        matches!(self, RoundPhase::Done | RoundPhase::Aborted)
+    }
+
+    /// Whether the state machine allows moving from `self` to `next`.
+    pub fn can_transition_to(self, next: RoundPhase) -> bool {
+        use RoundPhase::*;
+        matches!(
+            (self, next),
+            (Setup, CollectingInputs)
+                | (CollectingInputs, InputsClosed)
+                | (InputsClosed, CollectingCommitteeOutputs)
+                | (CollectingCommitteeOutputs, Done)
+                | (CollectingInputs | InputsClosed | CollectingCommitteeOutputs, Aborted)
+        )
+    }
+}
+
+impl fmt::Display for RoundPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RoundPhase::Setup => "setup",
+            RoundPhase::CollectingInputs => "collecting inputs",
+            RoundPhase::InputsClosed => "inputs closed",
+            RoundPhase::CollectingCommitteeOutputs => "collecting committee outputs",
+            RoundPhase::Done => "done",
+            RoundPhase::Aborted => "aborted",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Why a round refused a message or could not produce its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundError {
+    /// A message or operation arrived while the round was in a phase that does not accept it.
+    WrongPhase { operation: &'static str, phase: RoundPhase },
+    /// The caller asked for a transition the state machine does not allow.
+    IllegalTransition { from: RoundPhase, to: RoundPhase },
+    /// Fewer clients submitted than the round requires before revealing an aggregate.
+    TooFewParticipants { accepted: usize, required: usize },
+    /// Fewer usable committee outputs arrived than needed to reconstruct the seed.
+    TooFewCommitteeOutputs { received: usize, required: usize },
+    /// A committee output does not agree with the others.
+    InconsistentCommitteeOutput { member: usize },
+    /// The running sum covers `summed` clients, not the `clients` of the closed client set.
+    SumDoesNotCoverClientSet { summed: usize, clients: usize },
+    /// An accepted client sent no commitment to its input, so the aggregate cannot be proven.
+    MissingInputCommitment { client: u64 },
+    /// The aggregate does not open the clients' range-proven commitments, so some client
+    /// masked another input than it proved in range.
+    RangeCheckFailed,
+    /// The clients' seed commitments do not add up to the seed sum the committee
+    /// reconstructed, so some client committed to another seed than it shared.
+    SeedCommitmentsDoNotAddUp,
+    /// Unmasking left `slot` outside every value the clients' encodings can add up to, so
+    /// the seed the committee's outputs reconstruct is not the sum of the clients' seeds.
+    UnmaskingFailed { slot: usize },
+    /// Timed out waiting for the round to reach a phase.
+    Timeout { phase: RoundPhase },
+    /// A message was produced for a different round than the one in progress.
+    WrongRound { expected: RoundId, received: RoundId },
+    /// Round ids must increase; `requested` does not come after `current`.
+    StaleRound { current: RoundId, requested: RoundId },
+}
+
+impl fmt::Display for RoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundError::WrongPhase { operation, phase } => {
+                write!(f, "cannot {} while the round is {}", operation, phase)
+            }
+            RoundError::IllegalTransition { from, to } => {
+                write!(f, "illegal round transition from {} to {}", from, to)
+            }
+            RoundError::TooFewParticipants { accepted, required } => {
+                write!(f, "only {} of {} required clients submitted", accepted, required)
+            }
+            RoundError::TooFewCommitteeOutputs { received, required } => {
+                write!(f, "only {} of {} required committee outputs are usable", received, required)
+            }
+            RoundError::InconsistentCommitteeOutput { member } => {
+                write!(f, "committee member {} sent an inconsistent output", member)
+            }
+            RoundError::SumDoesNotCoverClientSet { summed, clients } => {
+                write!(f, "the running sum covers {} clients but the client set has {}", summed, clients)
+            }
+            RoundError::MissingInputCommitment { client } => {
+                write!(f, "client {} sent no input commitment", client)
+            }
+            RoundError::RangeCheckFailed => {
+                write!(f, "the aggregate does not match the inputs the clients proved in range")
+            }
+            RoundError::SeedCommitmentsDoNotAddUp => {
+                write!(f, "the clients' seed commitments do not add up to the reconstructed seed sum")
+            }
+            RoundError::UnmaskingFailed { slot } => {
+                write!(
+                    f,
+                    "unmasking left slot {} outside the encoding; the committee reconstructed a wrong seed",
+                    slot
+                )
+            }
+            RoundError::Timeout { phase } => write!(f, "timed out in phase {}", phase),
+            RoundError::WrongRound { expected, received } => {
+                write!(f, "message is for round {} but round {} is in progress", received, expected)
+            }
+            RoundError::StaleRound { current, requested } => {
+                write!(f, "cannot start round {} after round {}", requested, current)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RoundError {}
+
+impl From<RoundError> for io::Error {
+    fn from(e: RoundError) -> Self {
+        let kind = match e {
+            RoundError::WrongPhase { .. }
+            | RoundError::IllegalTransition { .. }
+            | RoundError::WrongRound { .. }
+            | RoundError::StaleRound { .. } => io::ErrorKind::PermissionDenied,
+            RoundError::Timeout { .. } => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+/// The round currently run by a server: its id, its phase, and once it has finished, its
+/// outcome. Shared between the server's callbacks and reused from one round to the next.
+/// Transitions are checked against `RoundPhase::can_transition_to` and every change is
+/// pushed to subscribers, so callers can wait on the round instead of sleeping.
+#[derive(Debug)]
+pub struct Round {
+    status: Mutex<RoundStatus>,
+    changed: Condvar,
+    subscribers: Mutex<Vec<mpsc::Sender<RoundPhase>>>,
+}
+
+#[derive(Debug)]
+struct RoundStatus {
+    id: RoundId,
+    phase: RoundPhase,
+    /// Set when the round reaches `Done` (the output) or `Aborted` (the reason).
+    outcome: Option<Result<Vec<u32>, RoundError>>,
+    /// Number of clients in the client set, once inputs are closed.
+    contributors: Option<usize>,
+}
+
+impl Default for Round {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Round {
+    pub fn new() -> Self {
+        Self {
+            status: Mutex::new(RoundStatus {
+                id: 0,
+                phase: RoundPhase::Setup,
+                outcome: None,
+                contributors: None,
+            }),
+            changed: Condvar::new(),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn id(&self) -> RoundId {
+        self.status.lock().unwrap().id
+    }
+
+    pub fn phase(&self) -> RoundPhase {
+        self.status.lock().unwrap().phase
+    }
+
+    /// Fail with `WrongPhase` unless the round is currently in `phase`.
+    pub fn expect(&self, phase: RoundPhase, operation: &'static str) -> Result<(), RoundError> {
+        let current = self.phase();
+        if current != phase {
+            return Err(RoundError::WrongPhase { operation, phase: current });
+        }
+        Ok(())
+    }
+
+    /// Fail with `WrongRound` unless `round_id` is the round in progress.
+    pub fn expect_id(&self, round_id: RoundId) -> Result<(), RoundError> {
+        let current = self.id();
+        if current != round_id {
+            return Err(RoundError::WrongRound { expected: current, received: round_id });
+        }
+        Ok(())
+    }
+
+    /// Start collecting inputs for round `round_id`. The first round may start from setup;
+    /// later ones only once the previous round has finished, and with a larger id.
+    pub fn begin(&self, round_id: RoundId) -> Result<(), RoundError> {
+        let mut status = self.status.lock().unwrap();
+        match status.phase {
+            RoundPhase::Setup if round_id >= status.id => {}
+            RoundPhase::Done | RoundPhase::Aborted if round_id > status.id => {}
+            RoundPhase::Setup | RoundPhase::Done | RoundPhase::Aborted => {
+                return Err(RoundError::StaleRound { current: status.id, requested: round_id });
+            }
+            phase => return Err(RoundError::WrongPhase { operation: "start a new round", phase }),
+        }
+        println!("Starting round {}", round_id);
+        status.id = round_id;
+        status.outcome = None;
+        status.contributors = None;
+        // a new round re-enters the machine at setup before collecting inputs
+        status.phase = RoundPhase::Setup;
+        self.transition_locked(&mut status, RoundPhase::CollectingInputs)
+    }
+
+    /// Move to `next`, notifying subscribers and waiters.
+    pub fn transition(&self, next: RoundPhase) -> Result<(), RoundError> {
+        let mut status = self.status.lock().unwrap();
+        self.transition_locked(&mut status, next)
+    }
+
+    /// Move to `next` unless the round is already there; several parties may race to
+    /// trigger the same transition and all of them should succeed. Returns whether this
+    /// call made the transition.
+    pub fn advance_to(&self, next: RoundPhase) -> Result<bool, RoundError> {
+        let mut status = self.status.lock().unwrap();
+        if status.phase == next {
+            return Ok(false);
+        }
+        self.transition_locked(&mut status, next).map(|()| true)
+    }
+
+    fn transition_locked(&self, status: &mut RoundStatus, next: RoundPhase) -> Result<(), RoundError> {
+        if !status.phase.can_transition_to(next) {
+            return Err(RoundError::IllegalTransition { from: status.phase, to: next });
+        }
+        println!("Round {} phase: {} -> {}", status.id, status.phase, next);
+        status.phase = next;
+        self.changed.notify_all();
+        // drop subscribers that have gone away
+        self.subscribers.lock().unwrap().retain(|subscriber| subscriber.send(next).is_ok());
+        Ok(())
+    }
+
+    /// Finish round `round_id` successfully with its decoded output.
+    pub fn complete(&self, round_id: RoundId, output: Vec<u32>) -> Result<(), RoundError> {
+        let mut status = self.status.lock().unwrap();
+        if status.id != round_id {
+            return Err(RoundError::WrongRound { expected: status.id, received: round_id });
+        }
+        self.transition_locked(&mut status, RoundPhase::Done)?;
+        status.outcome = Some(Ok(output));
+        Ok(())
+    }
+
+    /// Abort round `round_id` unless it has already finished or been superseded.
+    /// Returns the phase the round is in afterwards.
+    pub fn abort(&self, round_id: RoundId, reason: RoundError) -> RoundPhase {
+        let mut status = self.status.lock().unwrap();
+        if status.id == round_id && self.transition_locked(&mut status, RoundPhase::Aborted).is_ok() {
+            status.outcome = Some(Err(reason));
+        }
+        status.phase
+    }
+
+    /// Record how many clients the closed client set of the current round holds.
+    pub fn set_contributors(&self, contributors: usize) {
+        self.status.lock().unwrap().contributors = Some(contributors);
+    }
+
+    /// Number of clients the current round aggregates, once its inputs are closed.
+    pub fn contributors(&self) -> Option<usize> {
+        self.status.lock().unwrap().contributors
+    }
+
+    /// Receive every phase the round enters from now on.
+    pub fn subscribe(&self) -> mpsc::Receiver<RoundPhase> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Block until the round reaches `target`, a later phase, or a terminal phase,
+    /// returning the phase reached.
+    pub fn wait_for(&self, target: RoundPhase, timeout: Duration) -> Result<RoundPhase, RoundError> {
+        let deadline = Instant::now() + timeout;
+        let mut status = self.status.lock().unwrap();
+        while status.phase < target && !status.phase.is_terminal() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RoundError::Timeout { phase: status.phase });
+            }
+            status = self.changed.wait_timeout(status, remaining).unwrap().0;
+        }
+        Ok(status.phase)
+    }
+
+    /// Block until round `round_id` has finished and return its output or why it was aborted.
+    pub fn outcome(&self, round_id: RoundId, timeout: Duration) -> Result<Vec<u32>, RoundError> {
+        let deadline = Instant::now() + timeout;
+        let mut status = self.status.lock().unwrap();
+        loop {
+            if status.id > round_id {
+                return Err(RoundError::StaleRound { current: status.id, requested: round_id });
+            }
+            if status.id == round_id && let Some(ref outcome) = status.outcome {
+                return outcome.clone();
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RoundError::Timeout { phase: status.phase });
+            }
+            status = self.changed.wait_timeout(status, remaining).unwrap().0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // test that only the documented transitions are allowed and that observers see each one
+    fn test_round_transitions() {
+        let round = Round::new();
+        let phases = round.subscribe();
+
+        let err = round.transition(RoundPhase::Done).unwrap_err();
+        assert_eq!(err, RoundError::IllegalTransition { from: RoundPhase::Setup, to: RoundPhase::Done });
+        assert!(round.expect(RoundPhase::CollectingInputs, "accept client input").is_err());
+
+        round.transition(RoundPhase::CollectingInputs).unwrap();
+        round.transition(RoundPhase::InputsClosed).unwrap();
+        assert!(!round.advance_to(RoundPhase::InputsClosed).unwrap());
+        assert!(round.transition(RoundPhase::CollectingInputs).is_err());
+        let reason = RoundError::TooFewParticipants { accepted: 1, required: 2 };
+        assert_eq!(round.abort(0, reason.clone()), RoundPhase::Aborted);
+        assert_eq!(round.abort(0, RoundError::Timeout { phase: RoundPhase::Aborted }), RoundPhase::Aborted);
+        assert_eq!(round.outcome(0, Duration::ZERO), Err(reason));
+
+        let seen: Vec<RoundPhase> = phases.try_iter().collect();
+        assert_eq!(seen, vec![RoundPhase::CollectingInputs, RoundPhase::InputsClosed, RoundPhase::Aborted]);
+        assert_eq!(round.wait_for(RoundPhase::Done, Duration::ZERO), Ok(RoundPhase::Aborted));
+    }
+
+    #[test]
+    // test that rounds restart with increasing ids and keep their own outcome
+    fn test_round_ids() {
+        let round = Round::new();
+        round.begin(3).unwrap();
+        assert_eq!(
+            round.begin(4),
+            Err(RoundError::WrongPhase { operation: "start a new round", phase: RoundPhase::CollectingInputs })
+        );
+
+        round.transition(RoundPhase::InputsClosed).unwrap();
+        round.transition(RoundPhase::CollectingCommitteeOutputs).unwrap();
+        assert!(round.complete(2, vec![1]).is_err());
+        round.complete(3, vec![1, 2]).unwrap();
+        assert_eq!(round.outcome(3, Duration::ZERO), Ok(vec![1, 2]));
+
+        assert_eq!(round.begin(3), Err(RoundError::StaleRound { current: 3, requested: 3 }));
+        round.begin(4).unwrap();
+        assert_eq!(round.phase(), RoundPhase::CollectingInputs);
+        assert_eq!(round.expect_id(3), Err(RoundError::WrongRound { expected: 4, received: 3 }));
+        assert_eq!(round.outcome(3, Duration::ZERO), Err(RoundError::StaleRound { current: 4, requested: 3 }));
+        assert_eq!(
+            round.abort(3, RoundError::Timeout { phase: RoundPhase::CollectingInputs }),
+            RoundPhase::CollectingInputs
+        );
+    }
+}
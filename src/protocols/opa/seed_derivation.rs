@@ -0,0 +1,100 @@
+use std::fmt;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const DESCRIPTOR_DOMAIN: &[u8] = b"jagguar/opa/public-seed/descriptor/v1";
+
+/// How the succinct seed of the public LWR matrix was derived, so clients can check that
+/// the server did not pick it. Without one, the server samples the seed and could grind
+/// or backdoor the matrix.
+///
+/// Only descriptors are supported: a coin-toss transcript relayed by the server would not
+/// show who contributed to it, so the server could play every contributor itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedDerivation {
+    /// "Nothing up my sleeve": the hash of a public description of the session, such as
+    /// its name and date, fixed before the server is set up. A server free to choose the
+    /// descriptor can still grind it, so it must be agreed on in advance.
+    Descriptor(String),
+}
+
+impl SeedDerivation {
+    /// The succinct seed this derivation yields.
+    pub fn seed(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        match self {
+            SeedDerivation::Descriptor(descriptor) => {
+                hasher.update(DESCRIPTOR_DOMAIN);
+                hasher.update(descriptor.as_bytes());
+            }
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// Why clients refuse a public seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeedDerivationError {
+    /// The public seed differs from the one its derivation yields.
+    NotDerived,
+}
+
+impl fmt::Display for SeedDerivationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedDerivationError::NotDerived => {
+                write!(f, "the public seed does not match its published derivation")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SeedDerivationError {}
+
+impl From<SeedDerivationError> for io::Error {
+    fn from(e: SeedDerivationError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::client::Client;
+    use crate::protocols::server::Server;
+    use crate::protocols::opa::{OPAClient, OPAPublicParams, OPAServer, OPASetupParameters};
+
+    #[test]
+    // test that the server publishes a derived seed and clients refuse one it does not derive
+    fn test_server_public_seed() {
+        let derivation = SeedDerivation::Descriptor("example deployment, 2026-10-18".to_string());
+        let mut server = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3));
+        server.derive_public_seed(derivation.clone()).unwrap();
+        let params = server.get_state().public_params();
+        assert_eq!(params.succinct_seed, derivation.seed());
+        assert_eq!(OPAPublicParams::from_json(&params.to_json()).unwrap(), params);
+        assert_eq!(OPAPublicParams::from_bytes(&params.to_bytes()).unwrap(), params);
+
+        // setting the server up again keeps the derived seed
+        server.setup(OPASetupParameters::new(40, 1, 2, 4));
+        assert_eq!(server.get_state().succinct_seed, params.succinct_seed);
+
+        let ground = OPAPublicParams { succinct_seed: [9; 32], ..params };
+        let error = OPAPublicParams::from_bytes(&ground.to_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let mut client = OPAClient::<u32>::new();
+        client.set_server_state(ground.into());
+        client.set_input(vec![1, 2, 3]);
+        client.encrypt_input();
+        let refused = client.submit(18650).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            *refused.into_inner().unwrap().downcast::<SeedDerivationError>().unwrap(),
+            SeedDerivationError::NotDerived
+        );
+    }
+}
@@ -1,25 +1,66 @@
-use std::io::Read;
-use std::net::TcpStream;
-use std::sync::mpsc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
 
 use crate::protocols::server::Server;
 use crate::crypto::{
-    F256, FieldBytes, FIELD_ELEMENT_BYTES, SeedHomomorphicPRG, Shamir, field_from_bytes,
-    field_low_u128,
+    EncryptionKey, F256, FieldBytes, FIELD_ELEMENT_BYTES, KeyHomomorphicPRF, SEALED_OVERHEAD, SeedHomomorphicPRG,
+    Shamir, field_carry_u32, field_from_bytes, field_low_u128, sealed_box,
 };
 use crate::crypto::prg::populate_random_bytes;
+use crate::crypto::seed_homomorphic_prg::DEFAULT_INNER_MODULUS_BITS;
 use crate::util::packing::unpack_vector;
-use crate::communicator::Communicator;
+use crate::communicator::{
+    AddressBook, BufferBudget, CommitteeCompletion, Communicator, DEFAULT_SESSION, Session, SessionId,
+};
+use crate::protocols::opa::admission::{self, CREDENTIALS_LEN, ClientRegistry, Credentials, IdentityKey};
 use crate::protocols::opa::client::{NUM_PARTIES_UPPER_BOUND, OUTPUT_LEN};
+use crate::protocols::opa::client_set::{ClientEntry, ClientId, ClientSet, Commitment, submission_digest};
+use crate::protocols::opa::checkpoint::{RoundCheckpoint, ServerCheckpoint};
+use crate::protocols::opa::committee_selection::{self, Beacon, CommitteeSelection, SelectionError};
+use crate::protocols::opa::seed_derivation::{SeedDerivation, SeedDerivationError};
+use crate::protocols::opa::planner::{self, MAX_CLIENTS, MAX_SECURITY_PARAMETER, ParameterError};
+use crate::protocols::opa::norm_proof::{NormBound, NormProof};
+use crate::protocols::opa::range_proof::{self, InputRangeProof, RANGE_BITS};
+use crate::protocols::opa::round::{Round, RoundError, RoundId, RoundPhase};
+use crate::protocols::opa::verification::{
+    AggregateProof, ClientCommitments, CommittedClient, commit_masked_input,
+};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 
+/// How long the server keeps waiting for the remaining committee members once
+/// `reconstruction_threshold` outputs have arrived.
+pub const DEFAULT_COMMITTEE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+/// How long the server waits for reconstruction_threshold committee outputs before aborting.
+pub const DEFAULT_COMMITTEE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Fewest clients a round may aggregate; a single client's "aggregate" is its own input.
+pub const DEFAULT_MIN_PARTICIPANTS: u64 = 2;
+const SHARE_CONTEXT_DOMAIN: &[u8] = b"jagguar/opa/seed-share/v1";
+
 #[derive(Copy, Clone)]
 pub struct OPASetupParameters {
     security_parameter: u64,
     corruption_threshold: u64,
     reconstruction_threshold: u64,
     committee_size: u64,
+    committee_grace_period: Duration,
+    committee_timeout: Duration,
+    input_deadline: Option<Duration>,
+    min_participants: u64,
+    reuse_public_seed: bool,
+    key_homomorphic_masks: bool,
+    admission_control: bool,
+    verifiable_aggregation: bool,
+    range_proof_bits: Option<u32>,
+    norm_bound: Option<NormBound>,
+    session_id: SessionId,
+    first_committee_port_offset: u16,
 }
 
 impl OPASetupParameters {
@@ -34,33 +75,647 @@ impl OPASetupParameters {
             corruption_threshold,
             reconstruction_threshold,
             committee_size,
+            committee_grace_period: DEFAULT_COMMITTEE_GRACE_PERIOD,
+            committee_timeout: DEFAULT_COMMITTEE_TIMEOUT,
+            input_deadline: None,
+            min_participants: DEFAULT_MIN_PARTICIPANTS,
+            reuse_public_seed: false,
+            key_homomorphic_masks: false,
+            admission_control: false,
+            verifiable_aggregation: false,
+            range_proof_bits: None,
+            norm_bound: None,
+            session_id: DEFAULT_SESSION,
+            first_committee_port_offset: 1,
+        }
+    }
+
+    /// Override how long to wait for straggling committee members after the threshold is met.
+    pub fn with_committee_grace_period(mut self, grace_period: Duration) -> Self {
+        self.committee_grace_period = grace_period;
+        self
+    }
+
+    /// Abort the round if the committee has not produced enough outputs this long after
+    /// the first member fetched its shares.
+    pub fn with_committee_timeout(mut self, timeout: Duration) -> Self {
+        self.committee_timeout = timeout;
+        self
+    }
+
+    /// Close input collection automatically this long after the server starts listening.
+    /// Without a deadline, inputs close when the first committee member fetches its shares.
+    pub fn with_input_deadline(mut self, deadline: Duration) -> Self {
+        self.input_deadline = Some(deadline);
+        self
+    }
+
+    /// Abort the round instead of revealing an aggregate of fewer than `min_participants` clients.
+    pub fn with_min_participants(mut self, min_participants: u64) -> Self {
+        self.min_participants = min_participants;
+        self
+    }
+
+    /// Use the same SHPRG public seed in every round instead of deriving one per round id.
+    /// The committee is the same in every round either way (see `committee_port_offsets`
+    /// and `CommitteeSelection`).
+    pub fn with_public_seed_reuse(mut self, reuse: bool) -> Self {
+        self.reuse_public_seed = reuse;
+        self
+    }
+
+    /// Mask with a key-homomorphic PRF of the round id: each client splits one long-term
+    /// key among the committee in its first round and afterwards uploads only its masked
+    /// input. Members report the PRF under their key shares, so the server only learns each
+    /// round's mask, but every member has to report. Requires a fresh public seed per round.
+    pub fn with_key_homomorphic_masks(mut self, enabled: bool) -> Self {
+        self.key_homomorphic_masks = enabled;
+        self
+    }
+
+    /// Only accept submissions from clients that enrolled an identity key, carry a token
+    /// admitting them to the round and are signed under that key; each identity then
+    /// contributes at most once per round, whatever port it connects from.
+    pub fn with_admission_control(mut self, enabled: bool) -> Self {
+        self.admission_control = enabled;
+        self
+    }
+
+    /// Have clients commit to their masked inputs, and publish with each aggregate an
+    /// `AggregateProof` that anyone can check the output against.
+    pub fn with_verifiable_aggregation(mut self, enabled: bool) -> Self {
+        self.verifiable_aggregation = enabled;
+        self
+    }
+
+    /// Have clients prove that every coordinate of their input lies in `[0, 2^bits)`, and
+    /// only accept inputs whose proof verifies; see `InputRangeProof`. Clients with inputs
+    /// narrower than 32 bits cannot prove, since packing puts several coordinates in a word.
+    pub fn with_range_proofs(mut self, bits: u32) -> Self {
+        self.range_proof_bits = Some(bits);
+        self
+    }
+
+    /// Have clients also prove the norm of their input within `bound`, and only accept inputs
+    /// whose proof verifies; needs range proofs, and so 32-bit inputs, see `NormProof`.
+    pub fn with_norm_bound(mut self, bound: NormBound) -> Self {
+        self.norm_bound = Some(bound);
+        self
+    }
+
+    /// Serve the protocol as session `session_id` of the listener.
+    pub fn with_session_id(mut self, session_id: SessionId) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    /// Place the committee at consecutive port offsets from the server starting at `first`,
+    /// so committees of sessions sharing a listener do not collide.
+    pub fn with_committee_port_offsets_from(mut self, first: u16) -> Self {
+        self.first_committee_port_offset = first;
+        self
+    }
+
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    pub fn committee_size(&self) -> u64 {
+        self.committee_size
+    }
+
+    pub fn admission_control(&self) -> bool {
+        self.admission_control
+    }
+
+    pub fn verifiable_aggregation(&self) -> bool {
+        self.verifiable_aggregation
+    }
+
+    pub fn range_proof_bits(&self) -> Option<u32> {
+        self.range_proof_bits
+    }
+
+    pub fn norm_bound(&self) -> Option<NormBound> {
+        self.norm_bound
+    }
+
+    /// Check that the parameters are consistent: the tolerated corrupt members cannot
+    /// reconstruct a seed, the committee can, and kappa leaves room for the aggregate.
+    /// Setting a server up with parameters that fail the check panics, so parameters read
+    /// from a config or planned elsewhere are checked here first.
+    pub fn validate(&self) -> Result<(), ParameterError> {
+        let (corruption_threshold, reconstruction_threshold, committee_size) =
+            (self.corruption_threshold, self.reconstruction_threshold, self.committee_size);
+        if reconstruction_threshold < 2 {
+            return Err(ParameterError::ReconstructionThresholdTooSmall { reconstruction_threshold });
+        }
+        if reconstruction_threshold > committee_size {
+            return Err(ParameterError::ReconstructionThresholdExceedsCommittee {
+                reconstruction_threshold,
+                committee_size,
+            });
+        }
+        if corruption_threshold >= reconstruction_threshold {
+            return Err(ParameterError::CorruptionThresholdTooLarge {
+                corruption_threshold,
+                reconstruction_threshold,
+            });
+        }
+        let max_committee_size = (u16::MAX - self.first_committee_port_offset) as u64;
+        if committee_size > max_committee_size {
+            return Err(ParameterError::CommitteeTooLarge { committee_size, max: max_committee_size });
         }
+        if self.security_parameter > MAX_SECURITY_PARAMETER {
+            return Err(ParameterError::SecurityParameterTooLarge {
+                security_parameter: self.security_parameter,
+                max: MAX_SECURITY_PARAMETER,
+            });
+        }
+        if self.min_participants > MAX_CLIENTS {
+            return Err(ParameterError::TooManyClients { num_clients: self.min_participants, max: MAX_CLIENTS });
+        }
+        if let Some(bits) = self.range_proof_bits {
+            if !RANGE_BITS.contains(&bits) {
+                return Err(ParameterError::InvalidRangeBits { bits });
+            }
+            if self.key_homomorphic_masks {
+                return Err(ParameterError::RangeProofsWithRegisteredKeys);
+            }
+        }
+        if self.verifiable_aggregation && self.key_homomorphic_masks {
+            return Err(ParameterError::VerifiableAggregationWithRegisteredKeys);
+        }
+        if self.reuse_public_seed && self.key_homomorphic_masks {
+            return Err(ParameterError::PublicSeedReuseWithRegisteredKeys);
+        }
+        if self.norm_bound.is_some() && self.range_proof_bits.is_none() {
+            return Err(ParameterError::NormBoundWithoutRangeProofs);
+        }
+        Ok(())
     }
 }
 
+/// Running Z_{2^128} sum of the masked client ciphertexts received so far.
+#[derive(Debug, Clone, Default)]
+pub struct CiphertextSum {
+    /// Number of meaningful (non-padding) slots, fixed by the first client.
+    pub payload_len: Option<usize>,
+    pub sum: Vec<u128>,
+    /// How often each slot of `sum` wrapped around 2^128.
+    pub carries: Vec<u32>,
+    /// Sum of the clients' commitments to their coordinates, under range proofs.
+    pub range_commitments: Vec<RistrettoPoint>,
+    pub num_clients: usize,
+}
+
+impl CiphertextSum {
+    /// Fold one client's ciphertext into the running sum.
+    pub fn add(&mut self, payload_len: usize, ciphertext: &[u128]) -> io::Result<()> {
+        if *self.payload_len.get_or_insert(payload_len) != payload_len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "payload length differs from other clients"));
+        }
+        if self.sum.is_empty() {
+            self.sum = vec![0u128; ciphertext.len()];
+            self.carries = vec![0u32; ciphertext.len()];
+        }
+        for ((acc, carry), &c) in self.sum.iter_mut().zip(self.carries.iter_mut()).zip(ciphertext.iter()) {
+            let wrapped;
+            (*acc, wrapped) = acc.overflowing_add(c);
+            *carry += wrapped as u32;
+        }
+        self.num_clients += 1;
+        Ok(())
+    }
+
+    /// Fold the range-proven commitments of the client added last into their sum.
+    pub fn add_range_commitments(&mut self, commitments: &[RistrettoPoint]) {
+        if self.range_commitments.is_empty() {
+            self.range_commitments = vec![RistrettoPoint::default(); commitments.len()];
+        }
+        for (acc, commitment) in self.range_commitments.iter_mut().zip(commitments) {
+            *acc += commitment;
+        }
+    }
+}
+
+/// What a client proves about its masked input besides sending it.
+#[derive(Debug, Default)]
+struct Attachments {
+    /// Commitments to the masked input and its seed, under verifiable aggregation.
+    commitments: Option<ClientCommitments>,
+    /// Commitments to the coordinates, once their range proof verified.
+    range_commitments: Option<Vec<RistrettoPoint>>,
+}
+
+/// Client inputs for the round: the running ciphertext sum, the share section retained
+/// for each accepted client, and — once input collection closes — the agreed client set.
+/// Kept under a single lock so the set, the sum, and the forwarded shares always agree.
+#[derive(Debug, Default)]
+pub struct ClientInputs {
+    pub ciphertext_sum: CiphertextSum,
+    /// Accepted clients with the share section they sent this round; empty for clients
+    /// masking under their registered key.
+    accepted: Vec<(ClientEntry, Vec<u8>)>,
+    client_set: Option<ClientSet>,
+    /// Identities that submitted this round, under admission control.
+    identities: HashSet<IdentityKey>,
+    /// Commitments of the accepted clients to their masked inputs and seeds, under
+    /// verifiable aggregation.
+    commitments: HashMap<ClientId, ClientCommitments>,
+    /// Sections of additive key shares registered by client id, kept across rounds when
+    /// masks come from a key-homomorphic PRF.
+    client_keys: Option<HashMap<u64, Vec<u8>>>,
+}
+
+impl ClientInputs {
+    /// Inputs that keep each client's key shares across rounds.
+    pub fn with_client_keys() -> Self {
+        Self { client_keys: Some(HashMap::new()), ..Self::default() }
+    }
+
+    /// Accept one fully parsed submission, unless inputs are closed or the client, or the
+    /// identity it submits under, already submitted. With client keys, a submission carrying
+    /// shares (re-)registers the client's key and one without must have a registered key.
+    /// Returns the bytes of a replaced key.
+    fn accept(
+        &mut self,
+        entry: ClientEntry,
+        identity: Option<IdentityKey>,
+        attachments: Attachments,
+        payload_len: usize,
+        ciphertext: &[u128],
+        shares: Option<Vec<u8>>,
+    ) -> io::Result<usize> {
+        if self.client_set.is_some() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "input collection is closed"));
+        }
+        if self.accepted.iter().any(|(accepted, _)| accepted.id == entry.id) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "client already submitted"));
+        }
+        if identity.is_some_and(|identity| self.identities.contains(&identity)) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "identity already submitted this round"));
+        }
+        if shares.is_none() {
+            match self.client_keys.as_ref() {
+                None => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "submission carries no seed shares"));
+                }
+                Some(keys) if !keys.contains_key(&entry.id) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "no key registered for client; resend with key shares",
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        self.ciphertext_sum.add(payload_len, ciphertext)?;
+        if let Some(commitments) = attachments.range_commitments {
+            self.ciphertext_sum.add_range_commitments(&commitments);
+        }
+        let (section, replaced) = match (self.client_keys.as_mut(), shares) {
+            (Some(keys), Some(shares)) => (Vec::new(), keys.insert(entry.id, shares).map_or(0, |old| old.len())),
+            (_, shares) => (shares.unwrap_or_default(), 0),
+        };
+        if let Some(commitments) = attachments.commitments {
+            self.commitments.insert(entry.id, commitments);
+        }
+        self.accepted.push((entry, section));
+        self.identities.extend(identity);
+        Ok(replaced)
+    }
+
+    /// Stop accepting inputs and fix the ordered client set. Idempotent: later calls
+    /// return the set fixed by the first one.
+    pub fn close(&mut self) -> ClientSet {
+        if self.client_set.is_none() {
+            // order the retained shares the same way as the set sent to the committee
+            self.accepted.sort_by_key(|(entry, _)| entry.id);
+            let entries = self.accepted.iter().map(|(entry, _)| *entry).collect();
+            self.client_set = Some(ClientSet::new(entries));
+        }
+        self.client_set.clone().unwrap()
+    }
+
+    /// The agreed client set, if input collection has been closed.
+    pub fn client_set(&self) -> Option<&ClientSet> {
+        self.client_set.as_ref()
+    }
+
+    /// Drop everything buffered for the round, returning the number of retained share
+    /// bytes so the caller can release them from the communicator's budget.
+    /// Registered client keys are kept.
+    fn clear(&mut self) -> usize {
+        let retained = self.accepted.iter().map(|(_, shares)| shares.len()).sum();
+        *self = Self { client_keys: self.client_keys.take(), ..Self::default() };
+        retained
+    }
+
+    /// Restore the client keys registered before a restart, returning their size in bytes.
+    fn restore_keys(&mut self, keys: HashMap<u64, Vec<u8>>) -> usize {
+        let Some(client_keys) = self.client_keys.as_mut() else {
+            return 0;
+        };
+        *client_keys = keys;
+        client_keys.values().map(Vec::len).sum()
+    }
+
+    /// Restore the inputs of a round checkpointed before a restart, returning the number of
+    /// retained share bytes.
+    fn restore(&mut self, round: &mut RoundCheckpoint) -> usize {
+        self.accepted = std::mem::take(&mut round.accepted);
+        self.ciphertext_sum = std::mem::take(&mut round.ciphertext_sum);
+        self.commitments = std::mem::take(&mut round.commitments);
+        if round.inputs_closed {
+            self.close();
+        }
+        self.accepted.iter().map(|(_, shares)| shares.len()).sum()
+    }
+
+    /// Journal the client accepted last, together with the key it registered, if any.
+    fn checkpoint_last(&self, checkpoint: &ServerCheckpoint, sent_shares: bool) -> io::Result<()> {
+        let Some((entry, section)) = self.accepted.last() else {
+            return Ok(());
+        };
+        if sent_shares && let Some(key) = self.client_keys.as_ref().and_then(|keys| keys.get(&entry.id)) {
+            checkpoint.record_key(entry.id, key)?;
+        }
+        if let Some(commitments) = self.commitments.get(&entry.id) {
+            checkpoint.record_commitments(entry.id, commitments)?;
+        }
+        checkpoint.record_submission(entry, section, &self.ciphertext_sum)
+    }
+
+    /// One committee member's share from each accepted client, in client-set order, with
+    /// `overhead` bytes added to each share by sealing.
+    fn member_shares(&self, committee_index: usize, overhead: usize) -> Vec<Vec<u8>> {
+        self.accepted
+            .iter()
+            .filter_map(|(entry, section)| {
+                let section = match self.client_keys.as_ref() {
+                    Some(keys) if section.is_empty() => keys.get(&entry.id)?,
+                    _ => section,
+                };
+                OPAServer::member_share(section, committee_index, overhead)
+            })
+            .collect()
+    }
+}
+
+/// A committee member's aggregated share, bound to the client set it was computed over.
+#[derive(Debug, Clone)]
+pub struct CommitteeOutput {
+    pub index: usize,
+    pub round_id: RoundId,
+    pub commitment: Commitment,
+    pub share: Vec<FieldBytes>,
+}
+
 #[derive(Debug, Clone)]
 pub struct OPAState {
+    /// Session of the server's listener this protocol instance is served as.
+    pub session_id: SessionId,
+    /// Round this view of the state was handed out for.
+    pub round_id: RoundId,
     pub succinct_seed: [u8; 32],
+    /// Whether `succinct_seed` is used as is in every round or only as the base for per-round seeds.
+    pub reuse_public_seed: bool,
+    /// How `succinct_seed` was derived, if the server did not sample it.
+    pub seed_derivation: Option<SeedDerivation>,
+    /// Whether clients mask under a long-term key registered with the committee once.
+    pub key_homomorphic_masks: bool,
+    /// Whether submissions must be signed by an enrolled identity admitted to the round.
+    pub admission_control: bool,
+    /// Whether clients commit to their masked inputs and aggregates come with a proof.
+    pub verifiable_aggregation: bool,
+    /// Width of the range clients prove their coordinates in, if they must.
+    pub range_proof_bits: Option<u32>,
+    /// Bound clients prove the norm of their input vector within, if they must.
+    pub norm_bound: Option<NormBound>,
     pub security_parameter: u64,
     pub corruption_threshold: u64,
     pub reconstruction_threshold: u64,
     pub committee_size: u64,
+    /// Offsets from `port` of the ports committee members connect from, in committee index
+    /// order; they wrap around, so members may sit below the server's port.
     pub committee_port_offsets: Vec<u16>,
     pub port: u16,
+    /// Time after server start at which late submissions start being rejected.
+    pub input_deadline: Option<Duration>,
+    /// Time after the committee phase starts at which the round is aborted if still unfinished.
+    pub committee_timeout: Duration,
+    /// Fewer accepted clients than this aborts the round.
+    pub min_participants: u64,
+    /// Public draw of the committee from the clients, if it was not given by port offsets.
+    pub committee_selection: Option<CommitteeSelection>,
+    /// Keys the committee members publish by the port they connect from. If any are set,
+    /// clients seal each member's share to its key and the server only sees ciphertexts.
+    pub committee_keys: BTreeMap<u16, EncryptionKey>,
     /// Optional channel for sending decoded output back to the simulator.
     pub output_sender: Option<mpsc::Sender<Vec<u32>>>,
-    /// Optional cache of messages used during aggregation.
-    pub client_messages: Vec<Vec<u8>>,
-    pub committee_messages: Vec<Vec<u8>>,
+    /// Client inputs folded in as they arrive; shared with the receive callbacks.
+    pub client_inputs: Arc<Mutex<ClientInputs>>,
+    /// Phase of the round; callers can subscribe to or wait on its transitions.
+    pub round: Arc<Round>,
+    /// The server communicator's budget, charged for the client shares retained per round.
+    pub buffer_budget: Option<BufferBudget>,
+    /// Durable record of the round, if the server checkpoints to a directory.
+    pub checkpoint: Option<Arc<ServerCheckpoint>>,
+    /// Enrolled client identities, if the server runs admission control.
+    pub client_registry: Option<Arc<ClientRegistry>>,
+    /// Keys of the parties, if every message must be signed by the party at its port.
+    pub address_book: Option<Arc<AddressBook>>,
+    /// Parsed committee output shares, keyed by committee index.
+    pub committee_outputs: Vec<CommitteeOutput>,
+    /// Proof published with the last aggregate, under verifiable aggregation.
+    pub aggregate_proof: Arc<Mutex<Option<AggregateProof>>>,
 }
 
 pub struct OPAServer {
     setup_parameters: OPASetupParameters,
     state: OPAState,
     communicator: Option<Communicator>,
+    /// Unfinished round found in the checkpoint directory, resumed by `start`.
+    resumed: Option<RoundCheckpoint>,
 }
 
 impl OPAState {
+    /// Close input collection and return the client set every committee member will receive.
+    /// The round is aborted instead if fewer than `min_participants` clients submitted.
+    /// Closing an already closed round returns the same set.
+    pub fn close_inputs(&self) -> Result<ClientSet, RoundError> {
+        self.close_inputs_of(self.round.id())
+    }
+
+    /// Close input collection of round `round_id`, failing if another round is in progress.
+    pub fn close_inputs_of(&self, round_id: RoundId) -> Result<ClientSet, RoundError> {
+        // hold the inputs lock across the transition so no submission slips in after the set is fixed
+        let mut inputs = self.client_inputs.lock().unwrap();
+        self.round.expect_id(round_id)?;
+        match self.round.phase() {
+            RoundPhase::CollectingInputs => {
+                let client_set = inputs.close();
+                let required = self.min_participants.max(1) as usize;
+                if client_set.len() < required {
+                    let reason = RoundError::TooFewParticipants { accepted: client_set.len(), required };
+                    self.round.abort(round_id, reason.clone());
+                    self.release(inputs.clear());
+                    self.checkpoint("the aborted round", ServerCheckpoint::finish_round);
+                    return Err(reason);
+                }
+                self.round.transition(RoundPhase::InputsClosed)?;
+                self.round.set_contributors(client_set.len());
+                self.checkpoint("closing inputs", ServerCheckpoint::record_inputs_closed);
+                Ok(client_set)
+            }
+            RoundPhase::InputsClosed | RoundPhase::CollectingCommitteeOutputs => Ok(inputs.close()),
+            phase => Err(RoundError::WrongPhase { operation: "close input collection", phase }),
+        }
+    }
+
+    /// End round `round_id` with `outcome` and drop the inputs buffered for it.
+    fn finish_round(&self, round_id: RoundId, outcome: Result<Vec<u32>, RoundError>) {
+        let mut inputs = self.client_inputs.lock().unwrap();
+        match outcome {
+            Ok(output) => {
+                if let Err(e) = self.round.complete(round_id, output) {
+                    eprintln!("Could not finish round {}: {}", round_id, e);
+                    return;
+                }
+            }
+            Err(reason) => {
+                eprintln!("Aborting round {}: {}", round_id, reason);
+                if self.round.abort(round_id, reason) != RoundPhase::Aborted {
+                    return;
+                }
+            }
+        }
+        self.release(inputs.clear());
+        self.checkpoint("the finished round", ServerCheckpoint::finish_round);
+    }
+
+    /// Apply `record` to the checkpoint, if there is one. A failure is logged and the round
+    /// goes on; it only matters if the server also crashes.
+    fn checkpoint(&self, what: &str, record: impl FnOnce(&ServerCheckpoint) -> io::Result<()>) {
+        if let Some(ref checkpoint) = self.checkpoint
+            && let Err(e) = record(checkpoint)
+        {
+            eprintln!("Failed to checkpoint {}: {}", what, e);
+        }
+    }
+
+    /// Index of the committee member connecting from `port`, if it belongs to this committee.
+    pub fn committee_index(&self, port: u16) -> Option<usize> {
+        let offset = port.wrapping_sub(self.port);
+        self.committee_port_offsets.iter().position(|&o| o == offset)
+    }
+
+    /// Port the committee member with index `committee_index` connects from.
+    pub fn committee_port(&self, committee_index: usize) -> Option<u16> {
+        let offset = self.committee_port_offsets.get(committee_index)?;
+        Some(self.port.wrapping_add(*offset))
+    }
+
+    /// Whether clients seal their shares to the committee members' keys.
+    pub fn seals_shares(&self) -> bool {
+        !self.committee_keys.is_empty()
+    }
+
+    /// Bytes a share adds on the wire beyond its field elements.
+    pub fn share_overhead(&self) -> usize {
+        if self.seals_shares() { SEALED_OVERHEAD } else { 0 }
+    }
+
+    /// What a share from client `client_id` to the member on `member_port` is sealed under,
+    /// so the server cannot hand it to another member or pass it off as another client's.
+    pub fn share_context(&self, client_id: ClientId, member_port: u16) -> Vec<u8> {
+        [
+            SHARE_CONTEXT_DOMAIN,
+            &self.session_id.to_le_bytes(),
+            &client_id.to_le_bytes(),
+            &member_port.to_le_bytes(),
+        ]
+        .concat()
+    }
+
+    /// Seal client `client_id`'s share for committee member `committee_index` to that
+    /// member's key.
+    pub fn seal_share(&self, client_id: ClientId, committee_index: usize, share: &[u8]) -> io::Result<Vec<u8>> {
+        let member_port = self.committee_port(committee_index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no such committee member"))?;
+        let key = self.committee_keys.get(&member_port).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("committee member on port {} has no key to seal its share to", member_port),
+            )
+        })?;
+        sealed_box::seal(key, &self.share_context(client_id, member_port), share)
+    }
+
+    /// Check that the committee is the one its public selection draws, so the server did
+    /// not pick it, or without a selection the consecutive committee any server sets up.
+    pub fn verify_committee(&self) -> Result<(), SelectionError> {
+        committee_selection::check_committee(
+            self.committee_selection.as_ref(),
+            self.committee_size,
+            self.port,
+            &self.committee_port_offsets,
+        )
+    }
+
+    /// Check that `succinct_seed` is the one its derivation yields, so the server did not
+    /// pick the public matrix. A sampled seed has nothing to check.
+    pub fn verify_public_seed(&self) -> Result<(), SeedDerivationError> {
+        let Some(ref derivation) = self.seed_derivation else {
+            return Ok(());
+        };
+        if derivation.seed() != self.succinct_seed {
+            return Err(SeedDerivationError::NotDerived);
+        }
+        Ok(())
+    }
+
+    fn release(&self, bytes: usize) {
+        if let Some(ref budget) = self.buffer_budget {
+            budget.release(bytes);
+        }
+    }
+
+    /// The SHPRG public seed for `round_id`: the setup seed itself when it is reused,
+    /// otherwise a hash of it with the round id so every round gets a fresh public matrix.
+    pub fn public_seed_for(&self, round_id: RoundId) -> [u8; 32] {
+        if self.reuse_public_seed {
+            return self.succinct_seed;
+        }
+        KeyHomomorphicPRF::public_seed(self.succinct_seed, round_id)
+    }
+
+    /// The SHPRG public seed clients use for the round this state was handed out for.
+    pub fn round_public_seed(&self) -> [u8; 32] {
+        self.public_seed_for(self.round_id)
+    }
+
+    /// The proof published with the aggregate of the round this state was handed out for,
+    /// once the round has finished under verifiable aggregation.
+    pub fn aggregate_proof(&self) -> Option<AggregateProof> {
+        let proof = self.aggregate_proof.lock().unwrap();
+        proof.as_ref().filter(|proof| proof.round_id == self.round_id).cloned()
+    }
+
+    /// Block until the round this state was handed out for has finished, returning its
+    /// decoded output or the reason it was aborted.
+    pub fn round_result(&self, timeout: Duration) -> Result<Vec<u32>, RoundError> {
+        self.round.outcome(self.round_id, timeout)
+    }
+
+    /// Largest value any coordinate of the aggregate may reach and still decode: it has to
+    /// fit the 32-bit output slot and the bits the encoding leaves above the
+    /// 2^kappa * NUM_PARTIES_UPPER_BOUND scaling in Z_{2^128}.
+    pub fn max_aggregate_value(&self) -> u64 {
+        planner::max_aggregate_value(self.security_parameter)
+    }
+
     /// Decode a packed, masked aggregate back into a vector of u32s.
     /// This is shared between the server logic and tests/clients.
     pub fn decode_output(&self, output: Vec<u128>) -> Vec<u32> {
@@ -74,90 +729,830 @@ impl OPAState {
         let two_to_kappa: u128 = 1u128 << kappa;
         let two_to_kappa_times_n: u128 = two_to_kappa * (NUM_PARTIES_UPPER_BOUND as u128);
 
-        let denom = two_to_kappa_times_n as u128;
+        let denom = two_to_kappa_times_n;
         let decoded: Vec<u32> = output[..payload_len.min(output.len())]
             .iter()
             .map(|&x| {
                 let q = x / denom;
                 let r = x % denom;
-                let ceil = q + if r != 0 { 1 } else { 0 };
-                (ceil as u32) - 1
+                let ceil = q + u128::from(r != 0);
+                // unmasking leaves multiples of 2^32 * denom, so words are only defined
+                // mod 2^32 and a ceiling of 0 mod 2^32 decodes to u32::MAX
+                (ceil as u32).wrapping_sub(1)
             })
             .collect();
 
         unpack_vector(&decoded)
     }
 
+    /// Check that unmasking left every slot of `output` where an aggregate of
+    /// `num_clients` honest encodings lies, which a wrong mask misses with overwhelming
+    /// probability; returns the first slot that does not.
+    ///
+    /// Masks add up to the mask of the summed seed up to `-c + k * 2^92` with `c` below
+    /// the number of clients, since rounding drops each client's low bits. So padding,
+    /// which encodes zero, must unmask within `c` below a multiple of 2^92, and the low
+    /// part of a payload slot, `sum_i (r_i + 2^kappa)` less `c`, must lie in
+    /// `(n * 2^kappa - n, 2n * 2^kappa)` out of `2^kappa * NUM_PARTIES_UPPER_BOUND`. Under
+    /// registered keys the members' masks round down the same way, which adds up to one less
+    /// than the committee size back.
+    pub fn check_unmasked(
+        &self,
+        output: &[u128],
+        payload_len: usize,
+        num_clients: usize,
+    ) -> Result<(), RoundError> {
+        let n = num_clients.max(1) as u128;
+        let excess = if self.key_homomorphic_masks { self.committee_size.saturating_sub(1) as u128 } else { 0 };
+        let mask_modulus = 1u128 << DEFAULT_INNER_MODULUS_BITS;
+        let kappa = self.security_parameter as u32;
+        let denom = (1u128 << kappa) * (NUM_PARTIES_UPPER_BOUND as u128);
+        let slack = ((n << kappa) - (n - 1))..(((2 * n) << kappa) + excess);
+        let in_range = |slot: usize, x: u128| {
+            if slot >= payload_len {
+                x.wrapping_add(n - 1) % mask_modulus < n + excess
+            } else {
+                // above 2^92 the mask error reaches into the low part, which then says nothing
+                denom > mask_modulus || slack.contains(&(x % denom))
+            }
+        };
+        match output.iter().enumerate().find(|&(slot, &x)| !in_range(slot, x)) {
+            Some((slot, _)) => Err(RoundError::UnmaskingFailed { slot }),
+            None => Ok(()),
+        }
+    }
+
 }
 
 impl OPAServer {
-    fn decode_output(state: &OPAState, output: Vec<u128>, payload_len: usize) -> Vec<u32> {
-        state.decode_output_with_len(output, payload_len)
+    /// Derive the public seed with `derivation` instead of sampling it, and publish the
+    /// derivation so clients can check it. Must be called before the server listens.
+    pub fn derive_public_seed(&mut self, derivation: SeedDerivation) -> io::Result<()> {
+        if self.communicator.is_some() {
+            return Err(io::Error::other("the public seed must be derived before the server starts listening"));
+        }
+        self.state.succinct_seed = derivation.seed();
+        self.state.seed_derivation = Some(derivation);
+        Ok(())
     }
 
-    fn send_to_committee(tcp_stream: TcpStream, raw_messages: Vec<Vec<u8>>, port: u16) {
-        let committee_index = port - 10001; // TODO: this should be a constant/partyID
-
-        // deserialize each client message: [masked_input u128...][num_shares u32][share...]
-        let mut committee_shares = Vec::new();
-        
-        for raw_msg in &raw_messages {
-            let mut cursor = std::io::Cursor::new(raw_msg);
-            // skip masked_input (we know how long it is from the expand output length)
-            cursor.set_position((4 + OUTPUT_LEN * 16) as u64);
-            
-            // read num_shares
-            let mut buf = [0u8; 4];
-            cursor.read_exact(&mut buf).unwrap();
-            let num_shares = u32::from_le_bytes(buf) as usize;
-            
-            // skip to the share for this committee member
-            for i in 0..num_shares {
-                cursor.read_exact(&mut buf).unwrap();
-                let share_len = u32::from_le_bytes(buf) as usize;
-                let share_bytes = share_len * FIELD_ELEMENT_BYTES;
-                if i == committee_index as usize {
-                    let mut share = Vec::with_capacity(share_len);
-                    for _ in 0..share_len {
-                        let mut xbuf = [0u8; FIELD_ELEMENT_BYTES];
-                        cursor.read_exact(&mut xbuf).unwrap();
-                        share.push(xbuf);
-                    }
-                    committee_shares.push(share);
-                } else {
-                    cursor.set_position(cursor.position() + share_bytes as u64);
-                }
+    /// Only take messages signed by the party their envelope names in `address_book`, for
+    /// the current round, so no one can submit as a client or report as a committee member
+    /// without its key. Must be called before the server listens.
+    pub fn set_address_book(&mut self, address_book: AddressBook) -> io::Result<()> {
+        if self.communicator.is_some() {
+            return Err(io::Error::other("the address book must be set before the server starts listening"));
+        }
+        self.state.address_book = Some(Arc::new(address_book));
+        Ok(())
+    }
+
+    /// Publish the keys committee members open their shares with, by the port each member
+    /// connects from, so clients seal the shares and the server cannot reconstruct any
+    /// client's seed. Must be called before the server listens.
+    pub fn set_committee_keys(
+        &mut self,
+        keys: impl IntoIterator<Item = (u16, EncryptionKey)>,
+    ) -> io::Result<()> {
+        if self.communicator.is_some() {
+            return Err(io::Error::other("committee keys must be set before the server starts listening"));
+        }
+        self.state.committee_keys = keys.into_iter().collect();
+        Ok(())
+    }
+
+    /// Only let the clients with these identity keys enroll. Without an allowlist anyone
+    /// may enroll, so one party can still submit under many identities.
+    pub fn allow_clients(&mut self, identities: impl IntoIterator<Item = IdentityKey>) -> io::Result<()> {
+        let Some(ref registry) = self.state.client_registry else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the server does not run admission control"));
+        };
+        registry.allow(identities);
+        Ok(())
+    }
+
+    /// Decode one client submission straight from the socket, folding its ciphertext into
+    /// the running sum and retaining only the share section that must be forwarded later.
+    /// Nothing is folded unless the whole submission parses, so a peer that stalls or
+    /// overruns the size limit mid-message leaves the aggregate untouched.
+    fn receive_client_submission(
+        reader: &mut dyn Read,
+        addr: SocketAddr,
+        state: &OPAState,
+        budget: &BufferBudget,
+    ) -> io::Result<()> {
+        // under admission control: [identity][token][signature], see `Credentials`
+        let credentials = match state.client_registry {
+            Some(_) => {
+                let mut bytes = [0u8; CREDENTIALS_LEN];
+                reader.read_exact(&mut bytes)?;
+                Some(Credentials::from_bytes(&bytes))
+            }
+            None => None,
+        };
+
+        // [round_id u64][client_id u64][payload_len u32]
+        let mut header = [0u8; 20];
+        reader.read_exact(&mut header)?;
+        let round_id = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let client_id = ClientId::from_le_bytes(header[8..16].try_into().unwrap());
+        let payload_len = u32::from_le_bytes(header[16..].try_into().unwrap()) as usize;
+        if let (Some(registry), Some(credentials)) = (&state.client_registry, &credentials) {
+            registry.check_admitted(credentials, round_id)?;
+            if client_id != admission::client_id(&credentials.identity) {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "client id is not the identity's"));
+            }
+        }
+        if payload_len > OUTPUT_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "payload length exceeds SHPRG output length"));
+        }
+
+        let mut ciphertext_bytes = vec![0u8; OUTPUT_LEN * 16];
+        reader.read_exact(&mut ciphertext_bytes)?;
+        let ciphertext: Vec<u128> = ciphertext_bytes
+            .chunks_exact(16)
+            .map(|chunk| u128::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        // under verifiable aggregation, [commitment] to the payload slots of the ciphertext
+        // and [commitment] to the seed; only the first can be checked here, the second only
+        // in sum against the seed sum the committee reconstructs
+        let mut commitments = [0u8; ClientCommitments::LEN];
+        let commitments = if state.verifiable_aggregation {
+            reader.read_exact(&mut commitments)?;
+            let commitments = ClientCommitments::from_bytes(&commitments);
+            if commit_masked_input(&ciphertext[..payload_len]) != commitments.input {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "commitment does not match the masked input",
+                ));
+            }
+            Some(commitments)
+        } else {
+            None
+        };
+
+        // under range proofs, [commitment x payload_len][proof_len u32][proof], checked
+        // before anything is folded
+        let range_proof = match state.range_proof_bits {
+            Some(_) => Some(InputRangeProof::read_from(reader, payload_len)?),
+            None => None,
+        };
+        let mut proof_bytes = range_proof.as_ref().map(InputRangeProof::to_bytes).unwrap_or_default();
+        // and under a norm bound, the norm proof over the same commitments
+        let norm_proof = match state.norm_bound {
+            Some(bound) => Some(NormProof::read_from(reader, payload_len, bound)?),
+            None => None,
+        };
+        if let Some(proof) = &norm_proof {
+            proof_bytes.extend(proof.to_bytes());
+        }
+
+        // the remainder is [num_shares u32][share_len u32][field element x share_len]...,
+        // each share sealed to its member if the committee has keys, and no shares at all
+        // from a client masking under its registered key
+        let mut shares = Vec::new();
+        reader.read_to_end(&mut shares)?;
+        let num_shares = Self::validate_shares(&shares, state.committee_size as usize, state.share_overhead())?;
+
+        // the client set names clients by the id they submit under; admission control binds
+        // that id to an enrolled identity that may submit once per round
+        let commitment_bytes = commitments.map(|commitments| commitments.to_bytes());
+        let entry = ClientEntry {
+            id: client_id,
+            digest: submission_digest(&[
+                &header[..],
+                &ciphertext_bytes,
+                commitment_bytes.as_ref().map_or(&[][..], |c| &c[..]),
+                &proof_bytes,
+                &shares,
+            ]),
+        };
+        let identity = match (&state.client_registry, credentials) {
+            (Some(registry), Some(credentials)) => {
+                Some(registry.verify_submission(&credentials, round_id, &entry.digest)?)
+            }
+            _ => None,
+        };
+        // the costliest check comes last, once the sender is known to be admitted; a proof
+        // made for another masked input fails it
+        let range_commitments = match (state.range_proof_bits, &range_proof) {
+            (Some(bits), Some(proof)) => {
+                let payload = &ciphertext[..payload_len];
+                Some(proof.verify(bits, payload_len, state.session_id, round_id, payload)?)
             }
+            _ => None,
+        };
+        if let (Some(bound), Some(proof), Some(committed)) =
+            (state.norm_bound, &norm_proof, &range_commitments)
+        {
+            proof.verify(committed, bound, state.session_id, round_id)?;
         }
-        
-        // serialize all shares for this committee member.
+        let attachments = Attachments { commitments, range_commitments };
+
+        // the retained share section counts against the communicator's buffer budget
+        let shares = (num_shares > 0).then_some(shares);
+        let retained = shares.as_ref().map_or(0, Vec::len);
+        budget.reserve(retained)?;
+        let accepted = {
+            // closing takes the same lock, so the phase cannot change before the input is folded in
+            let mut inputs = state.client_inputs.lock().unwrap();
+            let round = &state.round;
+            let accepted = round
+                .expect(RoundPhase::CollectingInputs, "accept client input")
+                .and_then(|()| round.expect_id(round_id))
+                .map_err(io::Error::from)
+                .and_then(|()| inputs.accept(entry, identity, attachments, payload_len, &ciphertext, shares));
+            // journal the input before the client is told it was accepted
+            if accepted.is_ok()
+                && let Some(ref checkpoint) = state.checkpoint
+                && let Err(e) = inputs.checkpoint_last(checkpoint, retained > 0)
+            {
+                eprintln!("Failed to checkpoint submission from {:?}: {}", addr, e);
+            }
+            accepted
+        };
+        match accepted {
+            Ok(replaced) => {
+                budget.release(replaced);
+                Ok(())
+            }
+            Err(e) => {
+                budget.release(retained);
+                Err(e)
+            }
+        }
+    }
+
+    /// Check that a share section holds either no shares or exactly one well-formed share
+    /// per committee member, returning the number of shares. Sealed shares are only checked
+    /// for length; their members open them.
+    fn validate_shares(shares: &[u8], committee_size: usize, overhead: usize) -> io::Result<usize> {
+        let mut cursor = std::io::Cursor::new(shares);
+        let num_shares = read_u32(&mut cursor)? as usize;
+        if num_shares != committee_size && num_shares != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "share count does not match committee size"));
+        }
+        for _ in 0..num_shares {
+            let share_bytes = read_u32(&mut cursor)? as usize * FIELD_ELEMENT_BYTES + overhead;
+            let end = cursor.position() + share_bytes as u64;
+            if end > shares.len() as u64 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated share"));
+            }
+            cursor.set_position(end);
+        }
+        if cursor.position() != shares.len() as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "trailing bytes after shares"));
+        }
+        Ok(num_shares)
+    }
+
+    fn decode_output(state: &OPAState, output: Vec<u128>, payload_len: usize) -> Vec<u32> {
+        state.decode_output_with_len(output, payload_len)
+    }
+
+    /// The proof published with the aggregate of `client_set`: every accepted client must
+    /// have committed to its input, or nobody could check the sum against the commitments.
+    /// A proof whose seed commitments do not add up to the reconstructed seed sum would not
+    /// verify, so the round aborts instead of publishing it.
+    fn aggregate_proof(
+        inputs: &ClientInputs,
+        client_set: &ClientSet,
+        round_id: RoundId,
+        (seed_sum, seed_carries): (Vec<u128>, Vec<u32>),
+    ) -> Result<AggregateProof, RoundError> {
+        let clients = client_set
+            .entries()
+            .iter()
+            .map(|entry| {
+                let commitments = inputs
+                    .commitments
+                    .get(&entry.id)
+                    .copied()
+                    .ok_or(RoundError::MissingInputCommitment { client: entry.id })?;
+                Ok(CommittedClient {
+                    id: entry.id,
+                    digest: entry.digest,
+                    commitment: commitments.input,
+                    seed_commitment: commitments.seed,
+                })
+            })
+            .collect::<Result<Vec<_>, RoundError>>()?;
+        let sum = &inputs.ciphertext_sum;
+        let payload_len = sum.payload_len.unwrap_or(0);
+        let proof = AggregateProof {
+            round_id,
+            clients,
+            payload_len,
+            masked_sum: sum.sum[..payload_len.min(sum.sum.len())].to_vec(),
+            carries: sum.carries[..payload_len.min(sum.carries.len())].to_vec(),
+            seed_sum,
+            seed_carries,
+        };
+        proof.check_seed_sum().map_err(|_| RoundError::SeedCommitmentsDoNotAddUp)?;
+        Ok(proof)
+    }
+
+    fn send_to_committee(tcp_stream: TcpStream, state: &OPAState, port: u16) {
+        let Some(committee_index) = state.committee_index(port) else {
+            eprintln!("Port {} is not on the committee; not sending shares", port);
+            return;
+        };
+
+        // the first member to ask closes input collection; every member then receives the
+        // same ordered client set followed by its share from each of those clients.
         // send_on_stream prefixes each blob with its byte length, so the committee
         // derives the element count as byte_len / FIELD_ELEMENT_BYTES — do not add a redundant inner prefix.
-        let mut serialized_shares = Vec::new();
-        for share in &committee_shares {
-            let mut data = Vec::new();
-            for x in share {
-                data.extend_from_slice(x);
+        let client_set = match Self::open_committee_phase(state) {
+            Ok(client_set) => client_set,
+            Err(e) => {
+                // a round that hands out no shares closes the stream empty, which tells the member so
+                eprintln!("Not sending shares to committee member {}: {}", committee_index, e);
+                return;
             }
-            serialized_shares.push(data);
-        }
-        
-        // send the combined shares to the committee member
-        if let Err(e) = Communicator::send_on_stream(tcp_stream, serialized_shares) {
+        };
+        let mut blobs = Vec::with_capacity(client_set.len() + 1);
+        blobs.push(client_set.to_bytes());
+        blobs.extend(state.client_inputs.lock().unwrap().member_shares(committee_index, state.share_overhead()));
+
+        // send the client set and shares to the committee member
+        if let Err(e) = Communicator::send_on_stream(tcp_stream, blobs) {
             eprintln!("Failed to send data to committee member {}: {}", committee_index, e);
         }
     }
 
-    /// Called when all committee outputs have been received. This stores the
+    /// Close input collection if it is still open and move on to collecting committee outputs.
+    fn open_committee_phase(state: &OPAState) -> Result<ClientSet, RoundError> {
+        let client_set = state.close_inputs()?;
+        let started = state.round.advance_to(RoundPhase::CollectingCommitteeOutputs).map_err(|_| {
+            RoundError::WrongPhase { operation: "send shares to the committee", phase: state.round.phase() }
+        })?;
+        if started {
+            // don't wait forever on a committee that lost too many members
+            let state = state.clone();
+            let round_id = state.round.id();
+            std::thread::spawn(move || {
+                let outcome = state.round.outcome(round_id, state.committee_timeout);
+                if let Err(reason @ RoundError::Timeout { .. }) = outcome {
+                    state.finish_round(round_id, Err(reason));
+                }
+            });
+        }
+        Ok(client_set)
+    }
+
+    /// Locate one committee member's share inside a validated share section
+    /// `[num_shares u32][share_len u32][field element x share_len]...`, whose shares are
+    /// `overhead` bytes longer if sealed.
+    fn member_share(section: &[u8], committee_index: usize, overhead: usize) -> Option<Vec<u8>> {
+        let mut cursor = std::io::Cursor::new(section);
+        let num_shares = read_u32(&mut cursor).ok()? as usize;
+        for i in 0..num_shares {
+            let share_bytes = read_u32(&mut cursor).ok()? as usize * FIELD_ELEMENT_BYTES + overhead;
+            let start = cursor.position() as usize;
+            if i == committee_index {
+                return section.get(start..start + share_bytes).map(<[u8]>::to_vec);
+            }
+            cursor.set_position((start + share_bytes) as u64);
+        }
+        None
+    }
+
+    /// Parse a committee output `"committee" || index (u16 LE) || round id (u64 LE)
+    /// || client set commitment (32 bytes) || [len u32] [field element x len]`.
+    fn parse_committee_output(msg: &[u8]) -> Option<CommitteeOutput> {
+        let round_id = Self::committee_output_round(msg)?;
+        if msg.len() < 51 {
+            return None;
+        }
+        let index = u16::from_le_bytes([msg[9], msg[10]]) as usize;
+        let commitment: Commitment = msg[19..51].try_into().unwrap();
+
+        let mut cursor = std::io::Cursor::new(&msg[51..]);
+        let share_len = read_u32(&mut cursor).ok()? as usize;
+        let mut share = Vec::with_capacity(share_len);
+        for _ in 0..share_len {
+            let mut xbuf = [0u8; FIELD_ELEMENT_BYTES];
+            cursor.read_exact(&mut xbuf).ok()?;
+            share.push(xbuf);
+        }
+        Some(CommitteeOutput { index, round_id, commitment, share })
+    }
+
+    /// The round a committee output was computed for, if the message is long enough to say.
+    fn committee_output_round(msg: &[u8]) -> Option<RoundId> {
+        if msg.len() < 19 || &msg[..9] != b"committee" {
+            return None;
+        }
+        Some(u64::from_le_bytes(msg[11..19].try_into().unwrap()))
+    }
+
+    /// Called when all committee outputs have been received. This parses the
     /// messages into the state and runs aggregation.
-	fn on_committee_complete(mut state: OPAState, committee_messages: Vec<Vec<u8>>, client_messages: Vec<Vec<u8>>) {
-        // Store the messages into the state
-        state.committee_messages = committee_messages;
-        state.client_messages = client_messages;
-        
+	fn on_committee_complete(mut state: OPAState, committee_messages: Vec<Vec<u8>>) {
+        // extract the secret shares from the committee messages, along with their indices
+        state.committee_outputs = committee_messages
+            .iter()
+            .filter_map(|msg| {
+                let output = Self::parse_committee_output(msg);
+                if output.is_none() {
+                    eprintln!("Malformed committee message; skipping");
+                }
+                output
+            })
+            .collect();
+
         // Run aggregation directly using the state - clean and simple!
         OPAServer::aggregate(&state);
 	}
+
+    /// The handlers serving this protocol instance as a session of a listener on `port`,
+    /// charging retained client shares to that listener's `budget`. Fails if the checkpoint
+    /// cannot be read or what it holds does not fit the budget.
+    pub fn session(&mut self, port: u16, budget: BufferBudget) -> io::Result<Session> {
+        self.state.port = port;
+        if let Some(ref selection) = self.state.committee_selection {
+            // offsets are relative to the port, only known now
+            self.state.committee_port_offsets = selection
+                .port_offsets(self.state.committee_size, port)
+                .expect("the committee was drawn from clients connecting from the server's port");
+        }
+        self.state.buffer_budget = Some(budget.clone());
+        let retained = self.recover(&budget)?;
+        let mut session = Session::new();
+
+        // Fold each client's ciphertext into the running sum as it arrives, retaining only
+        // the share section that still has to be forwarded to the committee
+        let state_for_submissions = self.state.clone();
+        let submission_budget = budget.clone();
+        session.set_stream_callback(move |reader, addr| {
+            Self::receive_client_submission(reader, addr, &state_for_submissions, &submission_budget)?;
+            println!("Accepted client submission from {:?}", addr);
+            Ok(None)
+        });
+
+        // Verify every message against the address book before it reaches the handlers
+        if let Some(ref address_book) = self.state.address_book {
+            session.set_address_book(AddressBook::clone(address_book));
+            let round = Arc::clone(&self.state.round);
+            session.set_round_check(move |round_id| round.expect_id(round_id).map_err(io::Error::from));
+        }
+
+        // Enroll client identities and hand out admission tokens
+        if let Some(registry) = self.state.client_registry.clone() {
+            session.set_request_callback(move |request, _addr| registry.answer(request));
+        }
+
+        // Set up the callback to close the client set and send shares through the stream
+        let state = self.state.clone();
+        session.set_signal_callback(move |stream, port| {
+            println!("Signal handler called in server");
+            Self::send_to_committee(stream, &state, port);
+        });
+
+		// Configure auto-trigger for final aggregation: as soon as every committee member has
+		// reported, or a grace period after reconstruction_threshold of them have. Masks
+		// under registered keys need every member.
+		let threshold = match self.state.key_homomorphic_masks {
+			true => self.state.committee_size,
+			false => self.state.reconstruction_threshold,
+		};
+		let completion = CommitteeCompletion {
+			threshold: threshold as usize,
+			expected: self.state.committee_size as usize,
+			grace_period: self.setup_parameters.committee_grace_period,
+		};
+		session.set_committee_completion(completion);
+		// committee outputs only count while the current round is waiting for them
+		let state_for_filter = self.state.clone();
+		session.set_committee_message_filter(move |msg| {
+			let round = &state_for_filter.round;
+			round.expect(RoundPhase::CollectingCommitteeOutputs, "accept committee output")?;
+			let round_id = Self::committee_output_round(msg)
+				.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed committee output"))?;
+			round.expect_id(round_id)?;
+			state_for_filter.checkpoint("a committee output", |checkpoint| checkpoint.record_committee_output(msg));
+			Ok(())
+		});
+		let state_for_callback = self.state.clone();
+		session.set_committee_complete_callback(move |msgs| {
+			println!("Auto-triggering final aggregation with {} committee messages", msgs.len());
+			Self::on_committee_complete(state_for_callback.clone(), msgs);
+		});
+
+        // outputs that cannot finish a resumed round on their own wait for the rest
+        if let Some(ref mut resumed) = self.resumed
+            && resumed.committee_outputs.len() < self.state.reconstruction_threshold as usize
+        {
+            let outputs = std::mem::take(&mut resumed.committee_outputs);
+            if let Err(e) = session.restore_committee_messages(outputs, &budget) {
+                budget.release(retained);
+                return Err(e);
+            }
+        }
+        Ok(session)
+    }
+
+    /// Pick up where a server checkpointing to the same directory left off: its public seed
+    /// and round id, the registered client keys, and the inputs of an unfinished round.
+    /// Returns the bytes of recovered inputs charged to `budget`.
+    fn recover(&mut self, budget: &BufferBudget) -> io::Result<usize> {
+        let Some(checkpoint) = self.state.checkpoint.clone() else {
+            return Ok(0);
+        };
+        let Some((succinct_seed, round_id)) = checkpoint.load_setup()? else {
+            return Ok(0);
+        };
+        let keys = checkpoint.load_keys()?;
+        let round = checkpoint.load_round()?;
+        self.state.succinct_seed = succinct_seed;
+        self.state.round_id = round_id;
+
+        let mut inputs = self.state.client_inputs.lock().unwrap();
+        let mut retained = inputs.restore_keys(keys);
+        if let Some(mut round) = round.filter(|round| round.round_id == round_id) {
+            println!("Recovered {} clients of round {} from checkpoint", round.accepted.len(), round_id);
+            retained += inputs.restore(&mut round);
+            self.resumed = Some(round);
+        }
+        drop(inputs);
+        budget.reserve(retained)?;
+        Ok(retained)
+    }
+
+    /// Open the first round, or resume the one found in the checkpoint, once the session
+    /// is being served.
+    pub fn start(&mut self) {
+        let state = &self.state;
+        state.round.begin(state.round_id).expect("round already started");
+        match self.resumed.take() {
+            Some(resumed) => Self::resume(state, resumed),
+            None => state.checkpoint("the round start", |checkpoint| {
+                checkpoint.begin_round(state.succinct_seed, state.round_id)
+            }),
+        }
+        Self::arm_input_deadline(state);
+    }
+
+    /// Continue a recovered round from the phase it had reached: with inputs closed the
+    /// committee is waited on again, and enough recovered outputs finish the round at once.
+    fn resume(state: &OPAState, resumed: RoundCheckpoint) {
+        println!("Resuming round {}", state.round_id);
+        if !resumed.inputs_closed {
+            return;
+        }
+        if let Err(e) = Self::open_committee_phase(state) {
+            eprintln!("Could not resume round {}: {}", state.round_id, e);
+            return;
+        }
+        if !resumed.committee_outputs.is_empty() {
+            let state = state.clone();
+            std::thread::spawn(move || Self::on_committee_complete(state, resumed.committee_outputs));
+        }
+    }
+
+    /// Start round `round_id` once the previous round has finished, keeping the server's
+    /// setup. Returns the state to hand to the round's clients and committee; its
+    /// `round_result` yields the round's output.
+    pub fn start_round(state: &OPAState, round_id: RoundId) -> Result<OPAState, RoundError> {
+        state.round.begin(round_id)?;
+        let round_state = OPAState { round_id, ..state.clone() };
+        round_state.checkpoint("the round start", |checkpoint| {
+            checkpoint.begin_round(round_state.succinct_seed, round_id)
+        });
+        Self::arm_input_deadline(&round_state);
+        Ok(round_state)
+    }
+
+    /// Close input collection for the current round once the deadline passes; later
+    /// submissions are rejected.
+    fn arm_input_deadline(state: &OPAState) {
+        let Some(deadline) = state.input_deadline else {
+            return;
+        };
+        let state = state.clone();
+        let round_id = state.round.id();
+        std::thread::spawn(move || {
+            std::thread::sleep(deadline);
+            println!("Input deadline of {:?} for round {} passed", deadline, round_id);
+            match state.close_inputs_of(round_id) {
+                Ok(client_set) => println!("Closed input collection with {} clients", client_set.len()),
+                Err(e) => eprintln!("Could not close input collection: {}", e),
+            }
+        });
+    }
+
+    /// Rebuild the round's mask from the committee outputs and unmask the aggregate.
+    fn reveal(state: &OPAState) -> Result<Vec<u32>, RoundError> {
+        state.round.expect(RoundPhase::CollectingCommitteeOutputs, "aggregate committee outputs")?;
+        let round_id = state.round.id();
+        let client_set = state.close_inputs()?;
+        let summed = state.client_inputs.lock().unwrap().ciphertext_sum.num_clients;
+        if summed != client_set.len() {
+            return Err(RoundError::SumDoesNotCoverClientSet { summed, clients: client_set.len() });
+        }
+
+        // only use outputs computed over exactly the agreed client set, one per committee member
+        let commitment = client_set.commitment();
+        let mut seen = vec![false; state.committee_size as usize];
+        let committee_outputs: Vec<(usize, &Vec<FieldBytes>)> = state
+            .committee_outputs
+            .iter()
+            .filter(|output| {
+                if output.round_id != round_id {
+                    eprintln!(
+                        "Committee member {} sent an output for round {}; ignoring",
+                        output.index, output.round_id
+                    );
+                    return false;
+                }
+                if output.commitment != commitment {
+                    eprintln!("Committee member {} summed a different client set; ignoring", output.index);
+                    return false;
+                }
+                match seen.get_mut(output.index) {
+                    Some(seen) if !*seen => {
+                        *seen = true;
+                        true
+                    }
+                    _ => {
+                        eprintln!(
+                            "Unexpected or repeated output from committee member {}; ignoring",
+                            output.index
+                        );
+                        false
+                    }
+                }
+            })
+            .map(|output| (output.index, &output.share))
+            .collect();
+        println!("Performing final aggregation with {} committee messages", committee_outputs.len());
+
+        let (mask, blinding_sums, seed_sum) = if state.key_homomorphic_masks {
+            (Self::sum_member_masks(state, &committee_outputs)?, Vec::new(), None)
+        } else {
+            Self::reconstruct_mask(state, &committee_outputs)?
+        };
+        println!("Rebuilt the round's mask of length {}", mask.len());
+
+        // the input ciphertexts were already summed in Z_{2^128} as they arrived;
+        // unmask the running sum in place of a copy
+        let client_inputs = state.client_inputs.lock().unwrap();
+        let ciphertext_sum = &client_inputs.ciphertext_sum;
+        let payload_len = ciphertext_sum.payload_len.unwrap_or(0);
+        println!(
+            "Aggregated masked ciphertext from {} clients (client set {})",
+            ciphertext_sum.num_clients,
+            hex_prefix(&commitment),
+        );
+        let unmasked: Vec<u128> = ciphertext_sum
+            .sum
+            .iter()
+            .zip(mask.iter())
+            .map(|(&c, &m)| c.wrapping_sub(m))
+            .collect();
+        // a committee that sent bad shares, or summed another client set, leaves garbage
+        state.check_unmasked(&unmasked, payload_len, ciphertext_sum.num_clients)?;
+        if let Some(bits) = state.range_proof_bits {
+            let denominator = (1u128 << state.security_parameter) * NUM_PARTIES_UPPER_BOUND as u128;
+            let payload = &unmasked[..payload_len.min(unmasked.len())];
+            let (committed, num_clients) = (&ciphertext_sum.range_commitments, ciphertext_sum.num_clients);
+            if !range_proof::check_aggregate(
+                committed,
+                payload,
+                denominator,
+                &blinding_sums,
+                bits,
+                num_clients,
+            ) {
+                return Err(RoundError::RangeCheckFailed);
+            }
+            println!("Checked the aggregate against {} range-proven commitments", blinding_sums.len());
+        }
+        if let Some(seed_sum) = seed_sum {
+            let proof = Self::aggregate_proof(&client_inputs, &client_set, round_id, seed_sum)?;
+            *state.aggregate_proof.lock().unwrap() = Some(proof);
+        }
+        drop(client_inputs);
+
+        let decoded = Self::decode_output(state, unmasked, payload_len);
+        println!("Decoded output length: {}", decoded.len());
+        Ok(decoded)
+    }
+
+    /// Add up the round's mask from the members' PRF evaluations under their shares of the
+    /// registered keys. The keys are split additively, so every member has to report.
+    fn sum_member_masks(
+        state: &OPAState,
+        committee_outputs: &[(usize, &Vec<FieldBytes>)],
+    ) -> Result<Vec<u128>, RoundError> {
+        let committee_size = state.committee_size as usize;
+        if committee_outputs.len() < committee_size.max(1) {
+            return Err(RoundError::TooFewCommitteeOutputs {
+                received: committee_outputs.len(),
+                required: committee_size,
+            });
+        }
+        let mut mask = vec![0u128; OUTPUT_LEN];
+        for (member, share) in committee_outputs {
+            if share.len() != OUTPUT_LEN {
+                return Err(RoundError::InconsistentCommitteeOutput { member: *member });
+            }
+            for (acc, x) in mask.iter_mut().zip(share.iter()) {
+                *acc = acc.wrapping_add(field_low_u128(field_from_bytes::<F256>(x)));
+            }
+        }
+        println!("Added up the round's mask from {} committee outputs", committee_outputs.len());
+        Ok(mask)
+    }
+
+    /// Reconstruct the SHPRG seed sum from the members' Shamir shares and expand it into the
+    /// round's mask. Also returns the sums of the range proof blindings that follow the seed,
+    /// and under verifiable aggregation the seed sum itself with its carries above 2^128.
+    #[allow(clippy::type_complexity)]
+    fn reconstruct_mask(
+        state: &OPAState,
+        committee_outputs: &[(usize, &Vec<FieldBytes>)],
+    ) -> Result<(Vec<u128>, Vec<Scalar>, Option<(Vec<u128>, Vec<u32>)>), RoundError> {
+        let round_id = state.round.id();
+        let threshold = state.reconstruction_threshold as usize;
+        if committee_outputs.len() < threshold.max(1) {
+            return Err(RoundError::TooFewCommitteeOutputs {
+                received: committee_outputs.len(),
+                required: threshold,
+            });
+        }
+
+        // reconstruct the SHPRG seed from the secret shares (Shamir over F256)
+        let shamir = Shamir::<F256>::new(
+            state.committee_size as usize,
+            state.reconstruction_threshold as usize
+        );
+        let seed_len = committee_outputs[0].1.len();
+        if let Some((member, _)) = committee_outputs.iter().find(|(_, share)| share.len() != seed_len) {
+            return Err(RoundError::InconsistentCommitteeOutput { member: *member });
+        }
+        // every coordinate is shared at the same points, so the Lagrange coefficients for
+        // x = 0 and for each extra member's x are computed once for the whole seed
+        let xs: Vec<F256> = committee_outputs.iter().map(|(idx, _)| F256::from((*idx as u64) + 1)).collect();
+        let at_zero = shamir.lagrange_coefficients(&xs, F256::from(0u64)).expect("Shamir reconstruction failed");
+        let at_extra: Vec<Vec<F256>> = xs[threshold..]
+            .iter()
+            .map(|&x| shamir.lagrange_coefficients(&xs, x).expect("Shamir reconstruction failed"))
+            .collect();
+
+        let mut reconstructed: Vec<F256> = Vec::with_capacity(seed_len);
+        for j in 0..seed_len {
+            let ys: Vec<F256> = committee_outputs.iter().map(|(_, share)| field_from_bytes(&share[j])).collect();
+            let interpolate = |coefficients: &[F256]| -> F256 {
+                coefficients.iter().zip(&ys).map(|(&l, &y)| l * y).sum()
+            };
+            // outputs beyond the threshold must lie on the same polynomial, otherwise
+            // some committee member sent a bad share and the output cannot be trusted
+            let extra = &ys[threshold..];
+            if let Some(k) = at_extra.iter().zip(extra).position(|(l, &y)| interpolate(l) != y) {
+                return Err(RoundError::InconsistentCommitteeOutput {
+                    member: committee_outputs[threshold + k].0,
+                });
+            }
+            reconstructed.push(interpolate(&at_zero));
+        }
+        // under range proofs the shared vector ends in the sums of the blinding factors,
+        // one per payload slot
+        let blinding_len = match state.range_proof_bits {
+            Some(_) => state.client_inputs.lock().unwrap().ciphertext_sum.payload_len.unwrap_or(0),
+            None => 0,
+        };
+        if blinding_len > seed_len {
+            return Err(RoundError::RangeCheckFailed);
+        }
+        let blinding_sums: Vec<Scalar> = reconstructed
+            .split_off(seed_len - blinding_len)
+            .into_iter()
+            .map(range_proof::field_to_blinding)
+            .collect();
+        // Sum of client seed components can exceed 2^128; SHPRG uses Z_{2^128}. What lies
+        // above is the carry the seed commitments add up to, unless some client shared a
+        // seed that was no 128-bit integer, and then they cannot add up
+        let seed_carries = if state.verifiable_aggregation {
+            let carries: Option<Vec<u32>> = reconstructed.iter().map(|&x| field_carry_u32(x)).collect();
+            Some(carries.ok_or(RoundError::SeedCommitmentsDoNotAddUp)?)
+        } else {
+            None
+        };
+        let reconstructed_seed: Vec<u128> = reconstructed.into_iter().map(field_low_u128).collect();
+        println!(
+            "Reconstructed SHPRG seed of length {} ({} committee outputs, {} cross-checked)",
+            reconstructed_seed.len(),
+            committee_outputs.len(),
+            committee_outputs.len() - threshold,
+        );
+
+        // expand the SHPRG seed
+        let seed_sum = seed_carries.map(|carries| (reconstructed_seed.clone(), carries));
+        let shprg = SeedHomomorphicPRG::new_from_both_seeds(state.public_seed_for(round_id), reconstructed_seed);
+        Ok((shprg.expand(OUTPUT_LEN), blinding_sums, seed_sum))
+    }
 }
 
 impl Server for OPAServer {
@@ -169,18 +1564,39 @@ impl Server for OPAServer {
         let mut server = Self {
             setup_parameters,
             state: OPAState {
+                session_id: DEFAULT_SESSION,
+                round_id: 0,
                 succinct_seed: [0u8; 32],
+                reuse_public_seed: false,
+                seed_derivation: None,
+                key_homomorphic_masks: false,
+                admission_control: false,
+                verifiable_aggregation: false,
+                range_proof_bits: None,
+                norm_bound: None,
                 security_parameter: 0,
                 corruption_threshold: 0,
                 reconstruction_threshold: 0,
                 committee_size: 0,
                 committee_port_offsets: Vec::new(),
                 port: 0,
+                input_deadline: None,
+                committee_timeout: DEFAULT_COMMITTEE_TIMEOUT,
+                min_participants: 0,
+                committee_selection: None,
+                committee_keys: BTreeMap::new(),
                 output_sender: None,
-                client_messages: Vec::new(),
-                committee_messages: Vec::new(),
+                client_inputs: Arc::new(Mutex::new(ClientInputs::default())),
+                round: Arc::new(Round::new()),
+                buffer_budget: None,
+                checkpoint: None,
+                client_registry: None,
+                address_book: None,
+                committee_outputs: Vec::new(),
+                aggregate_proof: Arc::new(Mutex::new(None)),
             },
             communicator: None,
+            resumed: None,
         };
         server.setup(server.setup_parameters);
         server
@@ -195,63 +1611,96 @@ impl Server for OPAServer {
     }
 
     fn setup(&mut self, args: Self::SetupParameters) {
+        if let Err(e) = args.validate() {
+            panic!("invalid setup parameters: {}", e);
+        }
         self.setup_parameters = args;
 
-        // sample the public parameter seed
-        let mut rng = ChaCha20Rng::from_entropy();
-        let mut succinct_seed = [0u8; 32];
-        populate_random_bytes(&mut succinct_seed, &mut rng);
+        // derive the public parameter seed again if it is derived, otherwise sample it
+        let seed_derivation = self.state.seed_derivation.clone();
+        let succinct_seed = match seed_derivation {
+            Some(ref derivation) => derivation.seed(),
+            None => {
+                let mut rng = ChaCha20Rng::from_entropy();
+                let mut succinct_seed = [0u8; 32];
+                populate_random_bytes(&mut succinct_seed, &mut rng);
+                succinct_seed
+            }
+        };
 
-        // sample the committee ports
-        // TODO: currently just uses port offsets 1 through committee_size
-        let mut committee_port_offsets = Vec::<u16>::new();
-        for i in 1..=self.setup_parameters.committee_size as u16 {
-            committee_port_offsets.push(i);
-        }
+        // draw the committee again if it is drawn from the clients, otherwise give it
+        // committee_size consecutive port offsets
+        let committee_selection = self.state.committee_selection.clone();
+        let committee_port_offsets = match committee_selection {
+            Some(ref selection) => selection
+                .port_offsets(self.setup_parameters.committee_size, self.state.port)
+                .expect("the committee was drawn before"),
+            None => {
+                let first = self.setup_parameters.first_committee_port_offset;
+                (first..first + self.setup_parameters.committee_size as u16).collect()
+            }
+        };
 
         // preserve any existing output sender when refreshing the public state
         let output_sender = self.state.output_sender.clone();
-        let client_messages = self.state.client_messages.clone();
-        let committee_messages = self.state.committee_messages.clone();
+        let client_inputs = Arc::clone(&self.state.client_inputs);
+        if self.setup_parameters.key_homomorphic_masks {
+            *client_inputs.lock().unwrap() = ClientInputs::with_client_keys();
+        }
+        let round = Arc::clone(&self.state.round);
+        let committee_outputs = self.state.committee_outputs.clone();
+        // keep the enrolled identities if admission control stays on
+        let session_id = self.setup_parameters.session_id;
+        let client_registry = self.setup_parameters.admission_control.then(|| match self.state.client_registry {
+            Some(ref registry) if registry.session_id() == session_id => Arc::clone(registry),
+            _ => Arc::new(ClientRegistry::new(session_id)),
+        });
 
         // set the public state
         self.state = OPAState {
+            session_id: self.setup_parameters.session_id,
+            round_id: self.state.round_id,
             succinct_seed,
+            reuse_public_seed: self.setup_parameters.reuse_public_seed,
+            seed_derivation,
+            key_homomorphic_masks: self.setup_parameters.key_homomorphic_masks,
+            admission_control: self.setup_parameters.admission_control,
+            verifiable_aggregation: self.setup_parameters.verifiable_aggregation,
+            range_proof_bits: self.setup_parameters.range_proof_bits,
+            norm_bound: self.setup_parameters.norm_bound,
             security_parameter: self.setup_parameters.security_parameter,
             corruption_threshold: self.setup_parameters.corruption_threshold,
             reconstruction_threshold: self.setup_parameters.reconstruction_threshold,
             committee_size: self.setup_parameters.committee_size,
             committee_port_offsets,
             port: 0,
+            input_deadline: self.setup_parameters.input_deadline,
+            committee_timeout: self.setup_parameters.committee_timeout,
+            min_participants: self.setup_parameters.min_participants,
+            committee_selection,
+            committee_keys: self.state.committee_keys.clone(),
             output_sender,
-            client_messages,
-            committee_messages,
+            client_inputs,
+            round,
+            buffer_budget: self.state.buffer_budget.clone(),
+            checkpoint: self.state.checkpoint.clone(),
+            client_registry,
+            address_book: self.state.address_book.clone(),
+            committee_outputs,
+            aggregate_proof: Arc::clone(&self.state.aggregate_proof),
         };
     }
 
     fn on_communicator_setup(&mut self, port: u16) {
-        self.state.port = port;
-        
-        // Capture the received_messages Arc so the callback can access it
-        let messages = self.get_communicator().get_received_messages();
-        
-        // Set up the callback to gather inputs and send them through the stream
-        self.get_communicator().set_signal_callback(move |stream, port| {
-            println!("Signal handler called in server");
-            let inputs = messages.lock().unwrap().clone();
-            Self::send_to_committee(stream, inputs, port);
-        });
+        let budget = self.get_communicator().buffer_budget();
+        let session_id = self.state.session_id;
+        let session = self.session(port, budget).expect("could not resume from the checkpoint");
+        let communicator = self.get_communicator();
+        communicator.set_session_id(session_id);
+        *communicator.session_mut() = session;
 
-		// Configure auto-trigger for final aggregation when all committee outputs are received
-		let expected = self.state.committee_size as usize;
-		self.get_communicator().set_committee_expected_size(expected);
-		let state_for_callback = self.state.clone();
-		let client_messages_arc = self.get_communicator().get_received_messages();
-		self.get_communicator().set_committee_complete_callback(move |msgs| {
-			println!("Auto-triggering final aggregation with {} committee messages", msgs.len());
-			let client_messages = client_messages_arc.lock().unwrap().clone();
-			Self::on_committee_complete(state_for_callback.clone(), msgs, client_messages);
-		});
+		// the listener is about to start accepting connections
+		self.start();
     }
 
     fn get_state(&self) -> &Self::State {
@@ -262,120 +1711,456 @@ impl Server for OPAServer {
         self.state.committee_port_offsets.clone()
     }
 
+    fn committee_port_offsets(state: &OPAState) -> Vec<u16> {
+        state.committee_port_offsets.clone()
+    }
+
+    fn close_inputs(state: &OPAState) {
+        match state.close_inputs() {
+            Ok(client_set) => println!("Closed input collection with {} clients", client_set.len()),
+            Err(e) => eprintln!("Could not close input collection: {}", e),
+        }
+    }
+
     fn set_output_channel(&mut self, sender: mpsc::Sender<Vec<u32>>) {
         self.state.output_sender = Some(sender);
     }
 
+    fn set_checkpoint_dir(&mut self, dir: &Path) -> io::Result<()> {
+        self.state.checkpoint = Some(Arc::new(ServerCheckpoint::new(dir, self.state.session_id)?));
+        Ok(())
+    }
+
+    fn select_committee(&mut self, population: &[ClientId], beacon: Option<Beacon>) -> io::Result<()> {
+        if self.communicator.is_some() {
+            return Err(io::Error::other("the committee must be drawn before the server starts listening"));
+        }
+        let selection = CommitteeSelection::new(population.to_vec(), beacon, self.state.round_id);
+        self.state.committee_port_offsets = selection.port_offsets(self.state.committee_size, self.state.port)?;
+        self.state.committee_selection = Some(selection);
+        Ok(())
+    }
+
     fn aggregate(state: &OPAState) {
-        if state.committee_messages.is_empty() || state.client_messages.is_empty() {
-            eprintln!("aggregate() called but messages are not populated; skipping.");
-            return;
+        let round_id = state.round.id();
+        let outcome = Self::reveal(state);
+        if let Ok(ref decoded) = outcome
+            && let Some(ref sender) = state.output_sender
+            && let Err(e) = sender.send(decoded.clone())
+        {
+            eprintln!("Failed to send decoded output over channel: {}", e);
         }
+        state.finish_round(round_id, outcome);
+    }
+}
 
-        let committee_messages = state.committee_messages.clone();
-        let client_messages = state.client_messages.clone();
+fn hex_prefix(bytes: &[u8]) -> String {
+    bytes.iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
 
-        println!("Performing final aggregation with {} committee messages", committee_messages.len());
+fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
 
-        // extract the secret shares from the committee messages, along with their indices
-        let mut committee_outputs: Vec<(usize, Vec<FieldBytes>)> =
-            Vec::with_capacity(committee_messages.len());
-        for msg in committee_messages {
-            // format: "committee" (9 bytes) || index (u16 LE) || [len u32] [field element x len]
-            if msg.len() < 11 || &msg[..9] != b"committee" {
-                eprintln!("Malformed committee message; missing prefix");
-                continue;
-            }
-            let index_bytes = &msg[9..11];
-            let committee_index =
-                u16::from_le_bytes([index_bytes[0], index_bytes[1]]) as usize;
-
-            let mut cursor = std::io::Cursor::new(&msg[11..]);
-            let mut buf = [0u8; 4];
-            cursor.read_exact(&mut buf).unwrap();
-            let share_len = u32::from_le_bytes(buf) as usize;
-            let mut share = Vec::with_capacity(share_len);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::protocols::opa::admission;
+    use crate::protocols::opa::verification::{InputCommitment, commit_seed};
+
+    // serialize a client submission in the wire format produced by OPAClient::send_input
+    fn submission(payload_len: u32, value: u128, committee_size: u32, share_len: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_all(&0u64.to_le_bytes()).unwrap();
+        data.write_all(&payload_len.to_le_bytes()).unwrap();
+        for _ in 0..OUTPUT_LEN {
+            data.write_all(&value.to_le_bytes()).unwrap();
+        }
+        data.write_all(&committee_size.to_le_bytes()).unwrap();
+        for member in 0..committee_size {
+            data.write_all(&share_len.to_le_bytes()).unwrap();
             for _ in 0..share_len {
-                let mut xbuf = [0u8; FIELD_ELEMENT_BYTES];
-                cursor.read_exact(&mut xbuf).unwrap();
-                share.push(xbuf);
+                data.write_all(&[member as u8; FIELD_ELEMENT_BYTES]).unwrap();
             }
-            committee_outputs.push((committee_index, share));
         }
+        data
+    }
 
-        if committee_outputs.len() < state.reconstruction_threshold as usize {
-            eprintln!("Not enough committee outputs to reconstruct the SHPRG seed");
-            return;
-        }
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
 
-        // reconstruct the SHPRG seed from the secret shares (Shamir over F256)
-        let shamir = Shamir::<F256>::new(
-            state.committee_size as usize,
-            state.reconstruction_threshold as usize
+    // server state for a round that is accepting client inputs
+    fn collecting_state(min_participants: u64) -> OPAState {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_min_participants(min_participants);
+        let state = OPAServer::new(parameters).get_state().clone();
+        state.round.transition(RoundPhase::CollectingInputs).unwrap();
+        state
+    }
+
+    // a submission without credentials, as sent by client `id`
+    fn with_id(message: &[u8], id: ClientId) -> Vec<u8> {
+        [&message[..8], &id.to_le_bytes(), &message[8..]].concat()
+    }
+
+    fn receive(state: &OPAState, message: &[u8], id: ClientId, budget: &BufferBudget) -> io::Result<()> {
+        OPAServer::receive_client_submission(&mut &with_id(message, id)[..], addr(id as u16), state, budget)
+    }
+
+    #[test]
+    // test that submissions are folded into the running sum and only the shares are retained
+    fn test_streaming_submission() {
+        let state = collecting_state(2);
+        let budget = Communicator::new(0).buffer_budget();
+        let first = submission(8, u128::MAX, 3, 2);
+        let second = submission(8, 2, 3, 2);
+
+        receive(&state, &first, 2, &budget).unwrap();
+        receive(&state, &second, 1, &budget).unwrap();
+
+        let inputs = state.client_inputs.lock().unwrap();
+        let sum = &inputs.ciphertext_sum;
+        assert_eq!(sum.num_clients, 2);
+        assert_eq!(sum.payload_len, Some(8));
+        assert!(sum.sum.iter().all(|&x| x == 1));
+        assert_eq!(budget.used(), 2 * (first.len() - 12 - OUTPUT_LEN * 16));
+        assert_eq!(inputs.member_shares(2, 0), vec![vec![2u8; 2 * FIELD_ELEMENT_BYTES]; 2]);
+    }
+
+    #[test]
+    // test that closing inputs fixes an ordered client set and rejects later or repeated submissions
+    fn test_close_inputs() {
+        let state = collecting_state(2);
+        let budget = Communicator::new(0).buffer_budget();
+        let message = submission(8, 5, 3, 2);
+
+        receive(&state, &message, 9, &budget).unwrap();
+        receive(&state, &message, 4, &budget).unwrap();
+        let duplicate = receive(&state, &message, 4, &budget);
+        assert_eq!(duplicate.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+
+        let client_set = state.close_inputs().unwrap();
+        let ids: Vec<u64> = client_set.entries().iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![4, 9]);
+        assert_eq!(state.close_inputs().unwrap(), client_set);
+        assert_eq!(state.round.phase(), RoundPhase::InputsClosed);
+
+        // late submissions are rejected with a typed wrong-phase error
+        let late = receive(&state, &message, 5, &budget).unwrap_err();
+        assert_eq!(late.kind(), io::ErrorKind::PermissionDenied);
+        let late = late.into_inner().unwrap().downcast::<RoundError>().unwrap();
+        assert_eq!(
+            *late,
+            RoundError::WrongPhase { operation: "accept client input", phase: RoundPhase::InputsClosed }
         );
-        let seed_len = committee_outputs[0].1.len();
-        let mut reconstructed_seed: Vec<u128> = Vec::with_capacity(seed_len);
-        for j in 0..seed_len {
-            let mut pairs: Vec<(F256, F256)> = Vec::with_capacity(committee_outputs.len());
-            for (idx, share_vec) in committee_outputs.iter() {
-                let x = F256::from((*idx as u64) + 1);
-                let y = field_from_bytes(&share_vec[j]);
-                pairs.push((x, y));
-            }
-            let s = shamir.reconstruct(&pairs).expect("Shamir reconstruction failed");
-            // Sum of client seed components can exceed 2^128; SHPRG uses Z_{2^128}.
-            reconstructed_seed.push(field_low_u128(s));
-        }
-        println!("Reconstructed SHPRG seed of length {}", reconstructed_seed.len());
+        assert_eq!(state.client_inputs.lock().unwrap().ciphertext_sum.num_clients, 2);
+    }
 
-        // expand the SHPRG seed
-        let shprg = SeedHomomorphicPRG::new_from_both_seeds(state.succinct_seed, reconstructed_seed);
-        let mask = shprg.expand(OUTPUT_LEN);
-        println!("Expanded SHPRG mask of length {}", mask.len());
-
-        // aggregate input ciphertexts in Z_{2^128}
-        let mut client_iter = client_messages.into_iter();
-        let first_raw = client_iter.next().unwrap();
-        let mut cursor = std::io::Cursor::new(&first_raw);
-        let mut buf = [0u8; 4];
-        cursor.read_exact(&mut buf).unwrap();
-        let payload_len = u32::from_le_bytes(buf) as usize;
-
-        let mut aggregated_ciphertext: Vec<u128> = (0..OUTPUT_LEN)
-            .map(|_| {
-                let mut xbuf = [0u8; 16];
-                cursor.read_exact(&mut xbuf).unwrap();
-                u128::from_le_bytes(xbuf)
-            })
-            .collect();
-        let mut num_clients = 1usize;
-        for raw in client_iter {
-            let mut c = std::io::Cursor::new(&raw);
-            c.read_exact(&mut buf).unwrap();
-            for i in 0..OUTPUT_LEN {
-                let mut xbuf = [0u8; 16];
-                c.read_exact(&mut xbuf).unwrap();
-                aggregated_ciphertext[i] =
-                    aggregated_ciphertext[i].wrapping_add(u128::from_le_bytes(xbuf));
-            }
-            num_clients += 1;
-        }
-        println!("Aggregated masked ciphertext from {} clients", num_clients);
+    #[test]
+    // test that malformed submissions are rejected without touching the running sum
+    fn test_malformed_submission() {
+        let state = collecting_state(2);
+        let budget = Communicator::new(0).buffer_budget();
 
-        // unmask the aggregated ciphertext in Z_{2^128}
-        let unmasked: Vec<u128> = aggregated_ciphertext
-            .iter()
-            .zip(mask.iter())
-            .map(|(&c, &m)| c.wrapping_sub(m))
-            .collect();
+        let mut truncated = submission(8, 5, 3, 2);
+        truncated.truncate(truncated.len() - 1);
+        assert!(receive(&state, &truncated, 1, &budget).is_err());
 
-        let decoded = Self::decode_output(&state, unmasked, payload_len);
-        println!("Decoded output length: {}", decoded.len());
+        let wrong_committee = submission(8, 5, 2, 2);
+        assert!(receive(&state, &wrong_committee, 2, &budget).is_err());
 
-        if let Some(ref sender) = state.output_sender {
-            if let Err(e) = sender.send(decoded.clone()) {
-                eprintln!("Failed to send decoded output over channel: {}", e);
-            }
-        }
+        assert_eq!(state.client_inputs.lock().unwrap().ciphertext_sum.num_clients, 0);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    // test that under verifiable aggregation a submission counts only with a matching commitment
+    fn test_committed_submission() {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_verifiable_aggregation(true);
+        let state = OPAServer::new(parameters).get_state().clone();
+        state.round.transition(RoundPhase::CollectingInputs).unwrap();
+        let budget = Communicator::new(0).buffer_budget();
+        let committed = |input: InputCommitment| {
+            let commitments = ClientCommitments { input, seed: commit_seed(&[9; 4]) };
+            let mut message = submission(8, 5, 3, 2);
+            message.splice(12 + OUTPUT_LEN * 16..12 + OUTPUT_LEN * 16, commitments.to_bytes());
+            message
+        };
+
+        let wrong = receive(&state, &committed(commit_masked_input(&[5; 7])), 1, &budget).unwrap_err();
+        assert_eq!(wrong.kind(), io::ErrorKind::InvalidData);
+        assert!(receive(&state, &submission(8, 5, 3, 2), 1, &budget).is_err());
+        receive(&state, &committed(commit_masked_input(&[5; 8])), 2, &budget).unwrap();
+
+        // the seed commitment is kept alongside, to be checked against the seed sum
+        let mut inputs = state.client_inputs.lock().unwrap();
+        assert_eq!(inputs.ciphertext_sum.num_clients, 1);
+        let commitments = inputs.commitments.get(&2).unwrap();
+        assert_eq!(commitments.input, commit_masked_input(&[5; 8]));
+        assert_eq!(commitments.seed, commit_seed(&[9; 4]));
+        let client_set = inputs.close();
+        let proof = OPAServer::aggregate_proof(&inputs, &client_set, 0, (vec![9; 4], vec![0; 4])).unwrap();
+        assert_eq!(proof.clients[0].seed_commitment, commit_seed(&[9; 4]));
+        assert_eq!(
+            OPAServer::aggregate_proof(&inputs, &client_set, 0, (vec![9, 9, 9, 8], vec![0; 4])),
+            Err(RoundError::SeedCommitmentsDoNotAddUp)
+        );
+    }
+
+    #[test]
+    // test that under range proofs a submission counts only with a proof for its round and masked input
+    fn test_range_proven_submission() {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_range_proofs(8);
+        let state = OPAServer::new(parameters).get_state().clone();
+        state.round.transition(RoundPhase::CollectingInputs).unwrap();
+        let budget = Communicator::new(0).buffer_budget();
+        let proven = |proof: &InputRangeProof| {
+            let mut message = submission(2, 5, 3, 2);
+            message.splice(12 + OUTPUT_LEN * 16..12 + OUTPUT_LEN * 16, proof.to_bytes());
+            message
+        };
+
+        let (proof, _) = InputRangeProof::prove(&[7, 255], 8, state.session_id, 0, &[5, 5]).unwrap();
+        let (stale, _) = InputRangeProof::prove(&[7, 255], 8, state.session_id, 1, &[5, 5]).unwrap();
+        let (longer, _) = InputRangeProof::prove(&[7, 255, 1], 8, state.session_id, 0, &[5, 5, 5]).unwrap();
+        // a proof lifted from a submission with another masked input
+        let (lifted, _) = InputRangeProof::prove(&[7, 255], 8, state.session_id, 0, &[5, 6]).unwrap();
+        assert_eq!(receive(&state, &proven(&stale), 1, &budget).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(receive(&state, &proven(&longer), 1, &budget).is_err());
+        let error = receive(&state, &proven(&lifted), 1, &budget).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(receive(&state, &submission(2, 5, 3, 2), 1, &budget).is_err());
+        receive(&state, &proven(&proof), 2, &budget).unwrap();
+
+        let inputs = state.client_inputs.lock().unwrap();
+        assert_eq!(inputs.ciphertext_sum.num_clients, 1);
+        let commitments: Vec<RistrettoPoint> =
+            proof.commitments.iter().map(|c| c.decompress().unwrap()).collect();
+        assert_eq!(inputs.ciphertext_sum.range_commitments, commitments);
+    }
+
+    #[test]
+    // test that under a norm bound a submission counts only with a norm proof over its range commitments
+    fn test_norm_bounded_submission() {
+        let bound = NormBound::LInf(200);
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_range_proofs(8).with_norm_bound(bound);
+        let state = OPAServer::new(parameters).get_state().clone();
+        state.round.transition(RoundPhase::CollectingInputs).unwrap();
+        let budget = Communicator::new(0).buffer_budget();
+        let bounded = |proof: &InputRangeProof, norm_proof: &NormProof| {
+            let mut message = submission(2, 5, 3, 2);
+            let proofs = [proof.to_bytes(), norm_proof.to_bytes()].concat();
+            message.splice(12 + OUTPUT_LEN * 16..12 + OUTPUT_LEN * 16, proofs);
+            message
+        };
+
+        // 255 is in range but above the bound; a norm proof for another value does not pass
+        let (proof, blindings) = InputRangeProof::prove(&[7, 255], 8, state.session_id, 0, &[5, 5]).unwrap();
+        let lie = NormProof::prove(&[7, 200], &blindings, bound, state.session_id, 0).unwrap();
+        let error = receive(&state, &bounded(&proof, &lie), 1, &budget).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let (proof, blindings) = InputRangeProof::prove(&[7, 200], 8, state.session_id, 0, &[5, 5]).unwrap();
+        let norm_proof = NormProof::prove(&[7, 200], &blindings, bound, state.session_id, 0).unwrap();
+        let mut unbounded = submission(2, 5, 3, 2);
+        unbounded.splice(12 + OUTPUT_LEN * 16..12 + OUTPUT_LEN * 16, proof.to_bytes());
+        assert!(receive(&state, &unbounded, 1, &budget).is_err());
+        receive(&state, &bounded(&proof, &norm_proof), 2, &budget).unwrap();
+        assert_eq!(state.client_inputs.lock().unwrap().ciphertext_sum.num_clients, 1);
+    }
+
+    #[test]
+    // test that under admission control only admitted, signed submissions count, once per identity
+    fn test_admitted_submissions() {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_admission_control(true);
+        let state = OPAServer::new(parameters).get_state().clone();
+        state.round.transition(RoundPhase::CollectingInputs).unwrap();
+        let registry = state.client_registry.clone().unwrap();
+        let budget = Communicator::new(0).buffer_budget();
+
+        let key = ed25519_dalek::SigningKey::generate(&mut crate::crypto::prg::default_prg());
+        registry.answer(&admission::enroll_request(&key, 0)).unwrap();
+        let token = registry.answer(&admission::admit_request(&key, 0, 0)).unwrap().try_into().unwrap();
+        let id = admission::client_id(&key.verifying_key().to_bytes());
+        let signed = |message: &[u8], id: ClientId| {
+            let message = with_id(message, id);
+            let credentials = Credentials::sign(&key, 0, token, &submission_digest(&[&message]));
+            [&credentials.to_bytes()[..], &message].concat()
+        };
+        let receive_signed = |message: &[u8], port: u16| {
+            OPAServer::receive_client_submission(&mut &message[..], addr(port), &state, &budget)
+        };
+
+        // a submission without credentials is not admitted, nor one under another id
+        let first = submission(8, 5, 3, 2);
+        assert_eq!(receive(&state, &first, id, &budget).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(receive_signed(&signed(&first, 1), 1).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        receive_signed(&signed(&first, id), 1).unwrap();
+
+        // the same identity cannot submit again from another port, nor alter what it signed
+        let second = submission(8, 6, 3, 2);
+        assert_eq!(receive_signed(&signed(&second, id), 2).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        let mut tampered = signed(&second, id);
+        tampered[CREDENTIALS_LEN + 28] ^= 1;
+        assert_eq!(receive_signed(&tampered, 3).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(state.client_inputs.lock().unwrap().ciphertext_sum.num_clients, 1);
+    }
+
+    #[test]
+    // test that a round with fewer than min_participants clients is aborted instead of revealed
+    fn test_min_participants() {
+        let state = collecting_state(2);
+        let budget = Communicator::new(0).buffer_budget();
+        receive(&state, &submission(8, 5, 3, 2), 1, &budget).unwrap();
+
+        let err = state.close_inputs().unwrap_err();
+        assert_eq!(err, RoundError::TooFewParticipants { accepted: 1, required: 2 });
+        assert_eq!(state.round.phase(), RoundPhase::Aborted);
+        assert!(OPAServer::open_committee_phase(&state).is_err());
+
+        // even without a minimum, an empty round is never revealed
+        let state = collecting_state(0);
+        assert!(state.close_inputs().is_err());
+    }
+
+    #[test]
+    // test that a running sum that does not cover the client set aborts the round
+    fn test_sum_does_not_cover_client_set() {
+        let state = collecting_state(1);
+        let budget = Communicator::new(0).buffer_budget();
+        receive(&state, &submission(8, 5, 3, 2), 1, &budget).unwrap();
+        receive(&state, &submission(8, 5, 3, 2), 2, &budget).unwrap();
+        OPAServer::open_committee_phase(&state).unwrap();
+
+        state.client_inputs.lock().unwrap().ciphertext_sum.num_clients += 1;
+        OPAServer::aggregate(&state);
+        assert_eq!(state.round.phase(), RoundPhase::Aborted);
+        assert_eq!(
+            state.round_result(Duration::ZERO),
+            Err(RoundError::SumDoesNotCoverClientSet { summed: 3, clients: 2 })
+        );
+    }
+
+    #[test]
+    // test that a finished round releases its buffers and the next round starts from scratch
+    fn test_start_round() {
+        let mut state = collecting_state(1);
+        let budget = Communicator::new(0).buffer_budget();
+        state.buffer_budget = Some(budget.clone());
+        let message = submission(8, 5, 3, 2);
+
+        receive(&state, &message, 1, &budget).unwrap();
+        assert!(matches!(OPAServer::start_round(&state, 1), Err(RoundError::WrongPhase { .. })));
+
+        OPAServer::open_committee_phase(&state).unwrap();
+        state.finish_round(0, Ok(vec![7]));
+        assert_eq!(budget.used(), 0);
+        assert_eq!(state.client_inputs.lock().unwrap().ciphertext_sum.num_clients, 0);
+        assert_eq!(state.round_result(Duration::ZERO), Ok(vec![7]));
+
+        assert!(matches!(OPAServer::start_round(&state, 0), Err(RoundError::StaleRound { .. })));
+        let next = OPAServer::start_round(&state, 1).unwrap();
+        assert_eq!(next.round_id, 1);
+        assert_ne!(next.round_public_seed(), state.round_public_seed());
+
+        // submissions encrypted for the previous round are refused
+        let stale = receive(&next, &message, 2, &budget).unwrap_err();
+        let stale = stale.into_inner().unwrap().downcast::<RoundError>().unwrap();
+        assert_eq!(*stale, RoundError::WrongRound { expected: 1, received: 0 });
+
+        let reused = OPAState { reuse_public_seed: true, ..next.clone() };
+        assert_eq!(reused.round_public_seed(), reused.public_seed_for(0));
+    }
+
+    #[test]
+    // test that registered keys stand in for seed shares and survive into later rounds
+    fn test_registered_keys() {
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_key_homomorphic_masks(true);
+        let state = OPAServer::new(parameters).get_state().clone();
+        state.round.begin(0).unwrap();
+        let budget = Communicator::new(0).buffer_budget();
+        let with_key = submission(8, 5, 3, 2);
+        let without_key = submission(8, 5, 0, 2);
+
+        receive(&state, &with_key, 1, &budget).unwrap();
+        let unregistered = receive(&state, &without_key, 2, &budget).unwrap_err();
+        assert_eq!(unregistered.kind(), io::ErrorKind::NotFound);
+        receive(&state, &with_key, 2, &budget).unwrap();
+        let key_bytes = budget.used();
+
+        OPAServer::open_committee_phase(&state).unwrap();
+        assert_eq!(state.client_inputs.lock().unwrap().member_shares(1, 0), vec![vec![1u8; 64]; 2]);
+        state.finish_round(0, Ok(vec![10]));
+        assert_eq!(budget.used(), key_bytes);
+
+        // the next round needs no shares from either client
+        let next = OPAServer::start_round(&state, 1).unwrap();
+        let mut without_key = without_key;
+        without_key[..8].copy_from_slice(&1u64.to_le_bytes());
+        receive(&next, &without_key, 1, &budget).unwrap();
+        receive(&next, &without_key, 2, &budget).unwrap();
+        OPAServer::open_committee_phase(&next).unwrap();
+        assert_eq!(next.client_inputs.lock().unwrap().member_shares(2, 0), vec![vec![2u8; 64]; 2]);
+        assert_eq!(budget.used(), key_bytes);
+    }
+
+    #[test]
+    // test that a restarted server resumes a checkpointed round where it left off
+    fn test_resume_round() {
+        let dir = std::env::temp_dir().join(format!("jagguar-resume-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let parameters = OPASetupParameters::new(40, 1, 2, 3).with_min_participants(2);
+        let message = submission(8, 5, 3, 2);
+
+        let mut server = OPAServer::new(parameters);
+        server.set_checkpoint_dir(&dir).unwrap();
+        let budget = Communicator::new(0).buffer_budget();
+        server.session(0, budget.clone()).unwrap();
+        server.start();
+        let state = server.get_state().clone();
+        receive(&state, &message, 1, &budget).unwrap();
+        receive(&state, &message, 2, &budget).unwrap();
+        state.close_inputs().unwrap();
+
+        // a new server on the same directory picks up the seed, the inputs and the phase
+        let mut restarted = OPAServer::new(parameters);
+        restarted.set_checkpoint_dir(&dir).unwrap();
+        let restarted_budget = Communicator::new(0).buffer_budget();
+        restarted.session(0, restarted_budget.clone()).unwrap();
+        restarted.start();
+        let resumed = restarted.get_state();
+        assert_eq!(resumed.succinct_seed, state.succinct_seed);
+        assert_eq!(resumed.round.phase(), RoundPhase::CollectingCommitteeOutputs);
+        assert_eq!(restarted_budget.used(), budget.used());
+        let inputs = resumed.client_inputs.lock().unwrap();
+        assert_eq!(inputs.ciphertext_sum.num_clients, 2);
+        assert!(inputs.ciphertext_sum.sum.iter().all(|&x| x == 10));
+        assert_eq!(inputs.member_shares(1, 0), vec![vec![1u8; 2 * FIELD_ELEMENT_BYTES]; 2]);
+        drop(inputs);
+
+        resumed.finish_round(0, Ok(vec![10]));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    // test that an unreadable checkpoint fails the session instead of the listener serving it
+    fn test_unreadable_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("jagguar-unreadable-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut server = OPAServer::new(OPASetupParameters::new(40, 1, 2, 3));
+        server.set_checkpoint_dir(&dir).unwrap();
+        let setup = dir.join(format!("server-{}.setup", server.get_state().session_id));
+        std::fs::write(setup, b"not a setup").unwrap();
+        let budget = Communicator::new(0).buffer_budget();
+        let Err(error) = server.session(0, budget.clone()) else {
+            panic!("a session was served from an unreadable checkpoint");
+        };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(budget.used(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
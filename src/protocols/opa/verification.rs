@@ -0,0 +1,273 @@
+use std::fmt;
+use std::io;
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity, VartimeMultiscalarMul};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::crypto::SeedHomomorphicPRG;
+use crate::protocols::opa::client::OUTPUT_LEN;
+use crate::protocols::opa::client_set::{ClientId, Commitment};
+use crate::protocols::opa::params::{OPAPublicParams, hex_seed};
+use crate::protocols::opa::round::{RoundError, RoundId};
+use crate::protocols::opa::server::OPAState;
+
+const GENERATOR_DOMAIN: &[u8] = b"jagguar/opa/input-commitment/generator/v1";
+const SEED_GENERATOR_DOMAIN: &[u8] = b"jagguar/opa/seed-commitment/generator/v1";
+
+/// A client's commitment to its masked input: `sum_j c_j * G_j` over the payload slots,
+/// with one fixed Ristretto generator per slot and each `c_j` taken as an integer. It is
+/// binding but not hiding, which the mask already takes care of, and commitments add up:
+/// the accepted clients' commitments sum to a commitment to the integer sum of their inputs.
+pub type InputCommitment = [u8; 32];
+
+/// Commit to the payload slots of a masked input.
+pub fn commit_masked_input(masked_input: &[u128]) -> InputCommitment {
+    commit(GENERATOR_DOMAIN, masked_input.iter().map(|&c| Scalar::from(c)), masked_input.len())
+}
+
+/// Commit to the seed a client shares with the committee, the same way as to its masked
+/// input but over generators of its own. The committee reconstructs the integer sum of the
+/// seeds, so the clients' seed commitments add up to a commitment to it.
+pub fn commit_seed(seed: &[u128]) -> InputCommitment {
+    commit(SEED_GENERATOR_DOMAIN, seed.iter().map(|&s| Scalar::from(s)), seed.len())
+}
+
+fn commit(domain: &[u8], values: impl Iterator<Item = Scalar>, len: usize) -> InputCommitment {
+    RistrettoPoint::vartime_multiscalar_mul(values, generators(domain, len)).compress().to_bytes()
+}
+
+/// Integers given as their residues mod 2^128 and how often they wrapped around.
+fn integers<'a>(residues: &'a [u128], carries: &'a [u32]) -> impl Iterator<Item = Scalar> + 'a {
+    let two_to_128 = Scalar::from(u128::MAX) + Scalar::ONE;
+    residues.iter().zip(carries).map(move |(&x, &carry)| Scalar::from(x) + Scalar::from(carry) * two_to_128)
+}
+
+/// Add up commitments, naming the client whose commitment is not a group element.
+fn sum_commitments<'a>(
+    commitments: impl Iterator<Item = (ClientId, &'a InputCommitment)>,
+) -> Result<InputCommitment, VerificationError> {
+    let mut sum = RistrettoPoint::identity();
+    for (client, commitment) in commitments {
+        sum += CompressedRistretto(*commitment)
+            .decompress()
+            .ok_or(VerificationError::MalformedCommitment { client })?;
+    }
+    Ok(sum.compress().to_bytes())
+}
+
+/// The first `len` generators of `domain`, hashed from their index so nobody knows
+/// relations between them.
+fn generators(domain: &[u8], len: usize) -> Vec<RistrettoPoint> {
+    (0..len as u64)
+        .map(|j| {
+            let hash: [u8; 64] = Sha512::new()
+                .chain_update(domain)
+                .chain_update(j.to_le_bytes())
+                .finalize()
+                .into();
+            RistrettoPoint::from_uniform_bytes(&hash)
+        })
+        .collect()
+}
+
+/// What a client commits to along with its submission: its masked input, and the seed
+/// that masks it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientCommitments {
+    pub input: InputCommitment,
+    pub seed: InputCommitment,
+}
+
+impl ClientCommitments {
+    /// Length of the encoding: the input commitment, then the seed commitment.
+    pub const LEN: usize = 64;
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+        bytes[..32].copy_from_slice(&self.input);
+        bytes[32..].copy_from_slice(&self.seed);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::LEN]) -> Self {
+        Self { input: bytes[..32].try_into().unwrap(), seed: bytes[32..].try_into().unwrap() }
+    }
+}
+
+/// A client in the aggregate, with the digest of its submission and its commitments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommittedClient {
+    pub id: ClientId,
+    #[serde(with = "hex_seed")]
+    pub digest: Commitment,
+    #[serde(with = "hex_seed")]
+    pub commitment: InputCommitment,
+    #[serde(with = "hex_seed")]
+    pub seed_commitment: InputCommitment,
+}
+
+/// What the server publishes with a round's aggregate so that anyone holding the public
+/// parameters can check it: the accepted clients and their commitments, the sum of their
+/// masked inputs, and the seed sum the committee reconstructed to unmask it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateProof {
+    pub round_id: RoundId,
+    /// The accepted clients, in client-set order.
+    pub clients: Vec<CommittedClient>,
+    pub payload_len: usize,
+    /// Z_{2^128} sum of the masked inputs over the payload slots.
+    pub masked_sum: Vec<u128>,
+    /// How often each slot of `masked_sum` wrapped around 2^128, which makes it the
+    /// integer sum the commitments add up to.
+    pub carries: Vec<u32>,
+    /// Sum of the clients' fresh seeds, which expands to the sum of their masks; never a
+    /// sum of registered keys, which would outlive the round.
+    pub seed_sum: Vec<u128>,
+    /// How often each coordinate of `seed_sum` wrapped around 2^128, which makes it the
+    /// integer sum the seed commitments add up to.
+    pub seed_carries: Vec<u32>,
+}
+
+impl AggregateProof {
+    /// The commitment the server listed for client `id`, if it was accepted.
+    pub fn commitment_of(&self, id: ClientId) -> Option<InputCommitment> {
+        self.clients.iter().find(|client| client.id == id).map(|client| client.commitment)
+    }
+
+    /// Check that `output` is what the listed clients' masked inputs sum and unmask to
+    /// under `params`: the commitments must add up to the published sums of the masked
+    /// inputs and of the seeds, and the sum minus the expanded seed sum must be an
+    /// aggregate of honest encodings that decodes to `output`.
+    pub fn verify(&self, params: &OPAPublicParams, output: &[u32]) -> Result<(), VerificationError> {
+        if self.masked_sum.len() != self.payload_len
+            || self.carries.len() != self.payload_len
+            || self.payload_len > OUTPUT_LEN
+        {
+            return Err(VerificationError::MalformedProof);
+        }
+
+        let committed = sum_commitments(self.clients.iter().map(|client| (client.id, &client.commitment)))?;
+        if committed != commit(GENERATOR_DOMAIN, integers(&self.masked_sum, &self.carries), self.payload_len) {
+            return Err(VerificationError::CommitmentsDoNotAddUp);
+        }
+        self.check_seed_sum()?;
+
+        let state = OPAState::from(params.clone());
+        let public_seed = state.public_seed_for(self.round_id);
+        let shprg = SeedHomomorphicPRG::new_from_both_seeds(public_seed, self.seed_sum.clone());
+        let unmasked: Vec<u128> = self
+            .masked_sum
+            .iter()
+            .zip(shprg.expand(OUTPUT_LEN))
+            .map(|(&c, m)| c.wrapping_sub(m))
+            .collect();
+        if let Err(RoundError::UnmaskingFailed { slot }) =
+            state.check_unmasked(&unmasked, self.payload_len, self.clients.len())
+        {
+            return Err(VerificationError::OutsideEncoding { slot });
+        }
+        if state.decode_output_with_len(unmasked, self.payload_len) != output {
+            return Err(VerificationError::OutputMismatch);
+        }
+        Ok(())
+    }
+
+    /// Check that the clients' seed commitments add up to the published seed sum. Without
+    /// this the server could publish any seed sum, and so make the sum unmask to anything.
+    pub fn check_seed_sum(&self) -> Result<(), VerificationError> {
+        if self.seed_carries.len() != self.seed_sum.len() {
+            return Err(VerificationError::MalformedProof);
+        }
+        let committed = sum_commitments(self.clients.iter().map(|client| (client.id, &client.seed_commitment)))?;
+        let seed_sum = integers(&self.seed_sum, &self.seed_carries);
+        if committed != commit(SEED_GENERATOR_DOMAIN, seed_sum, self.seed_sum.len()) {
+            return Err(VerificationError::SeedCommitmentsDoNotAddUp);
+        }
+        Ok(())
+    }
+}
+
+/// Why an aggregate does not check out against its proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    /// The proof's vectors do not match its payload length.
+    MalformedProof,
+    /// A listed commitment is not a group element.
+    MalformedCommitment { client: ClientId },
+    /// The commitments do not add up to the published sum of the masked inputs.
+    CommitmentsDoNotAddUp,
+    /// The seed commitments do not add up to the published seed sum.
+    SeedCommitmentsDoNotAddUp,
+    /// Unmasking leaves a slot where no aggregate of honest encodings lies.
+    OutsideEncoding { slot: usize },
+    /// Unmasking the published sum does not decode to the output.
+    OutputMismatch,
+    /// The client's own commitment is not among the accepted clients'.
+    NotIncluded,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::MalformedProof => {
+                write!(f, "the aggregate proof does not match its payload length")
+            }
+            VerificationError::MalformedCommitment { client } => {
+                write!(f, "the commitment of client {} is malformed", client)
+            }
+            VerificationError::CommitmentsDoNotAddUp => {
+                write!(f, "the client commitments do not add up to the published sum")
+            }
+            VerificationError::SeedCommitmentsDoNotAddUp => {
+                write!(f, "the seed commitments do not add up to the published seed sum")
+            }
+            VerificationError::OutsideEncoding { slot } => {
+                write!(f, "the published sum unmasks outside the encoding at slot {}", slot)
+            }
+            VerificationError::OutputMismatch => write!(f, "the published sum does not unmask to the output"),
+            VerificationError::NotIncluded => write!(f, "the client's input is not among the aggregated ones"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+impl From<VerificationError> for io::Error {
+    fn from(e: VerificationError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // test that commitments add up like the integer sum of the inputs, wrap-arounds included
+    fn test_input_commitments() {
+        let (a, b) = (vec![u128::MAX, 5, 7], vec![3, u128::MAX - 1, 9]);
+        let (sum, carries): (Vec<u128>, Vec<u32>) = a
+            .iter()
+            .zip(&b)
+            .map(|(&x, &y)| {
+                let (sum, wrapped) = x.overflowing_add(y);
+                (sum, wrapped as u32)
+            })
+            .unzip();
+        assert_eq!(carries, vec![1, 1, 0]);
+
+        let points = [commit_masked_input(&a), commit_masked_input(&b)]
+            .map(|commitment| CompressedRistretto(commitment).decompress().unwrap());
+        let integer_sum = integers(&sum, &carries);
+        assert_eq!((points[0] + points[1]).compress().to_bytes(), commit(GENERATOR_DOMAIN, integer_sum, 3));
+        // the wrapped sum alone is a different value
+        assert_ne!((points[0] + points[1]).compress().to_bytes(), commit_masked_input(&sum));
+        assert_ne!(commit_masked_input(&a), commit_masked_input(&[u128::MAX, 5, 8]));
+        // seeds are committed over other generators
+        assert_ne!(commit_seed(&a), commit_masked_input(&a));
+    }
+}
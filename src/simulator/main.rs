@@ -1,36 +1,360 @@
-use jagguar::protocols::opa::{OPA, OPASetupParameters};
+use ed25519_dalek::SigningKey;
+use jagguar::communicator::Communicator;
+use jagguar::crypto::prg::default_prg;
+use jagguar::protocols::client::Client;
+use jagguar::protocols::opa::server::OPAState;
+use jagguar::protocols::opa::{NormBound, OPA, OPAClient, OPAHost, OPAServer, OPASetupParameters, RoundPhase};
 use jagguar::simulator::Simulator;
+use jagguar::simulator::simulator::INPUT_LEN;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+
+const COMMITTEE_SIZE: usize = 9;
+const RECONSTRUCTION_THRESHOLD: usize = 5;
+const CORRUPTION_THRESHOLD: usize = RECONSTRUCTION_THRESHOLD - 1;
+const COMMITTEE_GRACE_PERIOD: Duration = Duration::from_secs(1);
+/// How long the server waits for a committee that lost too many members.
+const COMMITTEE_TIMEOUT: Duration = Duration::from_secs(10);
+const MIN_PARTICIPANTS: usize = 2;
+/// How long clients have to submit before the server closes input collection.
+const INPUT_WINDOW: Duration = Duration::from_secs(5);
+/// Upper bound on how long any phase of the round may take before the simulation gives up.
+const PHASE_TIMEOUT: Duration = Duration::from_secs(120);
+/// Port of the listener shared by all sessions in the multi-session scenario.
+const HOST_PORT: u16 = 9000;
+/// Clients of session i take ports from SESSION_CLIENT_PORTS + 1000 * i on.
+const SESSION_CLIENT_PORTS: u16 = 20000;
+/// Ports the submissions admission control must turn away come from.
+const INTRUDER_PORTS: u16 = 30000;
+/// L2 bound of the norm-bound scenario; inputs below 2^20 in every coordinate stay within it.
+const INPUT_NORM_BOUND: u32 = 1 << 25;
+
+/// Knobs varied between simulator scenarios.
+struct Scenario {
+    num_clients: usize,
+    /// Clients that only start after the input deadline and must be rejected.
+    late_clients: usize,
+    /// Server-side input deadline; without one, inputs close once every client has submitted.
+    input_deadline: Option<Duration>,
+    /// Committee members killed mid-round.
+    committee_dropouts: usize,
+    /// Consecutive rounds run against the same server setup.
+    rounds: u64,
+    /// Clients share one long-term key and rejoin every round instead of fresh clients.
+    key_homomorphic: bool,
+    /// Independent sessions hosted side by side on one listener.
+    sessions: usize,
+    /// The server and every committee member crash once the committee has aggregated, and
+    /// the round finishes from their checkpoints.
+    crash: bool,
+    /// The committee is drawn from the clients with public randomness instead of being
+    /// separate parties, and the drawn clients serve on it themselves.
+    committee_from_clients: bool,
+    /// Clients enroll identities and sign their submissions, and submissions from an
+    /// unregistered client or a second one under the same identity are rejected.
+    admission_control: bool,
+    /// Clients commit to their masked inputs, and an auditor checks the aggregate against
+    /// the proof the server publishes with it.
+    verifiable: bool,
+    /// Clients prove their inputs in range, and the server checks the aggregate against the
+    /// proofs.
+    range_proof_bits: Option<u32>,
+    /// Clients also prove their inputs within a norm bound.
+    norm_bound: Option<NormBound>,
+}
 
 fn main() {
+    // pick the scenario from the command line, e.g. `cargo simulator -- committee-dropout`
+    // - default: every client and committee member participates
+    // - committee-dropout: all but reconstruction_threshold members die mid-round
+    // - committee-failure: too many members die and the round produces no output
+    // - late-clients: extra clients arrive after the deadline and are rejected
+    // - too-few-clients: fewer than min_participants clients submit and the round aborts
+    // - multi-round: several rounds run back to back on one server, each with fresh clients
+    // - key-homomorphic: the same clients rejoin every round, sharing their masking key only once
+    // - multi-session: several sessions on one listener collect and aggregate at the same time
+    // - crash-recovery: the server and committee crash mid-round and resume from checkpoints
+    // - client-committee: the committee is drawn from the clients by a hash of their ids and
+    //   the drawn clients take on committee duty
+    // - admission: clients enroll identity keys and get admitted to the round; an unregistered
+    //   client and an identity submitting twice are turned away
+    // - verifiable: clients commit to their inputs and an auditor checks the aggregate's proof
+    // - range-proofs: clients prove every input word in 32 bits before the server counts it
+    // - norm-bound: clients additionally prove the L2 norm of their input within a bound
+    let name = std::env::args().nth(1).unwrap_or_else(|| "default".to_string());
+    let default = Scenario {
+        num_clients: 25,
+        late_clients: 0,
+        input_deadline: None,
+        committee_dropouts: 0,
+        rounds: 1,
+        key_homomorphic: false,
+        sessions: 1,
+        crash: false,
+        committee_from_clients: false,
+        admission_control: false,
+        verifiable: false,
+        range_proof_bits: None,
+        norm_bound: None,
+    };
+    let scenario = match name.as_str() {
+        "default" => default,
+        "committee-dropout" => Scenario {
+            committee_dropouts: COMMITTEE_SIZE - RECONSTRUCTION_THRESHOLD,
+            ..default
+        },
+        "committee-failure" => Scenario {
+            committee_dropouts: COMMITTEE_SIZE - RECONSTRUCTION_THRESHOLD + 1,
+            ..default
+        },
+        "late-clients" => Scenario { late_clients: 3, input_deadline: Some(INPUT_WINDOW), ..default },
+        "too-few-clients" => Scenario { num_clients: MIN_PARTICIPANTS - 1, ..default },
+        "multi-round" => Scenario { rounds: 3, ..default },
+        "key-homomorphic" => Scenario { rounds: 3, key_homomorphic: true, ..default },
+        "multi-session" => Scenario { sessions: 3, ..default },
+        "crash-recovery" => Scenario { crash: true, ..default },
+        "client-committee" => Scenario { committee_from_clients: true, ..default },
+        "admission" => Scenario { admission_control: true, ..default },
+        "verifiable" => Scenario { verifiable: true, ..default },
+        "range-proofs" => Scenario { range_proof_bits: Some(32), ..default },
+        "norm-bound" => Scenario {
+            range_proof_bits: Some(32),
+            norm_bound: Some(NormBound::L2(INPUT_NORM_BOUND)),
+            ..default
+        },
+        other => {
+            eprintln!("Unknown scenario '{}'", other);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = server_parameters(&scenario).validate() {
+        eprintln!("Invalid parameters for scenario '{}': {}", name, e);
+        std::process::exit(1);
+    }
+    println!("Running scenario '{}'", name);
+    if scenario.sessions > 1 {
+        run_sessions(&scenario);
+        return;
+    }
+
     // create the simulator
 	let mut sim: Simulator<OPA<u32>> = Simulator::new();
 
+    let checkpoint_dir = std::env::temp_dir().join(format!("jagguar-simulator-{}", std::process::id()));
+    if scenario.crash {
+        sim.set_checkpoint_dir(&checkpoint_dir);
+    }
+
     // create the (single) server
-    // TODO: don't depend on OPA parameters explicitly
-    let server_parameters = OPASetupParameters::new(40, 5, 5, 9);
-    sim.start_server(server_parameters);
+    if scenario.committee_from_clients {
+        sim.draw_committee_from_clients(scenario.num_clients, None);
+    }
+    sim.start_server(server_parameters(&scenario));
+    let mut round = sim.server_state().unwrap().round.clone();
+
+    for round_id in 0..scenario.rounds {
+        // the first round starts with the server; later ones reuse its setup
+        if round_id > 0 {
+            let state = OPAServer::start_round(sim.server_state().unwrap(), round_id)
+                .expect("could not start round");
+            sim.set_server_state(state);
+        }
 
-    // create many clients
-    let num_clients = 25;
-    sim.start_clients(num_clients);
+        // create many clients, or bring back the previous round's
+        let num_clients = scenario.num_clients;
+        if scenario.key_homomorphic && round_id > 0 {
+            sim.restart_clients();
+        } else if scenario.committee_from_clients {
+            sim.start_parties(num_clients);
+        } else {
+            sim.start_clients(num_clients);
+        }
 
-    // collect all client inputs from the channel
-    // loop over all received messages and add them to a list
-    let client_inputs = sim.collect_client_inputs(num_clients);
-    println!("Collected {} client inputs", client_inputs.len());
+        // collect all client inputs from the channel
+        // loop over all received messages and add them to a list
+        let client_inputs = sim.collect_client_inputs(num_clients);
+        println!("Collected {} client inputs", client_inputs.len());
 
-    println!("Running the simulator...");
+        println!("Running the simulator...");
 
-    // let the simulator run for 5 seconds
-    std::thread::sleep(std::time::Duration::from_secs(5));
+        // wait for every client to get a verdict from the server
+        sim.wait_for_clients();
 
-    // connect the committee members, disjoint from clients
-    sim.start_committee();
+        // clients arriving after the deadline are turned away and not part of the expected sum
+        if scenario.late_clients > 0 {
+            round.wait_for(RoundPhase::InputsClosed, PHASE_TIMEOUT).expect("input deadline never passed");
+            sim.start_clients(scenario.late_clients);
+            sim.wait_for_clients();
+        }
 
-    std::thread::sleep(std::time::Duration::from_secs(1));
+        // submissions without credentials, or repeating an identity, must not be counted
+        if scenario.admission_control && !attempt_intrusions(sim.server_state().unwrap()) {
+            eprintln!("Admission control let an intruding submission through!");
+        }
 
-    sim.output();
+        // fix the client set before the committee fetches its shares (a no-op after the deadline)
+        sim.close_inputs();
+
+        if scenario.crash {
+            // every member goes down right after aggregating, then the server does too
+            sim.start_committee_with_dropouts(COMMITTEE_SIZE);
+            sim.wait_for_committee();
+            sim.restart_server(server_parameters(&scenario));
+            round = sim.server_state().unwrap().round.clone();
+            sim.resume_committee();
+        } else {
+            // connect the committee members, killing some of them mid-round
+            sim.start_committee_with_dropouts(scenario.committee_dropouts);
+        }
+
+        // the server finishes the round once enough committee members report and the grace period ends
+        match round.wait_for(RoundPhase::Done, PHASE_TIMEOUT) {
+            Ok(phase) => println!("Round finished: {}", phase),
+            Err(e) => eprintln!("Round did not finish: {}", e),
+        }
+        if scenario.verifiable {
+            audit(sim.server_state().unwrap());
+        }
+
+        sim.output();
+    }
 
     // teardown the simulator
     sim.teardown();
+    if scenario.crash {
+        let _ = std::fs::remove_dir_all(&checkpoint_dir);
+    }
+}
+
+// TODO: don't depend on OPA parameters explicitly
+fn server_parameters(scenario: &Scenario) -> OPASetupParameters {
+    let mut server_parameters = OPASetupParameters::new(
+        40,
+        CORRUPTION_THRESHOLD as u64,
+        RECONSTRUCTION_THRESHOLD as u64,
+        COMMITTEE_SIZE as u64,
+    )
+    .with_committee_grace_period(COMMITTEE_GRACE_PERIOD)
+    .with_committee_timeout(COMMITTEE_TIMEOUT)
+    .with_min_participants(MIN_PARTICIPANTS as u64)
+    .with_key_homomorphic_masks(scenario.key_homomorphic)
+    .with_admission_control(scenario.admission_control)
+    .with_verifiable_aggregation(scenario.verifiable);
+    if let Some(deadline) = scenario.input_deadline {
+        server_parameters = server_parameters.with_input_deadline(deadline);
+    }
+    if let Some(bits) = scenario.range_proof_bits {
+        server_parameters = server_parameters.with_range_proofs(bits);
+    }
+    if let Some(bound) = scenario.norm_bound {
+        server_parameters = server_parameters.with_norm_bound(bound);
+    }
+    server_parameters
+}
+
+/// Submit what admission control must turn away, returning whether it did: a client that
+/// never enrolled, and an enrolled identity submitting again from another port. The one
+/// submission that does count is all zeros, so the expected sum stays the same.
+fn attempt_intrusions(state: &OPAState) -> bool {
+    let client = |state: OPAState| {
+        let mut client = OPAClient::<u32>::new();
+        client.set_server_state(state);
+        client.set_input(vec![0; INPUT_LEN]);
+        client.encrypt_input();
+        client
+    };
+    let rejected = |what: &str, result: io::Result<()>| match result {
+        Ok(()) => {
+            eprintln!("Server accepted {}", what);
+            false
+        }
+        Err(e) => {
+            println!("Server rejected {}: {}", what, e);
+            true
+        }
+    };
+
+    // a client that skips enrollment and sends its submission without credentials
+    let unregistered = client(OPAState { admission_control: false, ..state.clone() });
+    let mut all_rejected = rejected("an unregistered client", unregistered.submit(INTRUDER_PORTS));
+
+    // an enrolled client submits once, then its identity tries again from another port
+    let key = SigningKey::generate(&mut default_prg());
+    let mut enrolled = client(state.clone());
+    enrolled.set_identity(key.clone());
+    enrolled
+        .admit(INTRUDER_PORTS + 1)
+        .and_then(|()| enrolled.submit(INTRUDER_PORTS + 1))
+        .expect("an admitted client was rejected");
+    let mut duplicate = client(state.clone());
+    duplicate.set_identity(key);
+    let resubmitted = duplicate.admit(INTRUDER_PORTS + 2).and_then(|()| duplicate.submit(INTRUDER_PORTS + 2));
+    all_rejected &= rejected("a second submission under the same identity", resubmitted);
+    all_rejected
+}
+
+/// Check the round's aggregate against the proof the server published with it, as an
+/// auditor holding only the public parameters would.
+fn audit(state: &OPAState) {
+    let aggregate = match state.round.outcome(state.round_id, PHASE_TIMEOUT) {
+        Ok(aggregate) => aggregate,
+        Err(e) => return eprintln!("No aggregate to audit: {}", e),
+    };
+    let Some(proof) = state.aggregate_proof() else {
+        return eprintln!("The server published no proof with the aggregate!");
+    };
+    match proof.verify(&state.public_params(), &aggregate) {
+        Ok(()) => println!("Audited the aggregate against the commitments of {} clients", proof.clients.len()),
+        Err(e) => eprintln!("The aggregate does not match its proof: {}", e),
+    }
+}
+
+/// Host several independent sessions on one listener and run a round of each side by side:
+/// every session collects its clients' inputs before any committee starts aggregating.
+fn run_sessions(scenario: &Scenario) {
+    let mut communicator = Communicator::new(HOST_PORT);
+    let shutdown = Arc::new(AtomicBool::new(false));
+    communicator.set_shutdown_flag(Arc::clone(&shutdown));
+    communicator.start_server().expect("failed to start server");
+    let host = OPAHost::new(&communicator);
+    let listener = std::thread::spawn(move || communicator.listen_loop());
+
+    // one simulator per session, each with its own client ports
+    let mut sims = Vec::new();
+    for i in 0..scenario.sessions {
+        let (output_sender, output_receiver) = mpsc::channel();
+        let state = host
+            .open_session(i as u64 + 1, server_parameters(scenario), Some(output_sender))
+            .expect("could not open session");
+        let mut sim: Simulator<OPA<u32>> = Simulator::with_ports(SESSION_CLIENT_PORTS + 1000 * i as u16, 1000);
+        let committee_port_offsets = state.committee_port_offsets.clone();
+        sim.attach_server(HOST_PORT, state, committee_port_offsets, output_receiver);
+        sims.push(sim);
+    }
+
+    for sim in sims.iter_mut() {
+        sim.start_clients(scenario.num_clients);
+    }
+    for sim in sims.iter_mut() {
+        let client_inputs = sim.collect_client_inputs(scenario.num_clients);
+        println!("Collected {} client inputs", client_inputs.len());
+        sim.wait_for_clients();
+    }
+    for sim in sims.iter_mut() {
+        sim.close_inputs();
+        sim.start_committee();
+    }
+
+    for (session_id, sim) in host.session_ids().into_iter().zip(sims.iter_mut()) {
+        match sim.server_state().unwrap().round.wait_for(RoundPhase::Done, PHASE_TIMEOUT) {
+            Ok(phase) => println!("Session {} finished: {}", session_id, phase),
+            Err(e) => eprintln!("Session {} did not finish: {}", session_id, e),
+        }
+        sim.output();
+    }
+
+    shutdown.store(true, Ordering::Relaxed);
+    listener.join().unwrap().expect("failed to listen");
 }
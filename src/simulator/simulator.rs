@@ -2,18 +2,28 @@ use crate::protocols::Protocol;
 use crate::protocols::server::Server;
 use crate::protocols::client::Client;
 use crate::protocols::committee::Committee;
+use crate::protocols::party::Party;
 use crate::crypto::prg::{default_prg, populate_random};
 use crate::simulator::port_pool::PortPool;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::mpsc;
+use std::thread::JoinHandle;
 
 const STARTING_PORT: u16 = 10000;
 const DEFAULT_MAX_PORTS: usize = 1000;
-const INPUT_LEN: usize = 1024;
+/// Length of every simulated client's input.
+pub const INPUT_LEN: usize = 1024;
 
 pub struct Simulator<P: Protocol> {
 	server_shutdown: Option<Arc<AtomicBool>>,
+	/// Thread running the server's listen loop, joined when the server is restarted
+	server_thread: Option<JoinHandle<()>>,
+	/// Directory the server and committee checkpoint to, if they do
+	checkpoint_dir: Option<PathBuf>,
+	/// Port the server listens on; committee ports are offsets from it
+	server_port: u16,
 	server_state: Option<<P::Server as Server>::State>,
 	committee_port_offsets: Option<Vec<u16>>,
 	/// Optional channel used by the server to send its final output back to the simulator
@@ -24,24 +34,106 @@ pub struct Simulator<P: Protocol> {
 	expected_output: Option<Vec<u64>>,
 	/// Port pool for managing port allocation and status
 	port_pool: PortPool,
-	_marker: core::marker::PhantomData<(P, P::Server, P::Client, P::Committee)>,
+	/// Client threads that have not been waited on yet, handing back the client and its port
+	client_handles: Vec<JoinHandle<(u16, P::Client)>>,
+	/// Clients that finished submitting, kept so they can take part in later rounds
+	returned_clients: Vec<(u16, P::Client)>,
+	/// Party threads that have not finished submitting yet
+	party_handles: Vec<JoinHandle<P::Party>>,
+	/// Parties that finished submitting, kept so the drawn ones can serve on the committee
+	parties: Vec<P::Party>,
+	/// Committee threads that have not been waited on yet, handing back the party if a
+	/// party served
+	committee_handles: Vec<JoinHandle<Option<P::Party>>>,
+	/// Ports reserved for the clients the next server draws its committee from, and the
+	/// beacon it draws with
+	committee_population: Option<(Vec<u16>, Option<[u8; 32]>)>,
+	/// Reserved client ports not handed to a client yet
+	reserved_client_ports: Vec<u16>,
+	_marker: core::marker::PhantomData<P>,
+}
+
+impl<P: Protocol> Default for Simulator<P> {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl<P: Protocol> Simulator<P> {
 	pub fn new() -> Self {
+		Self::with_ports(STARTING_PORT, DEFAULT_MAX_PORTS)
+	}
+
+	/// A simulator whose server and clients take ports from `max_ports` ports starting at
+	/// `starting_port`, so several simulators can run side by side.
+	pub fn with_ports(starting_port: u16, max_ports: usize) -> Self {
 		Self {
 			server_shutdown: Some(Arc::new(AtomicBool::new(false))),
+			server_thread: None,
+			checkpoint_dir: None,
+			server_port: starting_port,
 			server_state: None,
 			committee_port_offsets: None,
 			server_output: None,
 			client_input_channel: None,
 			expected_output: None,
-			port_pool: PortPool::new(STARTING_PORT, DEFAULT_MAX_PORTS),
+			port_pool: PortPool::new(starting_port, max_ports),
+			client_handles: Vec::new(),
+			returned_clients: Vec::new(),
+			party_handles: Vec::new(),
+			parties: Vec::new(),
+			committee_handles: Vec::new(),
+			committee_population: None,
+			reserved_client_ports: Vec::new(),
 			_marker: core::marker::PhantomData,
 		}
 	}
 
+	/// Have the server and committee members started from now on checkpoint under `dir`.
+	pub fn set_checkpoint_dir(&mut self, dir: &Path) {
+		self.checkpoint_dir = Some(dir.to_path_buf());
+	}
+
+	/// Have the next server started draw its committee from `num_clients` clients instead
+	/// of separate parties: their ports are reserved now and taken by the next clients
+	/// started, and the committee members run on the drawn clients' ports, inside the
+	/// clients if they are started as parties.
+	pub fn draw_committee_from_clients(&mut self, num_clients: usize, beacon: Option<[u8; 32]>) {
+		let ports: Vec<u16> = (0..num_clients).map(|_| self.port_pool.allocate_port().unwrap()).collect();
+		self.reserved_client_ports = ports.clone();
+		self.committee_population = Some((ports, beacon));
+	}
+
 	pub fn start_server(&mut self, server_parameters: <P::Server as Server>::SetupParameters)
+	where
+		P::Server: Send + 'static,
+		<P::Server as Server>::SetupParameters: Send + 'static,
+		<P::Server as Server>::State: Send,
+	{
+		let port = self.port_pool.allocate_port().unwrap();
+		self.run_server(port, server_parameters);
+	}
+
+	/// Crash the server and start a new one with `server_parameters` on the same port, which
+	/// resumes from the checkpoint directory if one is set.
+	pub fn restart_server(&mut self, server_parameters: <P::Server as Server>::SetupParameters)
+	where
+		P::Server: Send + 'static,
+		<P::Server as Server>::SetupParameters: Send + 'static,
+		<P::Server as Server>::State: Send,
+	{
+		self.teardown();
+		if let Some(handle) = self.server_thread.take()
+			&& handle.join().is_err()
+		{
+			eprintln!("The server thread panicked");
+		}
+		println!("Server on port {} crashed; restarting", self.server_port);
+		self.server_shutdown = Some(Arc::new(AtomicBool::new(false)));
+		self.run_server(self.server_port, server_parameters);
+	}
+
+	fn run_server(&mut self, port: u16, server_parameters: <P::Server as Server>::SetupParameters)
 	where
 		P::Server: Send + 'static,
 		<P::Server as Server>::SetupParameters: Send + 'static,
@@ -60,17 +152,38 @@ impl<P: Protocol> Simulator<P> {
 		let mut server = P::Server::new(server_parameters);
 		// allow the server to use the output channel if it chooses to
 		server.set_output_channel(output_sender);
-		self.committee_port_offsets = Some(server.get_committee_port_offsets());
-
+		if let Some(ref dir) = self.checkpoint_dir {
+			server.set_checkpoint_dir(dir).expect("Failed to open the checkpoint directory");
+		}
+		if let Some((ref ports, beacon)) = self.committee_population {
+			let population: Vec<u64> = ports.iter().map(|&port| port as u64).collect();
+			server.select_committee(&population, beacon).expect("Failed to draw the committee");
+		}
 		// run the server in a thread
-		let port = self.port_pool.allocate_port().unwrap();
-		std::thread::spawn(move || {
+		self.server_port = port;
+		self.server_thread = Some(std::thread::spawn(move || {
 			server.setup_communicator(port, shutdown, state_sender);
-		});
+		}));
 
 		// wait for server to be ready and receive the state
 		let state = state_receiver.recv().expect("Server failed to start");
+		self.committee_port_offsets = Some(P::Server::committee_port_offsets(&state));
+		self.server_state = Some(state);
+	}
+
+	/// Drive a server that already runs elsewhere, e.g. one session of a shared listener on
+	/// `port`, instead of starting one.
+	pub fn attach_server(
+		&mut self,
+		port: u16,
+		state: <P::Server as Server>::State,
+		committee_port_offsets: Vec<u16>,
+		output: mpsc::Receiver<Vec<u32>>,
+	) {
+		self.server_port = port;
 		self.server_state = Some(state);
+		self.committee_port_offsets = Some(committee_port_offsets);
+		self.server_output = Some(output);
 	}
 
 	pub fn start_clients(&mut self, num_clients: usize)
@@ -79,27 +192,85 @@ impl<P: Protocol> Simulator<P> {
 		P::Client: Send + 'static,
 		P::Input: From<u32>,
 	{
-		// create a channel for clients to send their inputs back to the simulator
+		// create the clients, on the reserved ports first
+		let clients = (0..num_clients)
+			.map(|_| {
+				let port = self.reserved_client_ports.pop().or_else(|| self.port_pool.allocate_port()).unwrap();
+				(port, P::Client::new())
+			})
+			.collect();
+		self.run_clients(clients);
+
+		println!("{} clients started", num_clients);
+	}
+
+	/// Start `num_parties` parties, on the reserved ports first, which submit an input like
+	/// clients and take on committee duty if the server draws them.
+	pub fn start_parties(&mut self, num_parties: usize)
+	where
+		<P::Server as Server>::State: Clone + Send + 'static,
+		P::Party: Send + 'static,
+		P::Input: From<u32>,
+	{
 		let (input_sender, input_receiver) = mpsc::channel();
 		self.client_input_channel = Some(input_receiver);
 
-		// create the clients
-		for _ in 0..num_clients {
-			let port = self.port_pool.allocate_port().unwrap();
+		for _ in 0..num_parties {
+			let port = self.reserved_client_ports.pop().or_else(|| self.port_pool.allocate_port()).unwrap();
+			let mut party = P::Party::new(port);
+			party.set_server_state(self.server_state.as_ref().unwrap().clone());
+			if let Some(ref dir) = self.checkpoint_dir {
+				party.set_checkpoint_dir(dir).expect("Failed to open the checkpoint directory");
+			}
+			let sender = input_sender.clone();
+
+			let handle = std::thread::spawn(move || {
+				let input = random_input();
+				let _ = sender.send(input.clone());
+				party.set_input(input.into_iter().map(|x| x.into()).collect());
+				party.encrypt_input();
+				party.send_input();
+				party
+			});
+			self.party_handles.push(handle);
+		}
+
+		println!("{} parties started", num_parties);
+	}
+
+	/// Run every client that has finished so far again, on the same port, with a fresh
+	/// input and the current server state.
+	pub fn restart_clients(&mut self)
+	where
+		<P::Server as Server>::State: Clone + Send + 'static,
+		P::Client: Send + 'static,
+		P::Input: From<u32>,
+	{
+		let clients = std::mem::take(&mut self.returned_clients);
+		let num_clients = clients.len();
+		self.run_clients(clients);
 
-			let mut client = P::Client::new();
+		println!("{} clients restarted", num_clients);
+	}
+
+	fn run_clients(&mut self, clients: Vec<(u16, P::Client)>)
+	where
+		<P::Server as Server>::State: Clone + Send + 'static,
+		P::Client: Send + 'static,
+		P::Input: From<u32>,
+	{
+		// create a channel for clients to send their inputs back to the simulator
+		let (input_sender, input_receiver) = mpsc::channel();
+		self.client_input_channel = Some(input_receiver);
+
+		for (port, mut client) in clients {
 			client.set_server_state(self.server_state.as_ref().unwrap().clone());
 			let sender = input_sender.clone();
 
-			std::thread::spawn(move || {
+			let handle = std::thread::spawn(move || {
 				// generate a random input
-				let mut rng = default_prg();
-				let mut input = vec![0u32; INPUT_LEN];
+				let input = random_input();
 
-				// generate random input, masked to the range [0, 2^20)
-				populate_random(&mut input, &mut rng);
-				input = input.iter().map(|x| x % (1 << 20) as u32).collect();
-				
 				// send the input to the simulator (non-blocking for unbounded channel)
 				let _ = sender.send(input.clone());
 				
@@ -114,16 +285,63 @@ impl<P: Protocol> Simulator<P> {
 				// send the input to the server
 				// the port is automatically passed by value, so this is thread-safe
 				client.send_input(port);
+				(port, client)
 			});
+			self.client_handles.push(handle);
 		}
+	}
 
-		println!("{} clients started", num_clients);
+	/// Block until every client and party started so far has finished submitting (or been
+	/// rejected).
+	pub fn wait_for_clients(&mut self) {
+		for handle in self.client_handles.drain(..) {
+			match handle.join() {
+				Ok(client) => self.returned_clients.push(client),
+				Err(_) => eprintln!("A client thread panicked"),
+			}
+		}
+		for handle in self.party_handles.drain(..) {
+			match handle.join() {
+				Ok(party) => self.parties.push(party),
+				Err(_) => eprintln!("A party thread panicked"),
+			}
+		}
+	}
+
+	/// The public server state handed to clients and committee members.
+	pub fn server_state(&self) -> Option<&<P::Server as Server>::State> {
+		self.server_state.as_ref()
+	}
+
+	/// Replace the server state handed to clients and committee members started from now
+	/// on, e.g. with the state of a new round.
+	pub fn set_server_state(&mut self, state: <P::Server as Server>::State) {
+		self.server_state = Some(state);
+	}
+
+	/// Close the server's input phase so every committee member sums the same client set.
+	pub fn close_inputs(&self) {
+		let server_state = self.server_state.as_ref().expect("Server state is not set");
+		P::Server::close_inputs(server_state);
 	}
 
 	pub fn start_committee(&mut self)
 	where
 		<P::Server as Server>::State: Clone + Send + 'static,
 		P::Committee: Send + 'static,
+		P::Party: Send + 'static,
+	{
+		self.start_committee_with_dropouts(0);
+	}
+
+	/// Start the committee, killing the last `dropouts` members mid-round: they fetch and
+	/// aggregate their shares but never send an output back to the server. Members whose
+	/// port a party submitted from serve inside that party.
+	pub fn start_committee_with_dropouts(&mut self, dropouts: usize)
+	where
+		<P::Server as Server>::State: Clone + Send + 'static,
+		P::Committee: Send + 'static,
+		P::Party: Send + 'static,
 	{
 		// make sure server state and port offsets are set
 		assert!(self.server_state.is_some(), "Server state is not set");
@@ -132,19 +350,86 @@ impl<P: Protocol> Simulator<P> {
 		let server_state = self.server_state.as_ref().unwrap();
 		let port_offsets = self.committee_port_offsets.as_ref().unwrap();
 
-		for (_, port_offset) in port_offsets.iter().enumerate() {
-			let port = STARTING_PORT + *port_offset;
+		let survivors = port_offsets.len().saturating_sub(dropouts);
+		for (i, port_offset) in port_offsets.iter().enumerate() {
+			let port = self.server_port.wrapping_add(*port_offset);
+			let drops_out = i >= survivors;
+
+			// a party drawn onto the committee serves on its own connection
+			if let Some(index) = self.parties.iter().position(|party| party.port() == port && party.on_committee()) {
+				let mut party = self.parties.swap_remove(index);
+				let handle = std::thread::spawn(move || {
+					party.retrieve_inputs();
+					party.aggregate();
+					if drops_out {
+						println!("Party on port {} dropped out before sending its committee output", port);
+					} else {
+						party.send_output();
+					}
+					Some(party)
+				});
+				self.committee_handles.push(handle);
+				continue;
+			}
 
 			// make a new committee member and set its server state
 			let mut committee_member = P::Committee::new(port);
 			committee_member.set_server_state(server_state.clone());
+			if let Some(ref dir) = self.checkpoint_dir {
+				committee_member.set_checkpoint_dir(dir).expect("Failed to open the checkpoint directory");
+			}
 
 			// run the committee member in a thread
-			std::thread::spawn(move || {
+			let handle = std::thread::spawn(move || {
 				committee_member.retrieve_inputs();
 				committee_member.aggregate();
+				if drops_out {
+					println!("Committee member on port {} dropped out before sending its output", port);
+					return None;
+				}
 				committee_member.send_output();
+				None
 			});
+			self.committee_handles.push(handle);
+		}
+	}
+
+	/// Restart every committee member on its port from its checkpoint, sending the output
+	/// share it had aggregated before it went down.
+	pub fn resume_committee(&mut self)
+	where
+		<P::Server as Server>::State: Clone + Send + 'static,
+		P::Committee: Send + 'static,
+		P::Party: Send + 'static,
+	{
+		let dir = self.checkpoint_dir.as_ref().expect("Checkpoint directory is not set");
+		let server_state = self.server_state.as_ref().expect("Server state is not set");
+		let port_offsets = self.committee_port_offsets.as_ref().expect("Committee port offsets are not set");
+
+		for port_offset in port_offsets {
+			let port = self.server_port.wrapping_add(*port_offset);
+			let mut committee_member = P::Committee::new(port);
+			committee_member.set_server_state(server_state.clone());
+			committee_member.set_checkpoint_dir(dir).expect("Failed to open the checkpoint directory");
+			if !committee_member.resume() {
+				println!("Committee member on port {} has nothing to resume", port);
+				continue;
+			}
+			let handle = std::thread::spawn(move || {
+				committee_member.send_output();
+				None
+			});
+			self.committee_handles.push(handle);
+		}
+	}
+
+	/// Block until every committee member started so far has finished its part of the round.
+	pub fn wait_for_committee(&mut self) {
+		for handle in self.committee_handles.drain(..) {
+			match handle.join() {
+				Ok(party) => self.parties.extend(party),
+				Err(_) => eprintln!("A committee thread panicked"),
+			}
 		}
 	}
 
@@ -233,3 +518,11 @@ impl<P: Protocol> Simulator<P> {
 		self.server_shutdown.as_ref().unwrap().store(true, Ordering::Relaxed);
 	}
 }
+
+/// A random input with values in [0, 2^20).
+fn random_input() -> Vec<u32> {
+	let mut rng = default_prg();
+	let mut input = vec![0u32; INPUT_LEN];
+	populate_random(&mut input, &mut rng);
+	input.iter().map(|x| x % (1 << 20) as u32).collect()
+}
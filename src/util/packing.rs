@@ -13,7 +13,7 @@ where
     }
 
     let compression_factor = 32 / bitwidth;
-    let mut out = Vec::with_capacity((vector.len() + compression_factor - 1) / compression_factor);
+    let mut out = Vec::with_capacity(vector.len().div_ceil(compression_factor));
 
     let mut acc = 0u32;
     let mut count = 0;
@@ -79,7 +79,7 @@ mod tests {
         assert!(packed.len() < input.len());
 
         // unpack the input and check that the original size is restored
-        let unpacked = unpack_vector(&packed);
+        let unpacked: Vec<u16> = unpack_vector(&packed);
         assert!(unpacked.len() == input.len());
 
         // check that the unpacked input is the same as the original input
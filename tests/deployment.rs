@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use jagguar::crypto::DecryptionKey;
+use jagguar::deploy::{DataFormat, OutputMetadata};
+
+const SERVER_PORT: u16 = 18631;
+const CLIENT_PORTS: [u16; 3] = [18641, 18642, 18643];
+const COMMITTEE_SIZE: u16 = 3;
+
+// kills the processes still running when the test fails part way
+struct Processes(Vec<Child>);
+
+impl Processes {
+    fn spawn(&mut self, binary: &str, config: &Path) -> usize {
+        let child = Command::new(binary).arg(config).spawn().expect("failed to launch binary");
+        self.0.push(child);
+        self.0.len() - 1
+    }
+
+    fn wait(&mut self, index: usize, timeout: Duration) -> ExitStatus {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.0[index].try_wait().unwrap() {
+                return status;
+            }
+            assert!(Instant::now() < deadline, "process did not exit in time");
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for Processes {
+    fn drop(&mut self) {
+        for child in &mut self.0 {
+            let _ = child.kill();
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// the secret committee member `offset` opens its shares with
+fn member_secret(offset: u16) -> [u8; 32] {
+    [offset as u8; 32]
+}
+
+fn write_config(dir: &Path, name: &str, role_fields: &str) -> PathBuf {
+    let path = dir.join(format!("{}.json", name));
+    let config = format!(
+        r#"{{
+            "server": "127.0.0.1:{}",
+            "public_params": "{}",
+            "timeout_ms": 300000{}
+        }}"#,
+        SERVER_PORT,
+        dir.join("params.json").display(),
+        role_fields,
+    );
+    fs::write(&path, config).unwrap();
+    path
+}
+
+#[test]
+// test that a server, clients and committee members running as separate processes aggregate the inputs
+fn test_separate_processes() {
+    let dir = std::env::temp_dir().join(format!("jagguar-deployment-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("aggregate.csv");
+    let mut processes = Processes(Vec::new());
+
+    // clients seal every share to its committee member
+    let committee_keys: Vec<String> = (1..=COMMITTEE_SIZE)
+        .map(|offset| {
+            let key = DecryptionKey::from_bytes(member_secret(offset)).public_key();
+            format!(r#"{{ "port": {}, "key": "{}" }}"#, SERVER_PORT + offset, hex(&key))
+        })
+        .collect();
+    let server_config = write_config(&dir, "server", &format!(
+        r#", "output": "{}",
+        "protocol": {{ "corruption_threshold": 1, "reconstruction_threshold": 2, "committee_size": {},
+                       "min_participants": 2, "committee_grace_period_ms": 200,
+                       "committee_keys": [{}], "verifiable_aggregation": true,
+                       "range_proof_bits": 16, "norm_bound": {{ "l2": 2000 }} }}"#,
+        output.display(),
+        COMMITTEE_SIZE,
+        committee_keys.join(", "),
+    ));
+    let server = processes.spawn(env!("CARGO_BIN_EXE_jagguar-server"), &server_config);
+
+    let inputs: Vec<Vec<u32>> = (0..CLIENT_PORTS.len() as u32).map(|i| vec![i, 10 * i, 1000, 7 + i]).collect();
+    let clients: Vec<usize> = CLIENT_PORTS
+        .iter()
+        .zip(&inputs)
+        .zip(["csv", "bin", "npy"])
+        .map(|((port, input), extension)| {
+            // every client reads its input in another format
+            let input_path = dir.join(format!("input-{}.{}", port, extension));
+            fs::write(&input_path, DataFormat::from_path(&input_path).encode(input)).unwrap();
+            let config = write_config(&dir, &format!("client-{}", port), &format!(
+                r#", "port": {}, "input": "{}""#,
+                port,
+                input_path.display(),
+            ));
+            processes.spawn(env!("CARGO_BIN_EXE_jagguar-client"), &config)
+        })
+        .collect();
+    for client in clients {
+        assert!(processes.wait(client, Duration::from_secs(300)).success(), "client failed");
+    }
+
+    // the first member to connect closes the inputs, so the committee starts after the clients
+    let members: Vec<usize> = (1..=COMMITTEE_SIZE)
+        .map(|offset| {
+            let port = SERVER_PORT + offset;
+            let config = write_config(&dir, &format!("committee-{}", port), &format!(
+                r#", "port": {}, "decryption_key": "{}""#,
+                port,
+                hex(&member_secret(offset)),
+            ));
+            processes.spawn(env!("CARGO_BIN_EXE_jagguar-committee"), &config)
+        })
+        .collect();
+    for member in members {
+        assert!(processes.wait(member, Duration::from_secs(120)).success(), "committee member failed");
+    }
+    assert!(processes.wait(server, Duration::from_secs(120)).success(), "server failed");
+
+    let expected: Vec<u32> = (0..4).map(|j| inputs.iter().map(|input| input[j]).sum()).collect();
+    assert_eq!(DataFormat::Csv.decode(&fs::read(&output).unwrap()).unwrap(), expected);
+    let metadata = OutputMetadata::from_file(&OutputMetadata::path(&output)).unwrap();
+    assert_eq!((metadata.round_id, metadata.contributors, metadata.length), (0, CLIENT_PORTS.len(), 4));
+    assert_eq!(metadata.params.committee_size, COMMITTEE_SIZE as u64);
+    // anyone holding the aggregate and its metadata can check it against the commitments
+    let proof = metadata.proof.expect("verifiable aggregation publishes a proof");
+    assert_eq!(proof.clients.len(), CLIENT_PORTS.len());
+    proof.verify(&metadata.params, &expected).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+}